@@ -0,0 +1,296 @@
+//! Circuit breaker for AppView calls.
+//!
+//! While the AppView is degraded, waiting out every call's timeout only piles
+//! up connections. Once enough recent calls fail, the circuit opens and calls
+//! fail at once. After a cool-off it half-opens: a single probe call is let
+//! through, and its outcome closes the circuit or opens it again.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tracing::{info, warn};
+
+/// Calls older than this no longer count towards the error rate
+const WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy)]
+pub struct BreakerSettings {
+    /// Share of recent calls that must fail to open the circuit (0 disables it)
+    pub error_rate: f64,
+    /// Calls needed in the window before the error rate is trusted
+    pub min_calls: usize,
+    /// How long the circuit stays open before a probe is let through
+    pub open_for: Duration,
+}
+
+impl BreakerSettings {
+    /// A breaker that never opens.
+    pub fn disabled() -> Self {
+        Self {
+            error_rate: 0.0,
+            min_calls: 0,
+            open_for: Duration::ZERO,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+impl CircuitState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CircuitState::Closed => "closed",
+            CircuitState::Open => "open",
+            CircuitState::HalfOpen => "half_open",
+        }
+    }
+}
+
+enum State {
+    /// Recent calls and whether each failed
+    Closed {
+        calls: VecDeque<(Instant, bool)>,
+    },
+    Open {
+        until: Instant,
+    },
+    HalfOpen {
+        probing: bool,
+    },
+}
+
+pub struct CircuitBreaker {
+    settings: BreakerSettings,
+    state: Mutex<State>,
+}
+
+/// Permission to make one call. Call [`Ticket::start`] once the call is
+/// sent and report its outcome with [`Ticket::finish`]. A ticket dropped
+/// after starting counts as a failure: the call was cut off (by a timeout or
+/// a cancelled request) before the AppView answered. One dropped before
+/// starting counts for nothing, and if it was a probe the next call probes.
+pub struct Ticket<'a> {
+    breaker: &'a CircuitBreaker,
+    probe: bool,
+    started: bool,
+    finished: bool,
+}
+
+impl CircuitBreaker {
+    pub fn new(settings: BreakerSettings) -> Self {
+        Self {
+            settings,
+            state: Mutex::new(State::Closed {
+                calls: VecDeque::new(),
+            }),
+        }
+    }
+
+    fn enabled(&self) -> bool {
+        self.settings.error_rate > 0.0
+    }
+
+    /// Ask to make a call. Fails while the circuit is open, or half-open with
+    /// a probe already in flight.
+    pub fn admit(&self, now: Instant) -> Option<Ticket<'_>> {
+        let ticket = |probe| Ticket {
+            breaker: self,
+            probe,
+            started: false,
+            finished: false,
+        };
+        if !self.enabled() {
+            return Some(ticket(false));
+        }
+
+        let mut state = self.state.lock().unwrap();
+        match &mut *state {
+            State::Closed { .. } => Some(ticket(false)),
+            State::Open { until } if now < *until => None,
+            State::Open { .. } => {
+                info!("upstream circuit half-open; probing");
+                *state = State::HalfOpen { probing: true };
+                Some(ticket(true))
+            }
+            State::HalfOpen { probing: true } => None,
+            State::HalfOpen { probing } => {
+                *probing = true;
+                Some(ticket(true))
+            }
+        }
+    }
+
+    fn record(&self, probe: bool, failed: bool, now: Instant) {
+        if !self.enabled() {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        match &mut *state {
+            State::Closed { calls } if !probe => {
+                calls.push_back((now, failed));
+                while calls
+                    .front()
+                    .is_some_and(|(at, _)| now.duration_since(*at) > WINDOW)
+                {
+                    calls.pop_front();
+                }
+                let failures = calls.iter().filter(|(_, failed)| *failed).count();
+                if calls.len() >= self.settings.min_calls.max(1)
+                    && failures as f64 / calls.len() as f64 >= self.settings.error_rate
+                {
+                    warn!(
+                        calls = calls.len(),
+                        failures,
+                        open_seconds = self.settings.open_for.as_secs(),
+                        "upstream circuit open; failing fast"
+                    );
+                    *state = State::Open {
+                        until: now + self.settings.open_for,
+                    };
+                }
+            }
+            State::HalfOpen { .. } if probe => {
+                if failed {
+                    warn!("upstream probe failed; circuit open again");
+                    *state = State::Open {
+                        until: now + self.settings.open_for,
+                    };
+                } else {
+                    info!("upstream probe succeeded; circuit closed");
+                    *state = State::Closed {
+                        calls: VecDeque::new(),
+                    };
+                }
+            }
+            // Calls admitted before the circuit last changed state
+            _ => {}
+        }
+    }
+
+    pub fn state(&self, now: Instant) -> CircuitState {
+        match &*self.state.lock().unwrap() {
+            State::Closed { .. } => CircuitState::Closed,
+            State::Open { until } if now < *until => CircuitState::Open,
+            State::Open { .. } | State::HalfOpen { .. } => CircuitState::HalfOpen,
+        }
+    }
+}
+
+impl Ticket<'_> {
+    pub fn start(&mut self) {
+        self.started = true;
+    }
+
+    pub fn finish(mut self, failed: bool) {
+        self.finished = true;
+        self.breaker.record(self.probe, failed, Instant::now());
+    }
+
+    /// End a call whose outcome says nothing about the AppView's health,
+    /// such as one it turned away with a 429. Nothing is recorded, and if it
+    /// was a probe the next call probes.
+    pub fn skip(mut self) {
+        self.finished = true;
+        self.release_probe();
+    }
+
+    fn release_probe(&self) {
+        if self.probe {
+            if let State::HalfOpen { probing } = &mut *self.breaker.state.lock().unwrap() {
+                *probing = false;
+            }
+        }
+    }
+}
+
+impl Drop for Ticket<'_> {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+        if self.started {
+            self.breaker.record(self.probe, true, Instant::now());
+        } else {
+            self.release_probe();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opens_and_recovers_through_a_probe() {
+        let breaker = CircuitBreaker::new(BreakerSettings {
+            error_rate: 0.5,
+            min_calls: 4,
+            open_for: Duration::from_secs(30),
+        });
+        let start = Instant::now();
+
+        for failed in [false, true, true] {
+            breaker.admit(start).unwrap();
+            breaker.record(false, failed, start);
+        }
+        // Too few calls to judge yet
+        assert_eq!(breaker.state(start), CircuitState::Closed);
+        breaker.record(false, false, start);
+        assert_eq!(breaker.state(start), CircuitState::Open);
+        assert!(breaker.admit(start + Duration::from_secs(29)).is_none());
+
+        // After the cool-off exactly one probe goes through
+        let later = start + Duration::from_secs(30);
+        let probe = breaker.admit(later).unwrap();
+        assert!(breaker.admit(later).is_none());
+        assert_eq!(breaker.state(later), CircuitState::HalfOpen);
+
+        // A probe dropped before it was sent hands over to the next call
+        drop(probe);
+        let probe = breaker.admit(later).unwrap();
+        assert!(breaker.admit(later).is_none());
+
+        // So does one that was rate limited
+        probe.skip();
+        assert_eq!(breaker.state(later), CircuitState::HalfOpen);
+        let probe = breaker.admit(later).unwrap();
+        probe.finish(false);
+        assert_eq!(breaker.state(later), CircuitState::Closed);
+        assert!(breaker.admit(later).is_some());
+    }
+
+    #[test]
+    fn test_failed_probe_reopens() {
+        let breaker = CircuitBreaker::new(BreakerSettings {
+            error_rate: 1.0,
+            min_calls: 1,
+            open_for: Duration::from_secs(10),
+        });
+        let start = Instant::now();
+        breaker.record(false, true, start);
+        assert_eq!(breaker.state(start), CircuitState::Open);
+
+        let later = start + Duration::from_secs(10);
+        let probe = breaker.admit(later).unwrap();
+        probe.finish(true);
+        assert_eq!(breaker.state(later), CircuitState::Open);
+
+        // A probe cut off after it was sent counts as failed
+        let later = later + Duration::from_secs(10);
+        let mut probe = breaker.admit(later).unwrap();
+        probe.start();
+        drop(probe);
+        assert_eq!(breaker.state(Instant::now()), CircuitState::Open);
+
+        // Disabled breakers never open
+        let disabled = CircuitBreaker::new(BreakerSettings::disabled());
+        disabled.record(false, true, start);
+        assert_eq!(disabled.state(start), CircuitState::Closed);
+    }
+}
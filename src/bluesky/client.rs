@@ -1,22 +1,49 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use atrium_api::app::bsky::feed::defs::{
-    PostViewEmbedRefs, ThreadViewPost, ThreadViewPostParentRefs, ThreadViewPostRepliesItem,
+    FeedViewPostReasonRefs, PostView, PostViewEmbedRefs, ReplyRefRootRefs, ThreadViewPost,
+    ThreadViewPostParentRefs, ThreadViewPostRepliesItem,
 };
-use atrium_api::app::bsky::feed::get_post_thread::{OutputThreadRefs, ParametersData};
+use atrium_api::app::bsky::feed::get_post_thread::{self, OutputThreadRefs, ParametersData};
 use atrium_api::client::AtpServiceClient;
 use atrium_api::types::Union;
-use atrium_xrpc_client::reqwest::ReqwestClient;
+use atrium_xrpc_client::reqwest::{ReqwestClient, ReqwestClientBuilder};
 use chrono::{DateTime, Utc};
+use futures::{StreamExt, TryStreamExt};
 use thiserror::Error;
-use tracing::warn;
+use tokio::sync::Semaphore;
+use tracing::{debug, field, info_span, instrument, warn, Instrument, Span};
 
+use crate::cache::Cache;
+use crate::metrics::{metrics, observe_upstream};
+
+use super::breaker::{BreakerSettings, CircuitBreaker, CircuitState};
+use super::singleflight::SingleFlight;
 use super::types::{
-    AspectRatio, Author, Embed, EmbedExternal, EmbedImage, EmbedRecord, EmbedVideo, StreamEvent,
-    Thread, ThreadPost,
+    AspectRatio, Author, AuthorThreadsPage, Embed, EmbedExternal, EmbedImage, EmbedRecord,
+    EmbedVideo, Profile, StreamEvent, Thread, ThreadPost, ThreadSummary,
 };
 
+/// Author feed items requested per profile page.
+const AUTHOR_FEED_PAGE_SIZE: u8 = 30;
+
+/// Reply depth inspected when counting the posts in a listed thread.
+/// Deeper chains are reported as "N+ posts" rather than walked in full.
+const THREAD_COUNT_DEPTH: u16 = 25;
+
+/// Threads counted at once for one profile page.
+const THREAD_COUNT_CONCURRENCY: usize = 4;
+
+/// Reply depth loaded per call when following a thread forward from a known post.
+/// New posts usually arrive a few at a time, so one call almost always suffices.
+const FOLLOW_DEPTH: u16 = 10;
+
+/// Maximum number of URIs `getPosts` accepts per call.
+const GET_POSTS_BATCH: usize = 25;
+
 /// Parse a `createdAt` field from a JSON value, falling back to UNIX_EPOCH with a warning.
 fn parse_created_at(value: &serde_json::Value, context: &str) -> DateTime<Utc> {
     value
@@ -30,10 +57,11 @@ fn parse_created_at(value: &serde_json::Value, context: &str) -> DateTime<Utc> {
         })
 }
 
-#[derive(Error, Debug)]
+// Clone so that callers sharing a coalesced call each get its error
+#[derive(Error, Debug, Clone)]
 pub enum ClientError {
     #[error("HTTP error: {0}")]
-    Http(#[from] reqwest::Error),
+    Http(Arc<reqwest::Error>),
     #[error("post not found")]
     NotFound,
     #[error("post is blocked")]
@@ -44,6 +72,83 @@ pub enum ClientError {
     Api(String),
     #[error("invalid response structure")]
     InvalidResponse,
+    #[error("Bluesky is having trouble right now")]
+    CircuitOpen,
+}
+
+impl ClientError {
+    /// Short label for metrics and span attributes.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ClientError::Http(_) => "http",
+            ClientError::NotFound => "not_found",
+            ClientError::Blocked => "blocked",
+            ClientError::RateLimited => "rate_limited",
+            ClientError::Api(_) => "api",
+            ClientError::InvalidResponse => "invalid_response",
+            ClientError::CircuitOpen => "circuit_open",
+        }
+    }
+
+    /// Whether the error says the AppView itself is unwell, as opposed to
+    /// the request being for something missing or malformed. A 429 is
+    /// backpressure rather than an outage, and is not counted either way.
+    fn is_outage(&self) -> bool {
+        match self {
+            ClientError::Http(_) => true,
+            ClientError::Api(msg) => {
+                msg.starts_with("http client error") || msg.starts_with("xrpc response error: 5")
+            }
+            _ => false,
+        }
+    }
+}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(err: reqwest::Error) -> Self {
+        ClientError::Http(Arc::new(err))
+    }
+}
+
+/// Record an upstream call's outcome as the `result` field of the current span.
+fn record_result<T>(result: Result<T, ClientError>) -> Result<T, ClientError> {
+    let outcome = match &result {
+        Ok(_) => "ok",
+        Err(e) => e.kind(),
+    };
+    Span::current().record("result", outcome);
+    result
+}
+
+/// Wait for an upstream permit, then make the call. The wait is recorded as
+/// `queue_ms` on an `upstream` span around the call. Fails fast with
+/// `CircuitOpen`, before queueing, while `breaker` is open.
+async fn call_upstream<T>(
+    permits: &Semaphore,
+    breaker: &CircuitBreaker,
+    method: &'static str,
+    call: impl Future<Output = Result<T, ClientError>>,
+) -> Result<T, ClientError> {
+    let span = info_span!("upstream", method, queue_ms = field::Empty);
+    async move {
+        let queued = Instant::now();
+        let mut ticket = breaker.admit(queued).ok_or(ClientError::CircuitOpen)?;
+        let _permit = permits
+            .acquire()
+            .await
+            .expect("upstream semaphore is never closed");
+        Span::current().record("queue_ms", queued.elapsed().as_millis() as u64);
+        // From here a timed-out or cancelled call counts against the breaker
+        ticket.start();
+        let result = observe_upstream(method, call).await;
+        match &result {
+            Err(ClientError::RateLimited) => ticket.skip(),
+            result => ticket.finish(result.as_ref().is_err_and(ClientError::is_outage)),
+        }
+        result
+    }
+    .instrument(span)
+    .await
 }
 
 fn map_api_error(err_str: String) -> ClientError {
@@ -54,6 +159,15 @@ fn map_api_error(err_str: String) -> ClientError {
     }
 }
 
+/// Like `map_api_error`, but unknown actors map to `NotFound`.
+fn map_profile_error(err_str: String) -> ClientError {
+    if err_str.contains("Profile not found") || err_str.contains("Actor not found") {
+        ClientError::NotFound
+    } else {
+        map_api_error(err_str)
+    }
+}
+
 fn extract_images_from_view(
     images: &[atrium_api::app::bsky::embed::images::ViewImage],
 ) -> Vec<EmbedImage> {
@@ -84,21 +198,143 @@ fn extract_aspect_ratio(ar: &atrium_api::app::bsky::embed::defs::AspectRatio) ->
     }
 }
 
+/// Limits on the upstream work done to load one thread page. A fetch that
+/// reaches either limit stops early and returns the posts it has, marked as
+/// truncated. `None` means no limit.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FetchBudget {
+    pub max_posts: Option<usize>,
+    pub max_duration: Option<Duration>,
+}
+
+impl FetchBudget {
+    /// Build a budget from config values, where 0 means no limit.
+    pub fn new(max_posts: usize, max_duration_seconds: u64) -> Self {
+        Self {
+            max_posts: Some(max_posts).filter(|&n| n > 0),
+            max_duration: Some(Duration::from_secs(max_duration_seconds)).filter(|d| !d.is_zero()),
+        }
+    }
+
+    /// Start spending the budget on a fetch.
+    pub fn start(&self) -> FetchLimit {
+        FetchLimit {
+            max_posts: self.max_posts,
+            deadline: self.max_duration.map(|d| tokio::time::Instant::now() + d),
+        }
+    }
+}
+
+/// A [`FetchBudget`] being spent by one fetch.
+#[derive(Debug, Clone, Copy)]
+pub struct FetchLimit {
+    max_posts: Option<usize>,
+    deadline: Option<tokio::time::Instant>,
+}
+
+impl FetchLimit {
+    /// Whether another post may be fetched after `fetched` posts.
+    pub fn allows(&self, fetched: usize) -> bool {
+        self.max_posts.is_none_or(|max| fetched < max)
+            && self
+                .deadline
+                .is_none_or(|deadline| tokio::time::Instant::now() < deadline)
+    }
+
+    /// The same limit with `fetched` posts already spent.
+    pub fn spend(self, fetched: usize) -> Self {
+        Self {
+            max_posts: self.max_posts.map(|max| max.saturating_sub(fetched)),
+            ..self
+        }
+    }
+
+    /// Run an upstream call, giving up with `None` once the deadline passes.
+    pub async fn run<F: std::future::Future>(&self, call: F) -> Option<F::Output> {
+        match self.deadline {
+            Some(deadline) => tokio::time::timeout_at(deadline, call).await.ok(),
+            None => Some(call.await),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct BlueskyClient {
     client: Arc<AtpServiceClient<ReqwestClient>>,
+    budget: FetchBudget,
+    /// Caps concurrent XRPC calls across every clone of this client
+    upstream: Arc<Semaphore>,
+    /// Fails calls fast while the AppView is failing, shared like `upstream`
+    breaker: Arc<CircuitBreaker>,
+    /// In-flight `getPostThread` calls, keyed by URI, depth and parent height
+    thread_calls: Arc<SingleFlight<get_post_thread::Output>>,
+    /// In-flight `resolveHandle` calls, keyed by handle
+    handle_calls: Arc<SingleFlight<String>>,
+    /// Consulted by `get_thread_by_handle` only
+    cache: Option<Cache>,
 }
 
 impl BlueskyClient {
-    pub fn new(base_url: &str, _timeout: Duration) -> Result<Self, ClientError> {
-        // Note: timeout is currently unused as ReqwestClient uses its own defaults.
-        // This parameter is kept for future configuration needs.
-        let xrpc_client = ReqwestClient::new(base_url);
+    /// A client for the AppView at `base_url`. Each XRPC request fails after
+    /// `timeout`.
+    pub fn new(base_url: &str, timeout: Duration) -> Result<Self, ClientError> {
+        let http = reqwest::Client::builder().timeout(timeout).build()?;
+        let xrpc_client = ReqwestClientBuilder::new(base_url).client(http).build();
         let client = Arc::new(AtpServiceClient::new(xrpc_client));
 
-        Ok(Self { client })
+        Ok(Self {
+            client,
+            budget: FetchBudget::default(),
+            upstream: Arc::new(Semaphore::new(Semaphore::MAX_PERMITS)),
+            breaker: Arc::new(CircuitBreaker::new(BreakerSettings::disabled())),
+            thread_calls: Arc::new(SingleFlight::new()),
+            handle_calls: Arc::new(SingleFlight::new()),
+            cache: None,
+        })
+    }
+
+    /// Allow at most `max` XRPC calls in flight at once (0 for no limit);
+    /// further calls queue. The limit is shared with clones made afterwards.
+    pub fn with_upstream_concurrency(mut self, max: usize) -> Self {
+        let permits = if max == 0 {
+            Semaphore::MAX_PERMITS
+        } else {
+            max
+        };
+        self.upstream = Arc::new(Semaphore::new(permits));
+        self
+    }
+
+    /// Put XRPC calls behind a circuit breaker with `settings`. Disabled by
+    /// default. The breaker is shared with clones made afterwards.
+    pub fn with_circuit_breaker(mut self, settings: BreakerSettings) -> Self {
+        self.breaker = Arc::new(CircuitBreaker::new(settings));
+        self
+    }
+
+    /// Limit the thread page fetches (`get_thread` and `get_thread_streaming`)
+    /// made through this client. Unlimited by default.
+    pub fn with_fetch_budget(mut self, budget: FetchBudget) -> Self {
+        self.budget = budget;
+        self
     }
 
+    /// Serve `get_thread_by_handle` from `cache` when possible, and store
+    /// what it fetches there.
+    pub fn with_cache(mut self, cache: Cache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub fn fetch_budget(&self) -> FetchBudget {
+        self.budget
+    }
+
+    pub fn circuit_state(&self) -> CircuitState {
+        self.breaker.state(Instant::now())
+    }
+
+    #[instrument(skip_all, fields(handle = %handle, result = field::Empty))]
     pub async fn resolve_handle(&self, handle: &str) -> Result<String, ClientError> {
         let params = atrium_api::com::atproto::identity::resolve_handle::ParametersData {
             handle: handle
@@ -106,71 +342,113 @@ impl BlueskyClient {
                 .map_err(|_| ClientError::Api("invalid handle".to_string()))?,
         };
 
-        let result = self
-            .client
-            .service
-            .com
-            .atproto
-            .identity
-            .resolve_handle(params.into())
-            .await
-            .map_err(|e| map_api_error(e.to_string()))?;
+        let client = self.client.clone();
+        let upstream = self.upstream.clone();
+        let breaker = self.breaker.clone();
+        let call = async move {
+            let output = call_upstream(
+                &upstream,
+                &breaker,
+                "com.atproto.identity.resolveHandle",
+                async {
+                    client
+                        .service
+                        .com
+                        .atproto
+                        .identity
+                        .resolve_handle(params.into())
+                        .await
+                        .map_err(|e| map_api_error(e.to_string()))
+                },
+            )
+            .await?;
+            Ok(output.did.to_string())
+        };
 
-        Ok(result.did.to_string())
+        record_result(self.handle_calls.run(handle.to_string(), call).await)
     }
 
+    /// Fetch a whole thread from any of its posts. The walk up to the root
+    /// and the chain down from it share the fetch budget; when it runs out
+    /// the thread is marked truncated. A walk cut off before the root starts
+    /// the thread at the highest post reached.
     pub async fn get_thread(&self, at_uri: &str) -> Result<Thread, ClientError> {
+        let limit = self.budget.start();
         // First, find the root by walking up parents with API calls
-        let root_uri = self.find_root_uri_async(at_uri).await?;
-
-        // Get author DID from the root post
-        let root_view = self.fetch_post_thread_shallow(&root_uri).await?;
-        let author_did = root_view.post.author.did.to_string();
-        let author = self.extract_author(&root_view)?;
-
-        // Now iteratively follow self-replies, making API calls as needed
-        let mut posts = vec![self.extract_post(&root_view)?];
-        let mut current_uri = match self.find_self_reply(&root_view, &author_did) {
-            Some(uri) => uri,
-            None => return Ok(Thread { posts, author }),
-        };
+        let (root_view, walked) = self.find_root_async(at_uri, &limit).await?;
+        // The root is counted again as the first post of the chain
+        let limit = limit.spend(walked - 1);
+        let root = self.extract_post(&root_view)?;
+        let thread = self.follow_chain(root_view, vec![root], limit).await?;
+        metrics().record_thread_posts(thread.posts.len());
+        Ok(thread)
+    }
 
-        loop {
-            let view = self.fetch_post_thread_shallow(&current_uri).await?;
-            posts.push(self.extract_post(&view)?);
+    /// Fetch the part of a thread after `at_uri` (exclusive), within the
+    /// fetch budget. Used to load more of a truncated thread.
+    pub async fn get_thread_after(&self, at_uri: &str) -> Result<Thread, ClientError> {
+        let limit = self.budget.start();
+        let view = self.fetch_post_thread_shallow(at_uri).await?;
+        self.follow_chain(view, Vec::new(), limit).await
+    }
 
-            // Find the self-reply in this post's replies
-            match self.find_self_reply(&view, &author_did) {
-                Some(reply_uri) => {
-                    current_uri = reply_uri;
-                }
-                None => break,
+    /// Iteratively follow self-replies from `view`, one API call per post,
+    /// appending them to `posts` until the chain ends or `limit` is reached.
+    async fn follow_chain(
+        &self,
+        mut view: ThreadViewPost,
+        mut posts: Vec<ThreadPost>,
+        limit: FetchLimit,
+    ) -> Result<Thread, ClientError> {
+        let author_did = view.post.author.did.to_string();
+        let author = self.extract_author(&view)?;
+
+        while let Some(reply_uri) = self.find_self_reply(&view, &author_did) {
+            if !limit.allows(posts.len()) {
+                return Ok(Thread::truncated(posts, author));
             }
+            view = match limit.run(self.fetch_post_thread_shallow(&reply_uri)).await {
+                Some(result) => result?,
+                None => return Ok(Thread::truncated(posts, author)),
+            };
+            posts.push(self.extract_post(&view)?);
         }
 
-        Ok(Thread { posts, author })
+        Ok(Thread::new(posts, author))
     }
 
-    /// Find the root URI by walking up parents with individual API calls.
+    /// Find the root by walking up parents with individual API calls.
     /// This avoids stack overflow from deeply nested response structures.
-    async fn find_root_uri_async(&self, start_uri: &str) -> Result<String, ClientError> {
-        let mut current_uri = start_uri.to_string();
-
-        loop {
-            let view = self.fetch_post_thread_shallow(&current_uri).await?;
-            let author_did = view.post.author.did.as_str();
+    /// Returns the root's view and the number of posts fetched on the way,
+    /// stopping early at the highest post reached once `limit` runs out.
+    #[instrument(
+        skip_all,
+        fields(at_uri = %start_uri, root_uri = field::Empty, result = field::Empty)
+    )]
+    async fn find_root_async(
+        &self,
+        start_uri: &str,
+        limit: &FetchLimit,
+    ) -> Result<(ThreadViewPost, usize), ClientError> {
+        let mut view = record_result(self.fetch_post_thread_shallow(start_uri).await)?;
+        let mut fetched = 1;
 
-            // Check if there's a parent by the same author
-            match self.get_parent_uri_if_same_author(&view, author_did) {
-                Some(parent_uri) => {
-                    current_uri = parent_uri;
-                }
-                None => {
-                    // No more parents by this author, current is the root
-                    return Ok(current_uri);
-                }
+        // Check if there's a parent by the same author
+        while let Some(parent_uri) =
+            self.get_parent_uri_if_same_author(&view, view.post.author.did.as_str())
+        {
+            if !limit.allows(fetched) {
+                break;
             }
+            view = match limit.run(self.fetch_post_thread_shallow(&parent_uri)).await {
+                Some(result) => record_result(result)?,
+                None => break,
+            };
+            fetched += 1;
         }
+
+        Span::current().record("root_uri", view.post.uri.as_str());
+        Ok((view, fetched))
     }
 
     /// Get parent URI if the parent is by the same author
@@ -193,22 +471,41 @@ impl BlueskyClient {
 
     /// Fetch a single post with shallow context (1 parent, 1 reply level).
     /// This prevents stack overflow from deeply nested response structures.
+    #[instrument(skip_all, fields(at_uri = %at_uri, result = field::Empty))]
     async fn fetch_post_thread_shallow(&self, at_uri: &str) -> Result<ThreadViewPost, ClientError> {
+        record_result(self.fetch_post_thread(at_uri, 1, 1).await)
+    }
+
+    async fn fetch_post_thread(
+        &self,
+        at_uri: &str,
+        depth: u16,
+        parent_height: u16,
+    ) -> Result<ThreadViewPost, ClientError> {
         let params = ParametersData {
             uri: at_uri.to_string(),
-            depth: Some(1.try_into().unwrap()),
-            parent_height: Some(1.try_into().unwrap()),
+            depth: Some(depth.try_into().unwrap()),
+            parent_height: Some(parent_height.try_into().unwrap()),
         };
 
-        let result = self
-            .client
-            .service
-            .app
-            .bsky
-            .feed
-            .get_post_thread(params.into())
+        let client = self.client.clone();
+        let upstream = self.upstream.clone();
+        let breaker = self.breaker.clone();
+        let call = async move {
+            call_upstream(&upstream, &breaker, "app.bsky.feed.getPostThread", async {
+                client
+                    .service
+                    .app
+                    .bsky
+                    .feed
+                    .get_post_thread(params.into())
+                    .await
+                    .map_err(|e| map_api_error(e.to_string()))
+            })
             .await
-            .map_err(|e| map_api_error(e.to_string()))?;
+        };
+        let key = format!("{} {} {}", at_uri, depth, parent_height);
+        let result = self.thread_calls.run(key, call).await?;
 
         match result.thread.clone() {
             Union::Refs(OutputThreadRefs::AppBskyFeedDefsThreadViewPost(view)) => Ok(*view),
@@ -224,25 +521,349 @@ impl BlueskyClient {
 
     /// Find the URI of the first self-reply in the post's replies
     fn find_self_reply(&self, view: &ThreadViewPost, author_did: &str) -> Option<String> {
+        self.find_self_reply_view(view, author_did)
+            .map(|reply_view| reply_view.post.uri.clone())
+    }
+
+    /// Find the first self-reply in the post's replies
+    fn find_self_reply_view<'a>(
+        &self,
+        view: &'a ThreadViewPost,
+        author_did: &str,
+    ) -> Option<&'a ThreadViewPost> {
         let replies = view.replies.as_ref()?;
         for reply in replies {
             if let Union::Refs(ThreadViewPostRepliesItem::ThreadViewPost(reply_view)) = reply {
                 if reply_view.post.author.did.as_str() == author_did {
-                    return Some(reply_view.post.uri.clone());
+                    return Some(reply_view);
                 }
             }
         }
         None
     }
 
+    /// Fetch the self-reply chain starting at `at_uri` (inclusive), oldest
+    /// first, without walking the thread from its root. Takes one
+    /// `getPostThread` call per `FOLLOW_DEPTH` posts in the chain.
+    pub async fn get_thread_from(&self, at_uri: &str) -> Result<Vec<ThreadPost>, ClientError> {
+        self.clone()
+            .stream_thread_from(at_uri.to_string())
+            .try_collect()
+            .await
+    }
+
+    /// Stream the self-reply chain starting at `at_uri` (inclusive), yielding
+    /// each batch of `FOLLOW_DEPTH` posts as soon as it is fetched.
+    pub fn stream_thread_from(
+        self,
+        at_uri: String,
+    ) -> impl futures::Stream<Item = Result<ThreadPost, ClientError>> {
+        async_stream::try_stream! {
+            let mut view = self.fetch_post_thread(&at_uri, FOLLOW_DEPTH, 0).await?;
+            let author_did = view.post.author.did.to_string();
+            yield self.extract_post(&view)?;
+
+            loop {
+                let mut depth = 0;
+                let mut current = &view;
+                while let Some(reply) = self.find_self_reply_view(current, &author_did) {
+                    yield self.extract_post(reply)?;
+                    current = reply;
+                    depth += 1;
+                }
+
+                // Replies below the loaded depth are not included; continue from the deepest post
+                if depth < FOLLOW_DEPTH {
+                    break;
+                }
+                let next_uri = current.post.uri.clone();
+                view = self.fetch_post_thread(&next_uri, FOLLOW_DEPTH, 0).await?;
+            }
+        }
+    }
+
+    /// Fetch the current versions of posts by URI, in batches.
+    /// Deleted posts (and posts the AppView will not show) are absent from the result.
+    pub async fn get_posts(&self, uris: &[String]) -> Result<Vec<ThreadPost>, ClientError> {
+        let batches = uris.chunks(GET_POSTS_BATCH).map(|chunk| {
+            let params = atrium_api::app::bsky::feed::get_posts::ParametersData {
+                uris: chunk.to_vec(),
+            };
+            call_upstream(
+                &self.upstream,
+                &self.breaker,
+                "app.bsky.feed.getPosts",
+                async move {
+                    self.client
+                        .service
+                        .app
+                        .bsky
+                        .feed
+                        .get_posts(params.into())
+                        .await
+                        .map_err(|e| map_api_error(e.to_string()))
+                },
+            )
+        });
+
+        let outputs = futures::future::try_join_all(batches).await?;
+
+        outputs
+            .iter()
+            .flat_map(|output| output.posts.iter())
+            .map(|post| self.extract_post_view(post))
+            .collect()
+    }
+
+    /// Re-fetch a thread the caller already has, given its posts' URIs in order.
+    ///
+    /// Known posts are refreshed in batches and deleted ones dropped; the chain
+    /// is then followed forward from the last post that still exists. Posts cut
+    /// off from the root by a deleted post are kept. Returns `None` when none of
+    /// the known posts exist any more.
+    pub async fn refresh_thread(
+        &self,
+        known_uris: &[String],
+    ) -> Result<Option<Vec<ThreadPost>>, ClientError> {
+        let mut fresh = self.get_posts(known_uris).await?;
+        let mut posts: Vec<ThreadPost> = known_uris
+            .iter()
+            .filter_map(|uri| {
+                let idx = fresh.iter().position(|p| &p.uri == uri)?;
+                Some(fresh.swap_remove(idx))
+            })
+            .collect();
+
+        // The chain starts with another fresh copy of the last post
+        let Some(last) = posts.pop() else {
+            return Ok(None);
+        };
+        posts.extend(self.get_thread_from(&last.uri).await?);
+        Ok(Some(posts))
+    }
+
     pub async fn get_thread_by_handle(
         &self,
         handle: &str,
         post_id: &str,
     ) -> Result<Thread, ClientError> {
-        let did = self.resolve_handle(handle).await?;
+        let Some(cache) = &self.cache else {
+            let did = self.resolve_handle(handle).await?;
+            let at_uri = format!("at://{}/app.bsky.feed.post/{}", did, post_id);
+            return self.get_thread(&at_uri).await;
+        };
+        if let Some(thread) = cache.thread(handle, post_id).await {
+            return Ok(thread);
+        }
+
+        let did = match cache.did(handle).await {
+            Some(did) => did,
+            None => {
+                let did = self.resolve_handle(handle).await?;
+                cache.put_did(handle, &did).await;
+                did
+            }
+        };
         let at_uri = format!("at://{}/app.bsky.feed.post/{}", did, post_id);
-        self.get_thread(&at_uri).await
+        let thread = self.get_thread(&at_uri).await?;
+        cache.put_thread(handle, post_id, &thread).await;
+        Ok(thread)
+    }
+
+    /// Fetch an author's profile card (bio, banner and counts).
+    pub async fn get_profile(&self, actor: &str) -> Result<Profile, ClientError> {
+        let params = atrium_api::app::bsky::actor::get_profile::ParametersData {
+            actor: actor
+                .parse()
+                .map_err(|_| ClientError::Api("invalid handle".to_string()))?,
+        };
+
+        let profile = call_upstream(
+            &self.upstream,
+            &self.breaker,
+            "app.bsky.actor.getProfile",
+            async {
+                self.client
+                    .service
+                    .app
+                    .bsky
+                    .actor
+                    .get_profile(params.into())
+                    .await
+                    .map_err(|e| map_profile_error(e.to_string()))
+            },
+        )
+        .await?;
+
+        Ok(Profile {
+            author: Author {
+                did: profile.did.to_string(),
+                handle: profile.handle.to_string(),
+                display_name: profile.display_name.clone(),
+                avatar_url: profile.avatar.clone(),
+            },
+            description: profile.description.clone(),
+            banner_url: profile.banner.clone(),
+            followers_count: profile.followers_count.map(|v| v as u64),
+            follows_count: profile.follows_count.map(|v| v as u64),
+            posts_count: profile.posts_count.map(|v| v as u64),
+        })
+    }
+
+    /// Fetch one page of an author's recent threads.
+    ///
+    /// Top-level posts come from `getAuthorFeed` (reposts and replies to others
+    /// are excluded). Posts without replies are skipped cheaply; for the rest the
+    /// self-reply chain is counted with one bounded-depth `getPostThread` call,
+    /// and only actual threads (two or more posts) are returned. Counting
+    /// spends the fetch budget; posts left uncounted when it runs out are
+    /// left out of the page.
+    pub async fn get_author_threads(
+        &self,
+        actor: &str,
+        cursor: Option<&str>,
+    ) -> Result<AuthorThreadsPage, ClientError> {
+        let params = atrium_api::app::bsky::feed::get_author_feed::ParametersData {
+            actor: actor
+                .parse()
+                .map_err(|_| ClientError::Api("invalid handle".to_string()))?,
+            cursor: cursor.map(String::from),
+            filter: Some("posts_no_replies".to_string()),
+            include_pins: None,
+            limit: AUTHOR_FEED_PAGE_SIZE.try_into().ok(),
+        };
+
+        let output = call_upstream(
+            &self.upstream,
+            &self.breaker,
+            "app.bsky.feed.getAuthorFeed",
+            async {
+                self.client
+                    .service
+                    .app
+                    .bsky
+                    .feed
+                    .get_author_feed(params.into())
+                    .await
+                    .map_err(|e| map_profile_error(e.to_string()))
+            },
+        )
+        .await?;
+
+        let roots: Vec<&PostView> = output
+            .feed
+            .iter()
+            .filter(|item| {
+                !matches!(
+                    item.reason,
+                    Some(Union::Refs(FeedViewPostReasonRefs::ReasonRepost(_)))
+                )
+            })
+            .filter(|item| match &item.reply {
+                // posts_no_replies should already exclude replies; keep only self-thread roots
+                Some(reply) => matches!(
+                    &reply.root,
+                    Union::Refs(ReplyRefRootRefs::PostView(root)) if root.uri == item.post.uri
+                ),
+                None => true,
+            })
+            .map(|item| &item.post)
+            .filter(|post| post.reply_count.unwrap_or(0) > 0)
+            .collect();
+
+        let limit = self.budget.start();
+        let counted = AtomicUsize::new(0);
+        // Each call checks the budget only once it is started
+        let calls: Vec<_> = roots
+            .iter()
+            .map(|&post| {
+                let (limit, counted) = (&limit, &counted);
+                async move {
+                    if !limit.allows(counted.load(Ordering::Relaxed)) {
+                        return None;
+                    }
+                    let count = limit.run(self.count_thread_posts(post)).await?;
+                    if let Ok((posts, _)) = &count {
+                        counted.fetch_add(*posts, Ordering::Relaxed);
+                    }
+                    Some(count)
+                }
+            })
+            .collect();
+        let counts: Vec<_> = futures::stream::iter(calls)
+            .buffered(THREAD_COUNT_CONCURRENCY)
+            .collect()
+            .await;
+
+        let mut threads = Vec::new();
+        for (post, count) in roots.into_iter().zip(counts) {
+            let (post_count, post_count_capped) = match count {
+                Some(Ok(count)) => count,
+                None => {
+                    debug!(uri = %post.uri, "fetch budget spent; not counting thread posts");
+                    continue;
+                }
+                Some(Err(e)) => {
+                    warn!(uri = %post.uri, error = %e, "failed to count thread posts");
+                    continue;
+                }
+            };
+            if post_count < 2 {
+                continue;
+            }
+            threads.push(ThreadSummary {
+                first_post: self.extract_post_view(post)?,
+                post_count,
+                post_count_capped,
+            });
+        }
+
+        Ok(AuthorThreadsPage {
+            threads,
+            cursor: output.cursor.clone(),
+        })
+    }
+
+    /// Count the posts in the self-reply chain starting at `root`.
+    /// Returns the count and whether the chain reached the inspected depth limit.
+    async fn count_thread_posts(&self, root: &PostView) -> Result<(usize, bool), ClientError> {
+        let params = ParametersData {
+            uri: root.uri.clone(),
+            depth: Some(THREAD_COUNT_DEPTH.try_into().unwrap()),
+            parent_height: Some(0.try_into().unwrap()),
+        };
+
+        let result = call_upstream(
+            &self.upstream,
+            &self.breaker,
+            "app.bsky.feed.getPostThread",
+            async {
+                self.client
+                    .service
+                    .app
+                    .bsky
+                    .feed
+                    .get_post_thread(params.into())
+                    .await
+                    .map_err(|e| map_api_error(e.to_string()))
+            },
+        )
+        .await?;
+
+        let view = match &result.thread {
+            Union::Refs(OutputThreadRefs::AppBskyFeedDefsThreadViewPost(view)) => view,
+            _ => return Ok((1, false)),
+        };
+
+        let author_did = root.author.did.as_str();
+        let mut count = 1;
+        let mut current: &ThreadViewPost = view;
+        while let Some(reply) = self.find_self_reply_view(current, author_did) {
+            count += 1;
+            current = reply;
+        }
+
+        Ok((count, count > THREAD_COUNT_DEPTH as usize))
     }
 
     /// Stream thread events as they are fetched from the API.
@@ -257,22 +878,35 @@ impl BlueskyClient {
             let did = self.resolve_handle(&handle).await?;
             let at_uri = format!("at://{}/app.bsky.feed.post/{}", did, post_id);
 
-            let root_uri = self.find_root_uri_async(&at_uri).await?;
-            let root_view = self.fetch_post_thread_shallow(&root_uri).await?;
+            let limit = self.budget.start();
+            let (root_view, walked) = self.find_root_async(&at_uri, &limit).await?;
+            let limit = limit.spend(walked - 1);
             let author = self.extract_author(&root_view)?;
             let author_did = author.did.clone();
 
             yield StreamEvent::Header(author);
             yield StreamEvent::Post(self.extract_post(&root_view)?);
 
+            let mut fetched = 1;
             let mut current_uri = self.find_self_reply(&root_view, &author_did);
 
             while let Some(uri) = current_uri {
-                let view = self.fetch_post_thread_shallow(&uri).await?;
+                let fetch = if limit.allows(fetched) {
+                    limit.run(self.fetch_post_thread_shallow(&uri)).await
+                } else {
+                    None
+                };
+                let Some(view) = fetch else {
+                    yield StreamEvent::Truncated;
+                    break;
+                };
+                let view = view?;
                 yield StreamEvent::Post(self.extract_post(&view)?);
+                fetched += 1;
                 current_uri = self.find_self_reply(&view, &author_did);
             }
 
+            metrics().record_thread_posts(fetched);
             yield StreamEvent::Done;
         }
     }
@@ -294,8 +928,10 @@ impl BlueskyClient {
         &self,
         view: &atrium_api::app::bsky::feed::defs::ThreadViewPost,
     ) -> Result<ThreadPost, ClientError> {
-        let post = &view.post;
+        self.extract_post_view(&view.post)
+    }
 
+    fn extract_post_view(&self, post: &PostView) -> Result<ThreadPost, ClientError> {
         let (text, created_at, langs) = self.extract_post_record(&post.record)?;
         let embed = self.extract_embed(&post.embed);
 
@@ -444,3 +1080,290 @@ impl BlueskyClient {
         Ok((text, created_at, langs))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_fetch_limit() {
+        let unlimited = FetchBudget::new(0, 0).start();
+        assert!(unlimited.allows(usize::MAX - 1));
+        assert_eq!(unlimited.run(async { 1 }).await, Some(1));
+
+        let limit = FetchBudget::new(2, 0).start();
+        assert!(limit.allows(1));
+        assert!(!limit.allows(2));
+
+        let limit = FetchBudget {
+            max_posts: None,
+            max_duration: Some(Duration::from_millis(20)),
+        }
+        .start();
+        assert!(limit.allows(1000));
+        assert_eq!(limit.run(std::future::pending::<()>()).await, None);
+        assert!(!limit.allows(0));
+    }
+
+    const AUTHOR: &str = "did:plc:author";
+    const CID: &str = "bafyreibnoelefnzgwbcacyt4vh52ymxvzbjq7mmqhtcnwarfq4lzegsiqe";
+
+    fn post_uri(n: usize) -> String {
+        format!("at://{AUTHOR}/app.bsky.feed.post/{n}")
+    }
+
+    fn post_view(n: usize) -> serde_json::Value {
+        serde_json::json!({
+            "uri": post_uri(n),
+            "cid": CID,
+            "author": { "did": AUTHOR, "handle": "author.bsky.social" },
+            "record": {
+                "$type": "app.bsky.feed.post",
+                "text": format!("post {n}"),
+                "createdAt": "2024-01-01T00:00:00Z",
+            },
+            "indexedAt": "2024-01-01T00:00:00Z",
+        })
+    }
+
+    fn thread_view(n: usize) -> serde_json::Value {
+        serde_json::json!({ "$type": "app.bsky.feed.defs#threadViewPost", "post": post_view(n) })
+    }
+
+    /// A stand-in AppView serving one self-reply chain of posts `1..=len`,
+    /// counting the `getPostThread` calls it answers.
+    async fn chain_appview(len: usize) -> (String, Arc<std::sync::atomic::AtomicUsize>) {
+        use axum::extract::Query;
+        use std::collections::HashMap;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let app = axum::Router::new().route(
+            "/xrpc/app.bsky.feed.getPostThread",
+            axum::routing::get(move |Query(query): Query<HashMap<String, String>>| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let n: usize = query["uri"].rsplit('/').next().unwrap().parse().unwrap();
+                let mut thread = thread_view(n);
+                if n > 1 {
+                    thread["parent"] = thread_view(n - 1);
+                }
+                let replies: Vec<_> = (n < len).then(|| thread_view(n + 1)).into_iter().collect();
+                thread["replies"] = replies.into();
+                async move { axum::Json(serde_json::json!({ "thread": thread })) }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{addr}"), calls)
+    }
+
+    #[tokio::test]
+    async fn test_budget_covers_walk_to_root() {
+        use std::sync::atomic::Ordering;
+
+        let (base_url, calls) = chain_appview(50).await;
+        let client = BlueskyClient::new(&base_url, Duration::from_secs(5)).unwrap();
+        let thread = client.get_thread(&post_uri(50)).await.unwrap();
+        assert!(!thread.truncated);
+        assert_eq!(thread.posts.len(), 50);
+
+        // Linked from deep in the chain, the walk up stops at the budget
+        calls.store(0, Ordering::SeqCst);
+        let client = client.with_fetch_budget(FetchBudget::new(5, 0));
+        let thread = client.get_thread(&post_uri(50)).await.unwrap();
+        assert!(thread.truncated);
+        assert_eq!(thread.posts.len(), 1);
+        assert_eq!(thread.posts[0].uri, post_uri(46));
+        assert_eq!(calls.load(Ordering::SeqCst), 5);
+
+        // A shallow link leaves the rest of the budget for the chain
+        calls.store(0, Ordering::SeqCst);
+        let thread = client.get_thread(&post_uri(2)).await.unwrap();
+        assert!(thread.truncated);
+        assert_eq!(thread.posts[0].uri, post_uri(1));
+        assert_eq!(thread.posts.len(), 4);
+        assert_eq!(calls.load(Ordering::SeqCst), 5);
+    }
+
+    /// A stand-in AppView for an author with ten three-post threads, eight
+    /// on the first feed page and two on the next. Counts the `getPostThread`
+    /// calls it answers and the most it answered at once.
+    async fn author_appview() -> (String, Arc<[std::sync::atomic::AtomicUsize; 3]>) {
+        use axum::extract::Query;
+        use std::collections::HashMap;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        // Calls, in flight, most in flight
+        let stats = Arc::new([0, 0, 0].map(AtomicUsize::new));
+        let feed = |roots: std::ops::RangeInclusive<usize>, cursor: Option<&str>| {
+            let items: Vec<_> = roots
+                .map(|n| {
+                    let mut post = post_view(n);
+                    post["replyCount"] = 1.into();
+                    serde_json::json!({ "post": post })
+                })
+                .collect();
+            let mut output = serde_json::json!({ "feed": items });
+            if let Some(cursor) = cursor {
+                output["cursor"] = cursor.into();
+            }
+            output
+        };
+        let counter = stats.clone();
+        let app = axum::Router::new()
+            .route(
+                "/xrpc/app.bsky.feed.getAuthorFeed",
+                axum::routing::get(
+                    move |Query(query): Query<HashMap<String, String>>| async move {
+                        axum::Json(match query.get("cursor").map(String::as_str) {
+                            None => feed(1..=8, Some("page 2")),
+                            Some("page 2") => feed(9..=10, None),
+                            Some(other) => panic!("unexpected cursor {other}"),
+                        })
+                    },
+                ),
+            )
+            .route(
+                "/xrpc/app.bsky.feed.getPostThread",
+                axum::routing::get(move |Query(query): Query<HashMap<String, String>>| {
+                    let stats = counter.clone();
+                    async move {
+                        stats[0].fetch_add(1, Ordering::SeqCst);
+                        let in_flight = stats[1].fetch_add(1, Ordering::SeqCst) + 1;
+                        stats[2].fetch_max(in_flight, Ordering::SeqCst);
+                        tokio::time::sleep(Duration::from_millis(20)).await;
+                        stats[1].fetch_sub(1, Ordering::SeqCst);
+
+                        let n: usize = query["uri"].rsplit('/').next().unwrap().parse().unwrap();
+                        let mut second = thread_view(n * 100 + 1);
+                        second["replies"] = vec![thread_view(n * 100 + 2)].into();
+                        let mut thread = thread_view(n);
+                        thread["replies"] = vec![second].into();
+                        axum::Json(serde_json::json!({ "thread": thread }))
+                    }
+                }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{addr}"), stats)
+    }
+
+    #[tokio::test]
+    async fn test_author_threads_pages_and_bounds() {
+        use std::sync::atomic::Ordering;
+
+        let (base_url, stats) = author_appview().await;
+        let client = BlueskyClient::new(&base_url, Duration::from_secs(5)).unwrap();
+
+        let page = client.get_author_threads(AUTHOR, None).await.unwrap();
+        assert_eq!(page.threads.len(), 8);
+        assert_eq!(page.threads[0].first_post.uri, post_uri(1));
+        assert!(page
+            .threads
+            .iter()
+            .all(|t| t.post_count == 3 && !t.post_count_capped));
+        assert_eq!(page.cursor.as_deref(), Some("page 2"));
+        // Counted concurrently, but no more than the bound at once
+        assert!((2..=THREAD_COUNT_CONCURRENCY).contains(&stats[2].load(Ordering::SeqCst)));
+
+        let page = client
+            .get_author_threads(AUTHOR, page.cursor.as_deref())
+            .await
+            .unwrap();
+        assert_eq!(page.threads.len(), 2);
+        assert_eq!(page.threads[0].first_post.uri, post_uri(9));
+        assert_eq!(page.cursor, None);
+
+        // The first batch of counts spends the whole budget
+        stats[0].store(0, Ordering::SeqCst);
+        let client = client.with_fetch_budget(FetchBudget::new(3, 0));
+        let page = client.get_author_threads(AUTHOR, None).await.unwrap();
+        assert_eq!(page.threads.len(), THREAD_COUNT_CONCURRENCY);
+        assert_eq!(stats[0].load(Ordering::SeqCst), THREAD_COUNT_CONCURRENCY);
+    }
+
+    /// An upstream that accepts connections and never answers.
+    async fn stalled_upstream() -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                held.push(socket);
+            }
+        });
+        format!("http://{addr}")
+    }
+
+    fn tripping_breaker() -> BreakerSettings {
+        BreakerSettings {
+            error_rate: 0.5,
+            min_calls: 2,
+            open_for: Duration::from_secs(30),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stalled_upstream_trips_breaker() {
+        let base_url = stalled_upstream().await;
+
+        let client = BlueskyClient::new(&base_url, Duration::from_millis(100))
+            .unwrap()
+            .with_circuit_breaker(tripping_breaker());
+        for _ in 0..2 {
+            assert!(client.resolve_handle("a.bsky.social").await.is_err());
+        }
+        assert_eq!(client.circuit_state(), CircuitState::Open);
+        assert!(matches!(
+            client.resolve_handle("a.bsky.social").await,
+            Err(ClientError::CircuitOpen)
+        ));
+
+        // Callers that give up before the upstream answers count too
+        let client = BlueskyClient::new(&base_url, Duration::from_secs(60))
+            .unwrap()
+            .with_circuit_breaker(tripping_breaker());
+        for _ in 0..2 {
+            let call = client.resolve_handle("b.bsky.social");
+            assert!(tokio::time::timeout(Duration::from_millis(100), call)
+                .await
+                .is_err());
+        }
+        assert_eq!(client.circuit_state(), CircuitState::Open);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limits_do_not_trip_breaker() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let body = r#"{"error":"RateLimitExceeded","message":"Rate Limit Exceeded"}"#;
+            let response = format!(
+                "HTTP/1.1 429 Too Many Requests\r\ncontent-type: application/json\r\n\
+                 content-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = [0; 1024];
+                let _ = socket.read(&mut request).await;
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+
+        let client = BlueskyClient::new(&format!("http://{addr}"), Duration::from_secs(5))
+            .unwrap()
+            .with_circuit_breaker(tripping_breaker());
+        for _ in 0..4 {
+            assert!(matches!(
+                client.resolve_handle("a.bsky.social").await,
+                Err(ClientError::RateLimited)
+            ));
+        }
+        assert_eq!(client.circuit_state(), CircuitState::Closed);
+    }
+}
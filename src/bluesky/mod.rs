@@ -1,7 +1,10 @@
+mod breaker;
 pub mod client;
+mod singleflight;
 pub mod types;
 pub mod url_parser;
 
-pub use client::BlueskyClient;
-pub use types::{Author, Thread, ThreadPost};
+pub use breaker::{BreakerSettings, CircuitState};
+pub use client::{BlueskyClient, FetchBudget};
+pub use types::{Author, AuthorThreadsPage, Profile, Thread, ThreadPost, ThreadSummary};
 pub use url_parser::{parse_bluesky_url, BlueskyUrlParts};
@@ -0,0 +1,154 @@
+//! Request coalescing for upstream calls.
+//!
+//! When a thread goes viral, many readers ask for the same posts at once.
+//! Concurrent calls with the same key share one in-flight future instead of
+//! each reaching the AppView; the entry is dropped as soon as the call
+//! completes, so nothing is cached beyond the life of the request. A call
+//! whose every waiter gives up is dropped too, cancelling it.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use futures::future::{BoxFuture, FutureExt, Shared};
+
+use super::client::ClientError;
+
+type SharedCall<T> = Shared<BoxFuture<'static, Result<T, ClientError>>>;
+
+pub(crate) struct SingleFlight<T> {
+    /// In-flight calls by key, each tagged with an id so a waiter can tell
+    /// its own call from a later one made under the same key
+    calls: Mutex<HashMap<String, (u64, SharedCall<T>)>>,
+    next_id: AtomicU64,
+}
+
+impl<T: Clone + Send + Sync + 'static> SingleFlight<T> {
+    pub fn new() -> Self {
+        Self {
+            calls: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    /// Run `call`, or join the call already in flight for `key`.
+    pub async fn run<F>(&self, key: String, call: F) -> Result<T, ClientError>
+    where
+        F: Future<Output = Result<T, ClientError>> + Send + 'static,
+    {
+        let (id, shared) = {
+            let mut calls = self.calls.lock().unwrap();
+            calls
+                .entry(key.clone())
+                .or_insert_with(|| {
+                    let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+                    (id, call.boxed().shared())
+                })
+                .clone()
+        };
+
+        let mut waiter = Waiter {
+            flight: self,
+            key,
+            id,
+            shared: Some(shared),
+            done: false,
+        };
+        let result = waiter.shared.as_mut().expect("set until drop").await;
+        waiter.done = true;
+        result
+    }
+}
+
+/// One caller waiting on a shared call. Removes the entry when dropped, if
+/// the call completed (whichever waiter finishes first; the leader may have
+/// been cancelled) or if no other waiter is left.
+struct Waiter<'a, T> {
+    flight: &'a SingleFlight<T>,
+    key: String,
+    id: u64,
+    shared: Option<SharedCall<T>>,
+    done: bool,
+}
+
+impl<T> Drop for Waiter<'_, T> {
+    fn drop(&mut self) {
+        let Some(shared) = self.shared.take() else {
+            return;
+        };
+        let mut calls = self.flight.calls.lock().unwrap();
+        let ours = calls.get(&self.key).is_some_and(|(id, _)| *id == self.id);
+        // Dropped under the lock, so of several waiters leaving at once the
+        // last to take the lock sees only the map's copy remaining
+        drop(shared);
+        let abandoned = || {
+            calls[&self.key]
+                .1
+                .strong_count()
+                .is_none_or(|count| count <= 1)
+        };
+        if ours && (self.done || abandoned()) {
+            let removed = calls.remove(&self.key);
+            drop(calls);
+            // Cancels the call if it is still running
+            drop(removed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_concurrent_calls_share_one_execution() {
+        let flight = Arc::new(SingleFlight::<usize>::new());
+        let executions = Arc::new(AtomicUsize::new(0));
+
+        let call = |key: &str| {
+            let flight = flight.clone();
+            let executions = executions.clone();
+            let key = key.to_string();
+            async move {
+                flight
+                    .run(key, async move {
+                        tokio::time::sleep(Duration::from_millis(20)).await;
+                        Ok(executions.fetch_add(1, Ordering::SeqCst))
+                    })
+                    .await
+            }
+        };
+
+        let results = futures::future::join_all([call("a"), call("a"), call("a"), call("b")]).await;
+        assert_eq!(executions.load(Ordering::SeqCst), 2);
+        assert_eq!(results[0].as_ref().unwrap(), results[1].as_ref().unwrap());
+        assert_eq!(results[1].as_ref().unwrap(), results[2].as_ref().unwrap());
+        assert_ne!(results[0].as_ref().unwrap(), results[3].as_ref().unwrap());
+
+        // Completed calls are not reused
+        call("a").await.unwrap();
+        assert_eq!(executions.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_abandoned_call_is_dropped() {
+        let flight = SingleFlight::<usize>::new();
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+
+        let call = flight.run("a".to_string(), async move {
+            let _tx = tx;
+            std::future::pending().await
+        });
+        assert!(tokio::time::timeout(Duration::from_millis(10), call)
+            .await
+            .is_err());
+
+        // The call's future was dropped with its last waiter
+        assert!(rx.await.is_err());
+        assert!(flight.calls.lock().unwrap().is_empty());
+    }
+}
@@ -1,12 +1,15 @@
 use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Thread {
     pub posts: Vec<ThreadPost>,
     pub author: Author,
+    /// The fetch stopped at its budget before reaching the end of the chain
+    pub truncated: bool,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ThreadPost {
     pub uri: String,
     pub cid: String,
@@ -20,7 +23,7 @@ pub struct ThreadPost {
     pub langs: Vec<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Embed {
     Images(Vec<EmbedImage>),
     Video(EmbedVideo),
@@ -32,7 +35,7 @@ pub enum Embed {
     },
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmbedImage {
     pub thumb_url: String,
     pub fullsize_url: String,
@@ -40,7 +43,7 @@ pub struct EmbedImage {
     pub aspect_ratio: Option<AspectRatio>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmbedVideo {
     pub thumbnail_url: Option<String>,
     pub playlist_url: String,
@@ -48,7 +51,7 @@ pub struct EmbedVideo {
     pub aspect_ratio: Option<AspectRatio>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmbedExternal {
     pub uri: String,
     pub title: String,
@@ -56,7 +59,7 @@ pub struct EmbedExternal {
     pub thumb_url: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmbedRecord {
     pub uri: String,
     pub cid: String,
@@ -67,13 +70,13 @@ pub struct EmbedRecord {
     pub embed: Option<Box<Embed>>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AspectRatio {
     pub width: u32,
     pub height: u32,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Author {
     pub did: String,
     pub handle: String,
@@ -89,6 +92,22 @@ impl Author {
 }
 
 impl Thread {
+    pub fn new(posts: Vec<ThreadPost>, author: Author) -> Self {
+        Self {
+            posts,
+            author,
+            truncated: false,
+        }
+    }
+
+    pub fn truncated(posts: Vec<ThreadPost>, author: Author) -> Self {
+        Self {
+            posts,
+            author,
+            truncated: true,
+        }
+    }
+
     pub fn original_post_url(&self) -> Option<String> {
         self.posts.first().map(|post| {
             let post_id = post.uri.rsplit('/').next().unwrap_or("");
@@ -109,6 +128,34 @@ impl Thread {
     }
 }
 
+/// An author's profile card as returned by `getProfile`
+#[derive(Debug, Clone)]
+pub struct Profile {
+    pub author: Author,
+    pub description: Option<String>,
+    pub banner_url: Option<String>,
+    pub followers_count: Option<u64>,
+    pub follows_count: Option<u64>,
+    pub posts_count: Option<u64>,
+}
+
+/// A thread listed on an author page: its first post plus the size of the chain
+#[derive(Debug, Clone)]
+pub struct ThreadSummary {
+    pub first_post: ThreadPost,
+    pub post_count: usize,
+    /// The chain continues past the depth that was inspected, so post_count is a lower bound
+    pub post_count_capped: bool,
+}
+
+/// One page of an author's recent threads
+#[derive(Debug, Clone)]
+pub struct AuthorThreadsPage {
+    pub threads: Vec<ThreadSummary>,
+    /// Cursor for the next (older) page, if any
+    pub cursor: Option<String>,
+}
+
 /// Events emitted during streaming thread fetching
 #[derive(Debug, Clone)]
 pub enum StreamEvent {
@@ -116,6 +163,8 @@ pub enum StreamEvent {
     Header(Author),
     /// A single post in the thread
     Post(ThreadPost),
+    /// The fetch budget ran out before the end of the thread
+    Truncated,
     /// Thread fetching is complete
     Done,
 }
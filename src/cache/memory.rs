@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use futures::future::{self, BoxFuture, FutureExt};
+
+use super::{CacheError, CacheStore};
+
+/// Above this many entries, expired ones are dropped; while still full, new
+/// entries are not stored.
+const MAX_ENTRIES: usize = 10_000;
+
+struct Entry {
+    value: Vec<u8>,
+    expires: Instant,
+}
+
+/// In-process cache store, for a single replica.
+#[derive(Default)]
+pub struct MemoryStore {
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(super) fn get_now(&self, key: &str, now: Instant) -> Option<Vec<u8>> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(key) {
+            Some(entry) if entry.expires > now => Some(entry.value.clone()),
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub(super) fn set_now(&self, key: &str, value: Vec<u8>, ttl: Duration, now: Instant) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= MAX_ENTRIES && !entries.contains_key(key) {
+            entries.retain(|_, entry| entry.expires > now);
+            if entries.len() >= MAX_ENTRIES {
+                return;
+            }
+        }
+        entries.insert(
+            key.to_string(),
+            Entry {
+                value,
+                expires: now + ttl,
+            },
+        );
+    }
+
+    pub(super) fn remove(&self, key: &str) {
+        self.entries.lock().unwrap().remove(key);
+    }
+
+    pub(super) fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+impl CacheStore for MemoryStore {
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Option<Vec<u8>>, CacheError>> {
+        future::ready(Ok(self.get_now(key, Instant::now()))).boxed()
+    }
+
+    fn set<'a>(
+        &'a self,
+        key: &'a str,
+        value: Vec<u8>,
+        ttl: Duration,
+    ) -> BoxFuture<'a, Result<(), CacheError>> {
+        self.set_now(key, value, ttl, Instant::now());
+        future::ready(Ok(())).boxed()
+    }
+
+    fn invalidate<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<(), CacheError>> {
+        self.remove(key);
+        future::ready(Ok(())).boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entries_expire() {
+        let store = MemoryStore::new();
+        let start = Instant::now();
+        store.set_now("a", b"1".to_vec(), Duration::from_secs(10), start);
+
+        assert_eq!(store.get_now("a", start), Some(b"1".to_vec()));
+        assert_eq!(
+            store.get_now("a", start + Duration::from_secs(9)),
+            Some(b"1".to_vec())
+        );
+        assert_eq!(store.get_now("a", start + Duration::from_secs(10)), None);
+        // Expired entries are removed once seen
+        assert_eq!(store.get_now("a", start), None);
+    }
+}
@@ -0,0 +1,223 @@
+//! Cache for resolved handles and fetched threads.
+//!
+//! The Helm chart scales replicas horizontally, so a per-process cache alone
+//! would still fetch a popular thread from the AppView once per pod. The
+//! storage behind [`Cache`] is pluggable: [`MemoryStore`] for a single
+//! replica, or [`RedisStore`] to share entries between replicas through any
+//! Redis-protocol server. Entries expire after a TTL, and thread watchers
+//! invalidate a thread as soon as they see it change.
+//!
+//! The cache is best effort: backend errors are logged and treated as misses,
+//! so an unavailable Redis only costs the upstream calls it would have saved.
+
+mod memory;
+mod redis;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::future::{BoxFuture, FutureExt};
+use serde::{de::DeserializeOwned, Serialize};
+use thiserror::Error;
+use tracing::{debug, warn};
+
+use crate::bluesky::Thread;
+use crate::config::{CacheBackend, Config};
+use crate::metrics::metrics;
+
+pub use self::memory::MemoryStore;
+pub use self::redis::RedisStore;
+
+#[derive(Error, Debug)]
+pub enum CacheError {
+    #[error("Redis error: {0}")]
+    Redis(#[from] ::redis::RedisError),
+    #[error("cache server timed out")]
+    Timeout,
+    /// Skipped because the server recently failed
+    #[error("cache server unavailable")]
+    Unavailable,
+    #[error("failed to encode cache entry: {0}")]
+    Encode(#[from] serde_json::Error),
+}
+
+/// Storage for serialized cache entries.
+pub trait CacheStore: Send + Sync {
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Option<Vec<u8>>, CacheError>>;
+
+    fn set<'a>(
+        &'a self,
+        key: &'a str,
+        value: Vec<u8>,
+        ttl: Duration,
+    ) -> BoxFuture<'a, Result<(), CacheError>>;
+
+    /// Remove an entry, on every replica sharing the store.
+    fn invalidate<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<(), CacheError>>;
+
+    /// Check that the store is reachable.
+    fn ping(&self) -> BoxFuture<'_, Result<(), CacheError>> {
+        futures::future::ready(Ok(())).boxed()
+    }
+
+    /// Start any background work the store needs, such as listening for
+    /// invalidations from other replicas.
+    fn start(&self) {}
+}
+
+/// Typed access to a [`CacheStore`], shared through `AppState`.
+#[derive(Clone)]
+pub struct Cache {
+    store: Arc<dyn CacheStore>,
+    thread_ttl: Duration,
+    handle_ttl: Duration,
+}
+
+impl Cache {
+    pub fn new(store: Arc<dyn CacheStore>, thread_ttl: Duration, handle_ttl: Duration) -> Self {
+        Self {
+            store,
+            thread_ttl,
+            handle_ttl,
+        }
+    }
+
+    /// The cache configured by `CACHE_BACKEND`, or `None` when disabled.
+    pub fn from_config(config: &Config) -> Result<Option<Self>, CacheError> {
+        let store: Arc<dyn CacheStore> = match config.cache_backend {
+            CacheBackend::None => return Ok(None),
+            CacheBackend::Memory => Arc::new(MemoryStore::new()),
+            CacheBackend::Redis => Arc::new(RedisStore::new(&config.redis_url)?),
+        };
+        Ok(Some(Self::new(
+            store,
+            Duration::from_secs(config.cache_thread_ttl_seconds),
+            Duration::from_secs(config.cache_handle_ttl_seconds),
+        )))
+    }
+
+    pub fn start(&self) {
+        self.store.start();
+    }
+
+    pub async fn ping(&self) -> Result<(), CacheError> {
+        self.store.ping().await
+    }
+
+    async fn get<T: DeserializeOwned>(&self, kind: &str, key: &str) -> Option<T> {
+        let value = match self.store.get(key).await {
+            Ok(value) => value,
+            Err(e) => {
+                report(key, &e);
+                None
+            }
+        };
+        // Entries that no longer decode (e.g. written by an older version) are misses
+        let value = value.and_then(|bytes| serde_json::from_slice(&bytes).ok());
+        metrics().record_cache_lookup(kind, value.is_some());
+        value
+    }
+
+    async fn set<T: Serialize>(&self, key: &str, value: &T, ttl: Duration) {
+        let result = match serde_json::to_vec(value) {
+            Ok(bytes) => self.store.set(key, bytes, ttl).await,
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = result {
+            report(key, &e);
+        }
+    }
+
+    /// The DID a handle resolved to.
+    pub async fn did(&self, handle: &str) -> Option<String> {
+        self.get("handles", &handle_key(handle)).await
+    }
+
+    pub async fn put_did(&self, handle: &str, did: &str) {
+        self.set(&handle_key(handle), &did, self.handle_ttl).await;
+    }
+
+    /// The thread fetched for a post, keyed like thread watchers.
+    pub async fn thread(&self, handle: &str, post_id: &str) -> Option<Thread> {
+        self.get("threads", &thread_key(handle, post_id)).await
+    }
+
+    pub async fn put_thread(&self, handle: &str, post_id: &str, thread: &Thread) {
+        self.set(&thread_key(handle, post_id), thread, self.thread_ttl)
+            .await;
+    }
+
+    pub async fn invalidate_thread(&self, handle: &str, post_id: &str) {
+        let key = thread_key(handle, post_id);
+        if let Err(e) = self.store.invalidate(&key).await {
+            report(&key, &e);
+        }
+    }
+}
+
+/// Log a failed cache operation. Operations skipped during an outage are
+/// only logged at debug level, since the failure that started it was not.
+fn report(key: &str, error: &CacheError) {
+    match error {
+        CacheError::Unavailable => debug!(key, "cache unavailable; skipped"),
+        _ => warn!(key, error = %error, "cache operation failed"),
+    }
+}
+
+fn handle_key(handle: &str) -> String {
+    format!("handle:{}", handle.to_lowercase())
+}
+
+fn thread_key(handle: &str, post_id: &str) -> String {
+    format!("thread:{}/{}", handle.to_lowercase(), post_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bluesky::{Author, ThreadPost};
+    use chrono::Utc;
+
+    #[tokio::test]
+    async fn test_thread_round_trip_and_invalidation() {
+        let cache = Cache::new(
+            Arc::new(MemoryStore::new()),
+            Duration::from_secs(60),
+            Duration::from_secs(60),
+        );
+        let author = Author {
+            did: "did:plc:abc".to_string(),
+            handle: "user.bsky.social".to_string(),
+            display_name: Some("User".to_string()),
+            avatar_url: None,
+        };
+        let post = ThreadPost {
+            uri: "at://did:plc:abc/app.bsky.feed.post/3kroot".to_string(),
+            cid: "cid".to_string(),
+            text: "hello".to_string(),
+            created_at: Utc::now(),
+            reply_count: Some(1),
+            repost_count: None,
+            like_count: None,
+            embed: None,
+            langs: vec!["en".to_string()],
+        };
+
+        assert!(cache.thread("user.bsky.social", "3kroot").await.is_none());
+        cache
+            .put_thread(
+                "user.bsky.social",
+                "3kroot",
+                &Thread::truncated(vec![post], author),
+            )
+            .await;
+
+        // Handles are case-insensitive
+        let thread = cache.thread("User.Bsky.Social", "3kroot").await.unwrap();
+        assert_eq!(thread.posts[0].text, "hello");
+        assert!(thread.truncated);
+
+        cache.invalidate_thread("user.bsky.social", "3kroot").await;
+        assert!(cache.thread("user.bsky.social", "3kroot").await.is_none());
+    }
+}
@@ -0,0 +1,210 @@
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::future::{BoxFuture, FutureExt};
+use futures::StreamExt;
+use redis::aio::{ConnectionManager, ConnectionManagerConfig};
+use redis::{AsyncCommands, Client};
+use tokio::sync::OnceCell;
+use tracing::{debug, info, warn};
+
+use super::memory::MemoryStore;
+use super::{CacheError, CacheStore};
+
+/// Prefix for every key this service writes
+const KEY_PREFIX: &str = "sklonger:";
+
+/// Channel on which replicas announce invalidated keys
+const INVALIDATION_CHANNEL: &str = "sklonger:invalidate";
+
+/// How long an entry read from Redis is also kept in process. Invalidations
+/// clear it early; this bounds staleness if one is missed.
+const LOCAL_TTL: Duration = Duration::from_secs(5);
+
+/// Longest wait for Redis before a lookup counts as a miss
+const TIMEOUT: Duration = Duration::from_millis(500);
+
+/// After Redis fails, how long requests skip it instead of waiting again
+const RETRY_AFTER: Duration = Duration::from_secs(5);
+
+/// Cache store shared between replicas through a Redis-protocol server, with
+/// a short-lived in-process tier in front of it.
+///
+/// Invalidations are published to every replica, which drop the entry from
+/// their in-process tier.
+#[derive(Clone)]
+pub struct RedisStore {
+    client: Client,
+    /// Connected on first use, so an unreachable server does not stop startup
+    connection: Arc<OnceCell<ConnectionManager>>,
+    local: Arc<MemoryStore>,
+    /// Set after a failure; Redis is not tried again until then
+    unavailable_until: Arc<Mutex<Option<Instant>>>,
+}
+
+impl RedisStore {
+    pub fn new(url: &str) -> Result<Self, CacheError> {
+        Ok(Self {
+            client: Client::open(url)?,
+            connection: Arc::new(OnceCell::new()),
+            local: Arc::new(MemoryStore::new()),
+            unavailable_until: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    /// Run a command on the shared connection, giving up after `TIMEOUT`.
+    /// While Redis is unavailable, commands fail at once.
+    async fn run<T, F, Fut>(&self, command: F) -> Result<T, CacheError>
+    where
+        F: FnOnce(ConnectionManager) -> Fut,
+        Fut: Future<Output = redis::RedisResult<T>>,
+    {
+        if let Some(until) = *self.unavailable_until.lock().unwrap() {
+            if Instant::now() < until {
+                return Err(CacheError::Unavailable);
+            }
+        }
+
+        let call = async {
+            let config = ConnectionManagerConfig::new()
+                .set_number_of_retries(0)
+                .set_connection_timeout(TIMEOUT)
+                .set_response_timeout(TIMEOUT);
+            let connection = self
+                .connection
+                .get_or_try_init(|| self.client.get_connection_manager_with_config(config))
+                .await?;
+            command(connection.clone()).await
+        };
+        let result = match tokio::time::timeout(TIMEOUT, call).await {
+            Ok(result) => result.map_err(CacheError::from),
+            Err(_) => Err(CacheError::Timeout),
+        };
+        *self.unavailable_until.lock().unwrap() =
+            result.is_err().then(|| Instant::now() + RETRY_AFTER);
+        result
+    }
+
+    /// Drop keys from the in-process tier as other replicas invalidate them.
+    /// Reconnects with backoff; the tier is cleared after each reconnect, since
+    /// invalidations sent while disconnected were missed.
+    async fn listen(self) {
+        let mut backoff = Duration::from_secs(1);
+        loop {
+            match self.client.get_async_pubsub().await {
+                Ok(mut pubsub) => match pubsub.subscribe(INVALIDATION_CHANNEL).await {
+                    Ok(()) => {
+                        info!("listening for cache invalidations");
+                        backoff = Duration::from_secs(1);
+                        self.local.clear();
+                        let mut messages = pubsub.on_message();
+                        while let Some(message) = messages.next().await {
+                            if let Ok(key) = message.get_payload::<String>() {
+                                debug!(key, "cache entry invalidated by a replica");
+                                self.local.remove(&key);
+                            }
+                        }
+                        warn!("cache invalidation subscription closed");
+                    }
+                    Err(e) => warn!(error = %e, "failed to subscribe to cache invalidations"),
+                },
+                Err(e) => warn!(error = %e, "failed to connect for cache invalidations"),
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(Duration::from_secs(60));
+        }
+    }
+}
+
+impl CacheStore for RedisStore {
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<Option<Vec<u8>>, CacheError>> {
+        async move {
+            if let Some(value) = self.local.get_now(key, Instant::now()) {
+                return Ok(Some(value));
+            }
+            let value: Option<Vec<u8>> = self
+                .run(|mut c| async move { c.get(format!("{KEY_PREFIX}{key}")).await })
+                .await?;
+            if let Some(value) = &value {
+                self.local
+                    .set_now(key, value.clone(), LOCAL_TTL, Instant::now());
+            }
+            Ok(value)
+        }
+        .boxed()
+    }
+
+    fn set<'a>(
+        &'a self,
+        key: &'a str,
+        value: Vec<u8>,
+        ttl: Duration,
+    ) -> BoxFuture<'a, Result<(), CacheError>> {
+        async move {
+            self.local
+                .set_now(key, value.clone(), ttl.min(LOCAL_TTL), Instant::now());
+            self.run(|mut c| async move {
+                c.set_ex(format!("{KEY_PREFIX}{key}"), value, ttl.as_secs().max(1))
+                    .await
+            })
+            .await
+        }
+        .boxed()
+    }
+
+    fn invalidate<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<(), CacheError>> {
+        async move {
+            self.local.remove(key);
+            self.run(|mut c| async move {
+                let _: usize = c.del(format!("{KEY_PREFIX}{key}")).await?;
+                let _: usize = c.publish(INVALIDATION_CHANNEL, key).await?;
+                Ok(())
+            })
+            .await
+        }
+        .boxed()
+    }
+
+    fn ping(&self) -> BoxFuture<'_, Result<(), CacheError>> {
+        async move {
+            self.run(|mut c| async move { redis::cmd("PING").query_async(&mut c).await })
+                .await
+        }
+        .boxed()
+    }
+
+    fn start(&self) {
+        tokio::spawn(self.clone().listen());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs against the Redis-compatible server at `REDIS_URL`, e.g.
+    /// `docker run -p 6379:6379 valkey/valkey` and
+    /// `REDIS_URL=redis://localhost:6379 cargo test -- --ignored`.
+    #[tokio::test]
+    #[ignore = "needs REDIS_URL"]
+    async fn test_invalidation_reaches_other_replicas() {
+        let url = std::env::var("REDIS_URL").expect("REDIS_URL must be set");
+        let first = RedisStore::new(&url).unwrap();
+        let second = RedisStore::new(&url).unwrap();
+        second.start();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let key = format!("test:{}", std::process::id());
+        first
+            .set(&key, b"value".to_vec(), Duration::from_secs(60))
+            .await
+            .unwrap();
+        // Read through Redis, which also fills the second replica's local tier
+        assert_eq!(second.get(&key).await.unwrap(), Some(b"value".to_vec()));
+
+        first.invalidate(&key).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(second.get(&key).await.unwrap(), None);
+    }
+}
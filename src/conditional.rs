@@ -0,0 +1,164 @@
+//! HTTP validators and conditional request handling.
+//!
+//! Posts can be added, removed, edited or recreated anywhere in a thread, so
+//! a version of it is identified by a hash of all its post CIDs in order.
+//! Responses derived from a thread carry that as a strong ETag, letting
+//! browsers and CDNs revalidate with a 304. The last post's time is sent as
+//! `Last-Modified` too, but it misses changes to earlier posts, so it is only
+//! consulted when the request has no `If-None-Match`.
+
+use axum::http::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use axum::http::{HeaderMap, HeaderValue};
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+
+use crate::bluesky::types::ThreadPost;
+use crate::metrics::metrics;
+
+/// Validators for one version of a thread.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Validators {
+    /// Quoted entity tag, e.g. `"3f0c9a1e5b7d2c4a-12"`: a hash of the post
+    /// CIDs and the post count
+    pub etag: String,
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+impl Validators {
+    pub fn for_posts(posts: &[ThreadPost]) -> Self {
+        let mut hasher = Sha256::new();
+        for post in posts {
+            hasher.update(post.cid.as_bytes());
+            hasher.update(b"\n");
+        }
+        let digest: String = hasher.finalize()[..8]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        Self {
+            etag: format!("\"{}-{}\"", digest, posts.len()),
+            last_modified: posts.last().map(|p| p.created_at),
+        }
+    }
+
+    /// Whether the request's conditional headers show the client already has
+    /// this version. `If-None-Match` takes precedence over `If-Modified-Since`.
+    pub fn not_modified(&self, headers: &HeaderMap) -> bool {
+        let fresh = self.matches(headers);
+        if headers.contains_key(IF_NONE_MATCH) || headers.contains_key(IF_MODIFIED_SINCE) {
+            metrics().record_cache_lookup("conditional", fresh);
+        }
+        fresh
+    }
+
+    fn matches(&self, headers: &HeaderMap) -> bool {
+        if let Some(if_none_match) = headers.get(IF_NONE_MATCH) {
+            return if_none_match
+                .to_str()
+                .map(|value| etag_list_matches(value, &self.etag))
+                .unwrap_or(false);
+        }
+
+        let since = headers
+            .get(IF_MODIFIED_SINCE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| DateTime::parse_from_rfc2822(v).ok());
+        match (since, self.last_modified) {
+            (Some(since), Some(modified)) => modified.timestamp() <= since.timestamp(),
+            _ => false,
+        }
+    }
+
+    /// Add `ETag` and `Last-Modified` to a response.
+    pub fn apply(&self, headers: &mut HeaderMap) {
+        if let Ok(etag) = HeaderValue::from_str(&self.etag) {
+            headers.insert(ETAG, etag);
+        }
+        if let Some(modified) = self.last_modified {
+            let value = modified.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+            if let Ok(value) = HeaderValue::from_str(&value) {
+                headers.insert(LAST_MODIFIED, value);
+            }
+        }
+    }
+}
+
+/// `If-None-Match` uses weak comparison, so `W/` prefixes are ignored.
+fn etag_list_matches(header: &str, etag: &str) -> bool {
+    header.split(',').map(str::trim).any(|candidate| {
+        candidate == "*" || candidate.strip_prefix("W/").unwrap_or(candidate) == etag
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn validators() -> Validators {
+        Validators {
+            etag: "\"bafyabc-3\"".to_string(),
+            last_modified: Some(Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap()),
+        }
+    }
+
+    fn headers(name: axum::http::HeaderName, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_if_none_match() {
+        let v = validators();
+        assert!(v.not_modified(&headers(IF_NONE_MATCH, "\"bafyabc-3\"")));
+        assert!(v.not_modified(&headers(IF_NONE_MATCH, "\"other\", W/\"bafyabc-3\"")));
+        assert!(v.not_modified(&headers(IF_NONE_MATCH, "*")));
+        assert!(!v.not_modified(&headers(IF_NONE_MATCH, "\"bafyabc-2\"")));
+        assert!(!v.not_modified(&HeaderMap::new()));
+    }
+
+    #[test]
+    fn test_if_modified_since() {
+        let v = validators();
+        assert!(v.not_modified(&headers(IF_MODIFIED_SINCE, "Wed, 01 May 2024 12:00:00 GMT")));
+        assert!(!v.not_modified(&headers(IF_MODIFIED_SINCE, "Wed, 01 May 2024 11:59:59 GMT")));
+
+        // If-None-Match wins when both are present
+        let mut both = headers(IF_MODIFIED_SINCE, "Wed, 01 May 2024 12:00:00 GMT");
+        both.insert(IF_NONE_MATCH, HeaderValue::from_static("\"stale-1\""));
+        assert!(!v.not_modified(&both));
+    }
+
+    #[test]
+    fn test_etag_covers_every_post() {
+        let post = |cid: &str| ThreadPost {
+            uri: format!("at://did:plc:abc/app.bsky.feed.post/{}", cid),
+            cid: cid.to_string(),
+            text: String::new(),
+            created_at: Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap(),
+            reply_count: None,
+            repost_count: None,
+            like_count: None,
+            embed: None,
+            langs: vec![],
+        };
+        let original = Validators::for_posts(&[post("a"), post("b"), post("c")]);
+        let edited = Validators::for_posts(&[post("a"), post("b2"), post("c")]);
+
+        assert_eq!(
+            original,
+            Validators::for_posts(&[post("a"), post("b"), post("c")])
+        );
+        assert_ne!(original.etag, edited.etag);
+        assert!(original.etag.ends_with("-3\""));
+    }
+
+    #[test]
+    fn test_apply_formats_http_date() {
+        let mut headers = HeaderMap::new();
+        validators().apply(&mut headers);
+        assert_eq!(headers[ETAG], "\"bafyabc-3\"");
+        assert_eq!(headers[LAST_MODIFIED], "Wed, 01 May 2024 12:00:00 GMT");
+    }
+}
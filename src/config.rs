@@ -1,31 +1,185 @@
 use std::env;
+use std::fs;
+use std::path::Path;
 use std::str::FromStr;
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use tracing_subscriber::EnvFilter;
+use url::Url;
+
+/// Shown in place of secrets by `print-config` and in reload logs
+pub(crate) const REDACTED: &str = "<redacted>";
+
+/// Config file keys whose values are never printed or logged
+/// (`redis_url` may carry a password)
+pub(crate) const SECRET_KEYS: &[&str] = &["webhook_secret", "vapid_private_key", "redis_url"];
+
+/// The running configuration, replaced whole when it is reloaded.
+pub type SharedConfig = Arc<ArcSwap<Config>>;
 
-#[derive(Debug, Clone)]
+/// Keys in the config file are the environment variable names in lowercase.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
     pub port: u16,
     pub log_level: String,
+    /// Human-readable (`text`) or one JSON object per line (`json`)
+    pub log_format: LogFormat,
+    /// Export spans over OTLP/HTTP to `otel_endpoint`
+    pub otel_enabled: bool,
+    /// OTLP/HTTP collector base URL; spans are sent to `{otel_endpoint}/v1/traces`
+    #[serde(rename = "otel_exporter_otlp_endpoint")]
+    pub otel_endpoint: String,
+    /// `service.name` resource attribute on exported spans
+    pub otel_service_name: String,
     pub bluesky_api_url: String,
     pub request_timeout_seconds: u64,
+    /// Most posts fetched for one thread page before it is truncated (0 for no limit)
+    pub thread_max_posts: usize,
+    /// Longest upstream fetch for one thread page before it is truncated (0 for no limit)
+    pub thread_fetch_timeout_seconds: u64,
+    /// Most XRPC calls in flight at once across the process (0 for no limit)
+    pub upstream_max_concurrency: usize,
+    /// Share of recent AppView calls that must fail to open the circuit breaker (0 disables it)
+    pub circuit_breaker_error_rate: f64,
+    /// AppView calls needed in the last minute before the breaker may open
+    pub circuit_breaker_min_calls: usize,
+    /// How long the breaker fails calls fast before probing the AppView again
+    pub circuit_breaker_open_seconds: u64,
+    /// Where resolved handles and fetched threads are cached
+    pub cache_backend: CacheBackend,
+    /// Redis-protocol server shared by all replicas, for the `redis` backend
+    pub redis_url: String,
+    pub cache_thread_ttl_seconds: u64,
+    pub cache_handle_ttl_seconds: u64,
+    /// How often the background health check probes upstream
+    pub health_check_interval_seconds: u64,
     pub poll_enabled: bool,
+    #[serde(rename = "poll_initial_interval_seconds")]
     pub poll_initial_interval: u64,
+    #[serde(rename = "poll_max_interval_seconds")]
     pub poll_max_interval: u64,
+    #[serde(rename = "poll_disable_after_seconds")]
     pub poll_disable_after: u64,
     /// Public URL where the app is hosted (for Open Graph meta tags)
     pub public_url: String,
+    /// User-Agent substrings of link-preview crawlers, which are served the
+    /// non-streaming page with Open Graph tags
+    pub crawler_user_agents: Vec<String>,
+    /// Serve gemtext renderings of threads over the Gemini protocol
+    pub gemini_enabled: bool,
+    pub gemini_port: u16,
+    /// PEM certificate chain and private key for the Gemini TLS listener
+    pub gemini_cert_path: String,
+    pub gemini_key_path: String,
+    /// Wake thread watchers from the Jetstream firehose instead of waiting for the next poll
+    pub jetstream_enabled: bool,
+    /// Jetstream subscribe endpoint (a local stand-in can be used for testing)
+    pub jetstream_url: String,
+    /// Enable the webhook subscription API and deliveries
+    pub webhooks_enabled: bool,
+    /// Shared secret that signs webhook payloads and authorizes the subscription API
+    pub webhook_secret: String,
+    /// JSON file where webhook subscriptions are persisted
+    pub webhooks_path: String,
+    /// Enable Web Push notifications for followed threads
+    pub push_enabled: bool,
+    /// Base64url raw P-256 private key identifying the server to push services
+    pub vapid_private_key: String,
+    /// Contact URL (`mailto:` or `https:`) sent with VAPID tokens; defaults to `public_url`
+    pub vapid_subject: String,
+    /// JSON file where push subscriptions are persisted
+    pub push_subscriptions_path: String,
+    /// Expose Prometheus metrics at `/metrics`
+    pub metrics_enabled: bool,
+    /// Serve `/metrics` on this separate admin port instead of the main one (0 for the main port)
+    pub metrics_port: u16,
+    /// Limit requests per client IP
+    pub rate_limit_enabled: bool,
+    /// Proxies in front of the service that append to `X-Forwarded-For` (0 to use the peer address)
+    pub rate_limit_trusted_proxies: usize,
+    /// Requests per minute per client for pages, the live-update API and crawlers (0 for no limit)
+    pub rate_limit_pages_per_minute: u32,
+    pub rate_limit_polling_per_minute: u32,
+    pub rate_limit_crawlers_per_minute: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+/// Storage for the upstream response cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheBackend {
+    /// No caching; every page fetches from upstream
+    None,
+    /// Per-process; replicas do not share entries
+    Memory,
+    /// Shared through the server at `redis_url`
+    Redis,
+}
+
+impl FromStr for CacheBackend {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "memory" => Ok(Self::Memory),
+            "redis" => Ok(Self::Redis),
+            _ => Err(()),
+        }
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(()),
+        }
+    }
 }
 
 #[derive(Error, Debug)]
 pub enum ConfigError {
     #[error("invalid {0} value: {1}")]
     InvalidEnvVar(&'static str, String),
+    #[error("failed to read config file {0}: {1}")]
+    Read(String, std::io::Error),
+    #[error("invalid config file {0}: {1}")]
+    File(String, toml::de::Error),
+    #[error("invalid {field}: {reason}")]
+    Invalid { field: &'static str, reason: String },
 }
 
 fn env_var_or_default(name: &str, default: &str) -> String {
     env::var(name).unwrap_or_else(|_| default.to_string())
 }
 
+/// A comma-separated list; blank entries are dropped.
+fn list_env_or_default(name: &str, default: &[String]) -> Vec<String> {
+    match env::var(name) {
+        Ok(val) => val
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+            .collect(),
+        Err(_) => default.to_vec(),
+    }
+}
+
 fn parse_env_or_default<T: FromStr>(name: &'static str, default: T) -> Result<T, ConfigError> {
     match env::var(name) {
         Ok(val) => val
@@ -46,19 +200,298 @@ fn parse_bool_env_or_default(name: &'static str, default: bool) -> Result<bool,
     }
 }
 
+/// Common social media crawler User-Agent patterns.
+/// These crawlers fetch pages to generate link previews.
+const DEFAULT_CRAWLER_USER_AGENTS: &[&str] = &[
+    "Twitterbot",
+    "facebookexternalhit",
+    "LinkedInBot",
+    "WhatsApp",
+    "Slackbot",
+    "TelegramBot",
+    "Discordbot",
+    // Some crawlers for general link previews
+    "Googlebot",
+    "bingbot",
+    "Applebot",
+];
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            port: 8080,
+            log_level: "info".to_string(),
+            log_format: LogFormat::Text,
+            otel_enabled: false,
+            otel_endpoint: "http://localhost:4318".to_string(),
+            otel_service_name: "sklonger".to_string(),
+            bluesky_api_url: "https://public.api.bsky.app".to_string(),
+            request_timeout_seconds: 10,
+            thread_max_posts: 0,
+            thread_fetch_timeout_seconds: 0,
+            upstream_max_concurrency: 0,
+            circuit_breaker_error_rate: 0.0,
+            circuit_breaker_min_calls: 20,
+            circuit_breaker_open_seconds: 30,
+            cache_backend: CacheBackend::None,
+            redis_url: "redis://localhost:6379".to_string(),
+            cache_thread_ttl_seconds: 30,
+            cache_handle_ttl_seconds: 3600,
+            health_check_interval_seconds: 10,
+            poll_enabled: true,
+            poll_initial_interval: 30,
+            poll_max_interval: 120,
+            poll_disable_after: 1800,
+            public_url: "https://sklonger.app".to_string(),
+            crawler_user_agents: DEFAULT_CRAWLER_USER_AGENTS
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            gemini_enabled: false,
+            gemini_port: 1965,
+            gemini_cert_path: "gemini-cert.pem".to_string(),
+            gemini_key_path: "gemini-key.pem".to_string(),
+            jetstream_enabled: false,
+            jetstream_url: "wss://jetstream2.us-east.bsky.network/subscribe".to_string(),
+            webhooks_enabled: false,
+            webhook_secret: "".to_string(),
+            webhooks_path: "webhooks.json".to_string(),
+            push_enabled: false,
+            vapid_private_key: "".to_string(),
+            vapid_subject: "".to_string(),
+            push_subscriptions_path: "push-subscriptions.json".to_string(),
+            metrics_enabled: false,
+            metrics_port: 0,
+            rate_limit_enabled: false,
+            rate_limit_trusted_proxies: 0,
+            rate_limit_pages_per_minute: 60,
+            rate_limit_polling_per_minute: 120,
+            rate_limit_crawlers_per_minute: 30,
+        }
+    }
+}
+
 impl Config {
+    /// Defaults overridden by environment variables.
     pub fn from_env() -> Result<Self, ConfigError> {
-        Ok(Self {
-            port: parse_env_or_default("PORT", 8080)?,
-            log_level: env_var_or_default("LOG_LEVEL", "info"),
-            bluesky_api_url: env_var_or_default("BLUESKY_API_URL", "https://public.api.bsky.app"),
-            request_timeout_seconds: parse_env_or_default("REQUEST_TIMEOUT_SECONDS", 10)?,
-            poll_enabled: parse_bool_env_or_default("POLL_ENABLED", true)?,
-            poll_initial_interval: parse_env_or_default("POLL_INITIAL_INTERVAL_SECONDS", 30)?,
-            poll_max_interval: parse_env_or_default("POLL_MAX_INTERVAL_SECONDS", 120)?,
-            poll_disable_after: parse_env_or_default("POLL_DISABLE_AFTER_SECONDS", 1800)?,
-            public_url: env_var_or_default("PUBLIC_URL", "https://sklonger.app"),
-        })
+        Self::load(None)
+    }
+
+    /// Defaults, then the TOML file at `path` (if any), then environment
+    /// variables. The result is not validated; see [`Config::validate`].
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let mut config = match path {
+            Some(path) => {
+                let text = fs::read_to_string(path)
+                    .map_err(|e| ConfigError::Read(path.display().to_string(), e))?;
+                toml::from_str(&text)
+                    .map_err(|e| ConfigError::File(path.display().to_string(), e))?
+            }
+            None => Self::default(),
+        };
+        config.apply_env()?;
+        Ok(config)
+    }
+
+    fn apply_env(&mut self) -> Result<(), ConfigError> {
+        self.port = parse_env_or_default("PORT", self.port)?;
+        self.log_level = env_var_or_default("LOG_LEVEL", &self.log_level);
+        self.log_format = parse_env_or_default("LOG_FORMAT", self.log_format)?;
+        self.otel_enabled = parse_bool_env_or_default("OTEL_ENABLED", self.otel_enabled)?;
+        self.otel_endpoint = env_var_or_default("OTEL_EXPORTER_OTLP_ENDPOINT", &self.otel_endpoint);
+        self.otel_service_name = env_var_or_default("OTEL_SERVICE_NAME", &self.otel_service_name);
+        self.bluesky_api_url = env_var_or_default("BLUESKY_API_URL", &self.bluesky_api_url);
+        self.request_timeout_seconds =
+            parse_env_or_default("REQUEST_TIMEOUT_SECONDS", self.request_timeout_seconds)?;
+        self.thread_max_posts = parse_env_or_default("THREAD_MAX_POSTS", self.thread_max_posts)?;
+        self.thread_fetch_timeout_seconds = parse_env_or_default(
+            "THREAD_FETCH_TIMEOUT_SECONDS",
+            self.thread_fetch_timeout_seconds,
+        )?;
+        self.upstream_max_concurrency =
+            parse_env_or_default("UPSTREAM_MAX_CONCURRENCY", self.upstream_max_concurrency)?;
+        self.circuit_breaker_error_rate = parse_env_or_default(
+            "CIRCUIT_BREAKER_ERROR_RATE",
+            self.circuit_breaker_error_rate,
+        )?;
+        self.circuit_breaker_min_calls =
+            parse_env_or_default("CIRCUIT_BREAKER_MIN_CALLS", self.circuit_breaker_min_calls)?;
+        self.circuit_breaker_open_seconds = parse_env_or_default(
+            "CIRCUIT_BREAKER_OPEN_SECONDS",
+            self.circuit_breaker_open_seconds,
+        )?;
+        self.cache_backend = parse_env_or_default("CACHE_BACKEND", self.cache_backend)?;
+        self.redis_url = env_var_or_default("REDIS_URL", &self.redis_url);
+        self.cache_thread_ttl_seconds =
+            parse_env_or_default("CACHE_THREAD_TTL_SECONDS", self.cache_thread_ttl_seconds)?;
+        self.cache_handle_ttl_seconds =
+            parse_env_or_default("CACHE_HANDLE_TTL_SECONDS", self.cache_handle_ttl_seconds)?;
+        self.health_check_interval_seconds = parse_env_or_default(
+            "HEALTH_CHECK_INTERVAL_SECONDS",
+            self.health_check_interval_seconds,
+        )?;
+        self.poll_enabled = parse_bool_env_or_default("POLL_ENABLED", self.poll_enabled)?;
+        self.poll_initial_interval =
+            parse_env_or_default("POLL_INITIAL_INTERVAL_SECONDS", self.poll_initial_interval)?;
+        self.poll_max_interval =
+            parse_env_or_default("POLL_MAX_INTERVAL_SECONDS", self.poll_max_interval)?;
+        self.poll_disable_after =
+            parse_env_or_default("POLL_DISABLE_AFTER_SECONDS", self.poll_disable_after)?;
+        self.public_url = env_var_or_default("PUBLIC_URL", &self.public_url);
+        self.crawler_user_agents =
+            list_env_or_default("CRAWLER_USER_AGENTS", &self.crawler_user_agents);
+        self.gemini_enabled = parse_bool_env_or_default("GEMINI_ENABLED", self.gemini_enabled)?;
+        self.gemini_port = parse_env_or_default("GEMINI_PORT", self.gemini_port)?;
+        self.gemini_cert_path = env_var_or_default("GEMINI_CERT_PATH", &self.gemini_cert_path);
+        self.gemini_key_path = env_var_or_default("GEMINI_KEY_PATH", &self.gemini_key_path);
+        self.jetstream_enabled =
+            parse_bool_env_or_default("JETSTREAM_ENABLED", self.jetstream_enabled)?;
+        self.jetstream_url = env_var_or_default("JETSTREAM_URL", &self.jetstream_url);
+        self.webhooks_enabled =
+            parse_bool_env_or_default("WEBHOOKS_ENABLED", self.webhooks_enabled)?;
+        self.webhook_secret = env_var_or_default("WEBHOOK_SECRET", &self.webhook_secret);
+        self.webhooks_path = env_var_or_default("WEBHOOKS_PATH", &self.webhooks_path);
+        self.push_enabled = parse_bool_env_or_default("PUSH_ENABLED", self.push_enabled)?;
+        self.vapid_private_key = env_var_or_default("VAPID_PRIVATE_KEY", &self.vapid_private_key);
+        self.vapid_subject = env_var_or_default("VAPID_SUBJECT", &self.vapid_subject);
+        self.push_subscriptions_path =
+            env_var_or_default("PUSH_SUBSCRIPTIONS_PATH", &self.push_subscriptions_path);
+        self.metrics_enabled = parse_bool_env_or_default("METRICS_ENABLED", self.metrics_enabled)?;
+        self.metrics_port = parse_env_or_default("METRICS_PORT", self.metrics_port)?;
+        self.rate_limit_enabled =
+            parse_bool_env_or_default("RATE_LIMIT_ENABLED", self.rate_limit_enabled)?;
+        self.rate_limit_trusted_proxies = parse_env_or_default(
+            "RATE_LIMIT_TRUSTED_PROXIES",
+            self.rate_limit_trusted_proxies,
+        )?;
+        self.rate_limit_pages_per_minute = parse_env_or_default(
+            "RATE_LIMIT_PAGES_PER_MINUTE",
+            self.rate_limit_pages_per_minute,
+        )?;
+        self.rate_limit_polling_per_minute = parse_env_or_default(
+            "RATE_LIMIT_POLLING_PER_MINUTE",
+            self.rate_limit_polling_per_minute,
+        )?;
+        self.rate_limit_crawlers_per_minute = parse_env_or_default(
+            "RATE_LIMIT_CRAWLERS_PER_MINUTE",
+            self.rate_limit_crawlers_per_minute,
+        )?;
+        Ok(())
+    }
+
+    /// Check that values make sense on their own and together. The error
+    /// names the first offending variable and why it was rejected.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        fn invalid(field: &'static str, reason: impl Into<String>) -> Result<(), ConfigError> {
+            Err(ConfigError::Invalid {
+                field,
+                reason: reason.into(),
+            })
+        }
+        fn check_url(field: &'static str, value: &str) -> Result<(), ConfigError> {
+            match Url::parse(value) {
+                Ok(_) => Ok(()),
+                Err(e) => invalid(field, format!("{:?} is not a valid URL ({})", value, e)),
+            }
+        }
+
+        if EnvFilter::try_new(&self.log_level).is_err() {
+            return invalid(
+                "LOG_LEVEL",
+                format!("{:?} is not a valid log filter", self.log_level),
+            );
+        }
+        check_url("BLUESKY_API_URL", &self.bluesky_api_url)?;
+        check_url("PUBLIC_URL", &self.public_url)?;
+        if self.otel_enabled {
+            check_url("OTEL_EXPORTER_OTLP_ENDPOINT", &self.otel_endpoint)?;
+        }
+        if self.jetstream_enabled {
+            check_url("JETSTREAM_URL", &self.jetstream_url)?;
+        }
+        if self.cache_backend == CacheBackend::Redis {
+            check_url("REDIS_URL", &self.redis_url)?;
+        }
+
+        // Thread watchers run on these intervals for SSE, webhooks and push
+        // even when client polling is off
+        if self.poll_initial_interval == 0 {
+            return invalid("POLL_INITIAL_INTERVAL_SECONDS", "must be at least 1");
+        }
+        if self.poll_initial_interval > self.poll_max_interval {
+            return invalid(
+                "POLL_INITIAL_INTERVAL_SECONDS",
+                format!(
+                    "{} is greater than POLL_MAX_INTERVAL_SECONDS ({})",
+                    self.poll_initial_interval, self.poll_max_interval
+                ),
+            );
+        }
+
+        if self.crawler_user_agents.iter().any(|p| p.trim().is_empty()) {
+            return invalid(
+                "CRAWLER_USER_AGENTS",
+                "an empty pattern would match every client",
+            );
+        }
+
+        if !(0.0..=1.0).contains(&self.circuit_breaker_error_rate) {
+            return invalid(
+                "CIRCUIT_BREAKER_ERROR_RATE",
+                format!("{} is not between 0 and 1", self.circuit_breaker_error_rate),
+            );
+        }
+        if self.circuit_breaker_error_rate > 0.0 && self.circuit_breaker_open_seconds == 0 {
+            return invalid("CIRCUIT_BREAKER_OPEN_SECONDS", "must be at least 1");
+        }
+
+        if self.health_check_interval_seconds == 0 {
+            return invalid("HEALTH_CHECK_INTERVAL_SECONDS", "must be at least 1");
+        }
+
+        if self.gemini_enabled && self.gemini_port == self.port {
+            return invalid(
+                "GEMINI_PORT",
+                format!("{} is already used by PORT", self.port),
+            );
+        }
+        if self.metrics_enabled && self.metrics_port == self.port {
+            return invalid(
+                "METRICS_PORT",
+                format!(
+                    "{} is already used by PORT (use 0 to serve metrics there)",
+                    self.port
+                ),
+            );
+        }
+
+        if self.webhooks_enabled && self.webhook_secret.is_empty() {
+            return invalid(
+                "WEBHOOK_SECRET",
+                "must be set when WEBHOOKS_ENABLED is true",
+            );
+        }
+        if self.push_enabled && self.vapid_private_key.is_empty() {
+            return invalid("VAPID_PRIVATE_KEY", "must be set when PUSH_ENABLED is true");
+        }
+        Ok(())
+    }
+
+    /// The configuration as TOML, in the format [`Config::load`] reads, with
+    /// secrets replaced by a placeholder.
+    pub fn redacted_toml(&self) -> String {
+        let mut table = self.to_table();
+        for key in SECRET_KEYS {
+            if table.get(*key).and_then(|v| v.as_str()) != Some("") {
+                table.insert(key.to_string(), REDACTED.into());
+            }
+        }
+        toml::to_string(&table).expect("config serializes to TOML")
+    }
+
+    /// The configuration keyed as in the config file.
+    pub(crate) fn to_table(&self) -> toml::Table {
+        toml::Table::try_from(self).expect("config serializes to a TOML table")
     }
 }
 
@@ -72,4 +505,59 @@ mod tests {
         assert!(err.to_string().contains("PORT"));
         assert!(err.to_string().contains("abc"));
     }
+
+    #[test]
+    fn test_log_format_parse() {
+        assert_eq!("json".parse(), Ok(LogFormat::Json));
+        assert_eq!("TEXT".parse(), Ok(LogFormat::Text));
+        assert!("yaml".parse::<LogFormat>().is_err());
+    }
+
+    #[test]
+    fn test_file_values_and_validation() {
+        let config: Config = toml::from_str(
+            "port = 9000\nlog_format = \"json\"\npoll_initial_interval_seconds = 300\n",
+        )
+        .unwrap();
+        assert_eq!(config.port, 9000);
+        assert_eq!(config.log_format, LogFormat::Json);
+        // Unset keys keep their defaults
+        assert_eq!(config.poll_max_interval, 120);
+
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("POLL_INITIAL_INTERVAL_SECONDS"));
+        assert!(err.contains("POLL_MAX_INTERVAL_SECONDS (120)"));
+
+        // Watchers still poll upstream with client polling off
+        let config = Config {
+            poll_enabled: false,
+            poll_max_interval: 0,
+            ..Config::default()
+        };
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("POLL_INITIAL_INTERVAL_SECONDS"));
+
+        // Typos are rejected rather than silently ignored
+        let err = toml::from_str::<Config>("prot = 9000").unwrap_err();
+        assert!(err.to_string().contains("prot"));
+    }
+
+    #[test]
+    fn test_redacted_toml_round_trips() {
+        let config = Config {
+            webhook_secret: "hunter2".to_string(),
+            ..Config::default()
+        };
+
+        let text = config.redacted_toml();
+        assert!(!text.contains("hunter2"));
+        assert!(text.contains("webhook_secret = \"<redacted>\""));
+        // An unset secret is shown as unset
+        assert!(text.contains("vapid_private_key = \"\""));
+
+        let parsed: Config = toml::from_str(&text).unwrap();
+        assert_eq!(parsed.poll_initial_interval, config.poll_initial_interval);
+        assert_eq!(parsed.otel_endpoint, config.otel_endpoint);
+        assert!(Config::default().validate().is_ok());
+    }
 }
@@ -1,5 +1,5 @@
 use axum::{
-    http::StatusCode,
+    http::{header::RETRY_AFTER, HeaderValue, StatusCode},
     response::{Html, IntoResponse, Response},
 };
 use thiserror::Error;
@@ -9,11 +9,16 @@ pub enum AppError {
     #[error("bad request: {0}")]
     BadRequest(String),
 
+    #[error("unauthorized")]
+    Unauthorized,
+
     #[error("not found: {0}")]
     NotFound(String),
 
+    /// Too many requests, upstream or from this client. `retry_after` (in
+    /// seconds) is sent as `Retry-After` when known.
     #[error("rate limited")]
-    RateLimited,
+    RateLimited { retry_after: Option<u64> },
 
     #[error("internal error: {0}")]
     Internal(#[from] anyhow::Error),
@@ -26,8 +31,13 @@ impl IntoResponse for AppError {
     fn into_response(self) -> Response {
         let (status, title, message) = match &self {
             AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, "Bad Request", msg.as_str()),
+            AppError::Unauthorized => (
+                StatusCode::UNAUTHORIZED,
+                "Unauthorized",
+                "A valid bearer token is required.",
+            ),
             AppError::NotFound(msg) => (StatusCode::NOT_FOUND, "Not Found", msg.as_str()),
-            AppError::RateLimited => (
+            AppError::RateLimited { .. } => (
                 StatusCode::TOO_MANY_REQUESTS,
                 "Too Many Requests",
                 "Rate limit exceeded. Please try again later.",
@@ -45,6 +55,15 @@ impl IntoResponse for AppError {
         };
 
         let html = crate::html::templates::error_page(status.as_u16(), title, message);
-        (status, Html(html)).into_response()
+        let mut response = (status, Html(html)).into_response();
+        if let AppError::RateLimited {
+            retry_after: Some(seconds),
+        } = self
+        {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(seconds));
+        }
+        response
     }
 }
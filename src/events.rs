@@ -0,0 +1,338 @@
+//! Server-pushed thread updates.
+//!
+//! Each `/api/thread/events` connection follows the shared thread watcher
+//! and diffs every snapshot against the posts the client already has, so
+//! only changes go over the wire. The polling endpoint uses the same diff.
+//! Outbound notifications (webhooks, Web Push) follow the same events but
+//! only care about new posts; see [`new_post_batches`].
+
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+use crate::bluesky::types::ThreadPost;
+use crate::watcher::Snapshot;
+
+/// A change to a watched thread.
+#[derive(Debug, Clone)]
+pub enum ThreadEvent {
+    /// A post was appended to the thread
+    NewPost(Box<ThreadPost>),
+    /// A post the client has now has a different CID
+    ChangedPost(Box<ThreadPost>),
+    /// A post the client has was deleted
+    DeletedPost { uri: String },
+    /// The last post is older than the configured window; no more events follow
+    Stale,
+}
+
+/// Most posts a polling client reports as known: the tail of its page. Keeps
+/// the query string short and the refresh to one `getPosts` batch.
+pub const KNOWN_POSTS_WINDOW: usize = 20;
+
+/// A post the client already shows, with the CID it was rendered from.
+/// The CID may be a suffix of the full CID, as sent by polling clients.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KnownPost {
+    pub uri: String,
+    pub cid: String,
+}
+
+impl From<&ThreadPost> for KnownPost {
+    fn from(post: &ThreadPost) -> Self {
+        Self {
+            uri: post.uri.clone(),
+            cid: post.cid.clone(),
+        }
+    }
+}
+
+/// Differences between what a client shows and the current thread.
+#[derive(Debug, Default)]
+pub struct ThreadDiff {
+    pub appended: Vec<ThreadPost>,
+    pub changed: Vec<ThreadPost>,
+    /// URIs of known posts missing from the thread
+    pub removed: Vec<String>,
+}
+
+impl ThreadDiff {
+    pub fn is_empty(&self) -> bool {
+        self.appended.is_empty() && self.changed.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Compare the posts a client has against the current posts of a thread.
+pub fn diff_thread(known: &[KnownPost], posts: &[ThreadPost]) -> ThreadDiff {
+    let mut diff = ThreadDiff {
+        removed: known
+            .iter()
+            .filter(|k| !posts.iter().any(|p| p.uri == k.uri))
+            .map(|k| k.uri.clone())
+            .collect(),
+        ..Default::default()
+    };
+
+    for post in posts {
+        match known.iter().find(|k| k.uri == post.uri) {
+            Some(k) if !post.cid.ends_with(&k.cid) => diff.changed.push(post.clone()),
+            Some(_) => {}
+            None => diff.appended.push(post.clone()),
+        }
+    }
+    diff
+}
+
+/// Like [`diff_thread`], for a client that only reports the tail of its page:
+/// posts before the first known one that still exists are left out.
+pub fn diff_thread_tail(known: &[KnownPost], posts: &[ThreadPost]) -> ThreadDiff {
+    let start = posts
+        .iter()
+        .position(|p| known.iter().any(|k| k.uri == p.uri))
+        .unwrap_or(0);
+    diff_thread(known, &posts[start..])
+}
+
+/// Events for the posts a client still needs from a freshly fetched thread,
+/// given the CID of the last post it received, along with every post it
+/// knows once they are sent.
+/// An unknown CID (e.g. that post was deleted) resends everything; the client
+/// replaces posts it already shows.
+fn initial_events(
+    posts: &[ThreadPost],
+    resume_cid: Option<&str>,
+) -> (Vec<KnownPost>, Vec<ThreadEvent>) {
+    let split = resume_cid
+        .and_then(|cid| posts.iter().position(|p| p.cid == cid))
+        .map(|idx| idx + 1)
+        .unwrap_or(0);
+
+    let known = posts.iter().map(KnownPost::from).collect();
+    let events = posts[split..]
+        .iter()
+        .map(|p| ThreadEvent::NewPost(Box::new(p.clone())))
+        .collect();
+    (known, events)
+}
+
+/// Diff a re-fetched thread against the posts already sent, updating `known`.
+fn diff_posts(known: &mut Vec<KnownPost>, posts: &[ThreadPost]) -> Vec<ThreadEvent> {
+    let diff = diff_thread(known, posts);
+    *known = posts.iter().map(KnownPost::from).collect();
+
+    diff.removed
+        .into_iter()
+        .map(|uri| ThreadEvent::DeletedPost { uri })
+        .chain(
+            diff.changed
+                .into_iter()
+                .map(|p| ThreadEvent::ChangedPost(Box::new(p))),
+        )
+        .chain(
+            diff.appended
+                .into_iter()
+                .map(|p| ThreadEvent::NewPost(Box::new(p))),
+        )
+        .collect()
+}
+
+/// Turn a watcher subscription into the events one client needs, starting
+/// after the CID of the last post it received.
+///
+/// The stream ends after a [`ThreadEvent::Stale`] event or when the watcher
+/// stops because the thread disappeared.
+pub fn thread_events(
+    mut rx: watch::Receiver<Snapshot>,
+    resume_cid: Option<String>,
+) -> impl futures::Stream<Item = ThreadEvent> {
+    async_stream::stream! {
+        let snapshot = rx.borrow_and_update().clone();
+        if snapshot.thread.truncated {
+            // The client may show posts past the cut-off, so there is nothing to diff
+            yield ThreadEvent::Stale;
+            return;
+        }
+        let (mut known, events) = initial_events(&snapshot.thread.posts, resume_cid.as_deref());
+        for event in events {
+            yield event;
+        }
+        if snapshot.stale {
+            yield ThreadEvent::Stale;
+            return;
+        }
+
+        while rx.changed().await.is_ok() {
+            let snapshot = rx.borrow_and_update().clone();
+            for event in diff_posts(&mut known, &snapshot.thread.posts) {
+                yield event;
+            }
+            if snapshot.stale {
+                yield ThreadEvent::Stale;
+                return;
+            }
+        }
+    }
+}
+
+/// How far a notification subscriber has got in a thread: the newest post it
+/// was notified about (or that existed when it subscribed).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FollowCursor {
+    pub last_cid: Option<String>,
+    /// Guards against renotifying older posts if the last one is deleted
+    pub last_post_at: Option<DateTime<Utc>>,
+}
+
+impl FollowCursor {
+    /// A cursor past every post currently in the thread.
+    pub fn at_end(posts: &[ThreadPost]) -> Self {
+        let mut cursor = Self::default();
+        cursor.advance(posts);
+        cursor
+    }
+
+    pub fn advance(&mut self, posts: &[ThreadPost]) {
+        if let Some(last) = posts.last() {
+            self.last_cid = Some(last.cid.clone());
+            self.last_post_at = Some(last.created_at);
+        }
+    }
+
+    fn is_new(&self, post: &ThreadPost) -> bool {
+        self.last_post_at.is_none_or(|last| post.created_at > last)
+    }
+}
+
+/// New posts from one watcher snapshot.
+#[derive(Debug)]
+pub struct PostBatch {
+    pub posts: Vec<ThreadPost>,
+    /// The thread went stale; this is the last batch
+    pub stale: bool,
+}
+
+/// Group the new posts of a watcher subscription per snapshot, starting after
+/// `cursor`. Batches may be empty when only `stale` is set. The stream ends
+/// after a stale batch, or without one when the thread disappears.
+pub fn new_post_batches(
+    rx: watch::Receiver<Snapshot>,
+    cursor: FollowCursor,
+) -> impl futures::Stream<Item = PostBatch> {
+    // Events from one snapshot are produced together
+    thread_events(rx, cursor.last_cid.clone())
+        .ready_chunks(64)
+        .map(move |events| {
+            let mut batch = PostBatch {
+                posts: Vec::new(),
+                stale: false,
+            };
+            for event in events {
+                match event {
+                    ThreadEvent::NewPost(post) if cursor.is_new(&post) => batch.posts.push(*post),
+                    ThreadEvent::Stale => batch.stale = true,
+                    _ => {}
+                }
+            }
+            batch
+        })
+        .filter(|batch| std::future::ready(batch.stale || !batch.posts.is_empty()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn post(id: &str) -> ThreadPost {
+        ThreadPost {
+            uri: format!("at://did:plc:abc/app.bsky.feed.post/{}", id),
+            cid: format!("cid-{}", id),
+            text: id.to_string(),
+            created_at: Utc::now(),
+            reply_count: None,
+            repost_count: None,
+            like_count: None,
+            embed: None,
+            langs: vec![],
+        }
+    }
+
+    fn new_post_ids(events: &[ThreadEvent]) -> Vec<&str> {
+        events
+            .iter()
+            .filter_map(|e| match e {
+                ThreadEvent::NewPost(p) => Some(p.text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_initial_events_resume_after_cid() {
+        let posts = vec![post("a"), post("b"), post("c")];
+        let (mut known, events) = initial_events(&posts, Some("cid-b"));
+        assert_eq!(known.len(), 3);
+        assert_eq!(new_post_ids(&events), vec!["c"]);
+        // Posts sent on resuming are not sent again with the next snapshot
+        assert!(diff_posts(&mut known, &posts).is_empty());
+
+        let (known, events) = initial_events(&posts, Some("cid-c"));
+        assert_eq!(known.len(), 3);
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn test_initial_events_unknown_cid_resends_all() {
+        let posts = vec![post("a"), post("b")];
+        let (known, events) = initial_events(&posts, Some("cid-gone"));
+        assert_eq!(known.len(), 2);
+        assert_eq!(new_post_ids(&events), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_diff_posts_reports_new_and_deleted() {
+        let (mut known, _) = initial_events(&[post("a"), post("b")], Some("cid-b"));
+        let events = diff_posts(&mut known, &[post("a"), post("c")]);
+
+        assert!(matches!(
+            &events[0],
+            ThreadEvent::DeletedPost { uri } if uri.ends_with("/b")
+        ));
+        assert_eq!(new_post_ids(&events), vec!["c"]);
+        assert!(diff_posts(&mut known, &[post("a"), post("c")]).is_empty());
+    }
+
+    #[test]
+    fn test_diff_thread_matches_cid_suffix() {
+        let mut edited = post("b");
+        edited.cid = "cid-b2".to_string();
+        let known = vec![
+            KnownPost {
+                uri: post("a").uri,
+                cid: "-a".to_string(),
+            },
+            KnownPost {
+                uri: post("b").uri,
+                cid: "-b".to_string(),
+            },
+        ];
+
+        let diff = diff_thread(&known, &[post("a"), edited, post("c")]);
+        assert!(diff.removed.is_empty());
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].cid, "cid-b2");
+        assert_eq!(diff.appended.len(), 1);
+    }
+
+    #[test]
+    fn test_diff_thread_tail_skips_posts_before_window() {
+        let known = vec![KnownPost::from(&post("c")), KnownPost::from(&post("d"))];
+        let diff = diff_thread_tail(&known, &[post("a"), post("b"), post("c"), post("e")]);
+
+        assert_eq!(diff.removed, vec![post("d").uri]);
+        assert!(diff.changed.is_empty());
+        assert_eq!(diff.appended.len(), 1);
+        assert_eq!(diff.appended[0].uri, post("e").uri);
+    }
+}
@@ -0,0 +1,224 @@
+//! Gemtext rendering of threads.
+//!
+//! Gemtext is line-oriented: only whole lines starting with `=>`, `#`, `*`,
+//! `>` or a code fence carry meaning, and links cannot appear inline. Post text
+//! is therefore escaped line by line and any URLs are repeated as link lines
+//! after the post.
+
+use std::sync::OnceLock;
+
+use crate::bluesky::types::{Embed, EmbedRecord, Thread, ThreadPost};
+
+/// Landing page shown at the Gemini root.
+pub fn landing_page() -> String {
+    "# sklonger\n\
+     \n\
+     Read Bluesky threads as single pages.\n\
+     \n\
+     => /thread Read a thread\n\
+     \n\
+     Or replace bsky.app with this capsule's host in any Bluesky post URL:\n\
+     => /profile/nameshiv.bsky.social/post/3l3dpw5bpmi2j /profile/{handle}/post/{post_id}\n"
+        .to_string()
+}
+
+pub fn render_thread(thread: &Thread) -> String {
+    let author = &thread.author;
+    let author_name = author.display_name.as_deref().unwrap_or(&author.handle);
+
+    let mut out = String::new();
+    out.push_str(&format!("# Thread by {}\n", single_line(author_name)));
+    out.push_str(&format!(
+        "@{} · {} posts\n",
+        author.handle,
+        thread.posts.len()
+    ));
+    out.push_str(&format!(
+        "=> {} Author profile on Bluesky\n",
+        author.profile_url()
+    ));
+
+    for post in &thread.posts {
+        out.push('\n');
+        out.push_str(&render_post(post, &author.handle));
+    }
+
+    out.push('\n');
+    if let Some(last) = thread.posts.last().filter(|_| thread.truncated) {
+        let post_id = last.uri.rsplit('/').next().unwrap_or("");
+        out.push_str("Thread truncated: this is as much as could be loaded at once.\n");
+        out.push_str(&format!(
+            "=> https://bsky.app/profile/{}/post/{} Continue on Bluesky\n",
+            author.handle, post_id
+        ));
+    }
+    if let Some(url) = thread.original_post_url() {
+        out.push_str(&format!("=> {} View original on Bluesky\n", url));
+    }
+    out.push_str("=> / sklonger home\n");
+    out
+}
+
+fn render_post(post: &ThreadPost, author_handle: &str) -> String {
+    let mut out = String::new();
+
+    for line in post.text.lines() {
+        out.push_str(&escape_line(line));
+        out.push('\n');
+    }
+
+    for url in find_urls(&post.text) {
+        out.push_str(&format!("=> {}\n", url));
+    }
+
+    if let Some(embed) = &post.embed {
+        out.push_str(&render_embed(embed));
+    }
+
+    let post_id = post.uri.rsplit('/').next().unwrap_or("");
+    out.push_str(&format!(
+        "=> https://bsky.app/profile/{}/post/{} {}\n",
+        author_handle,
+        post_id,
+        post.created_at.format("%b %d, %Y at %H:%M UTC")
+    ));
+    out
+}
+
+fn render_embed(embed: &Embed) -> String {
+    match embed {
+        Embed::Images(images) => images
+            .iter()
+            .map(|img| {
+                let label = if img.alt.is_empty() {
+                    "Image".to_string()
+                } else {
+                    format!("Image: {}", single_line(&img.alt))
+                };
+                format!("=> {} {}\n", img.fullsize_url, label)
+            })
+            .collect(),
+        Embed::Video(video) => {
+            let label = match video.alt.as_deref().filter(|a| !a.is_empty()) {
+                Some(alt) => format!("Video: {}", single_line(alt)),
+                None => "Video".to_string(),
+            };
+            format!("=> {} {}\n", video.playlist_url, label)
+        }
+        Embed::External(external) => {
+            let title = if external.title.is_empty() {
+                external.uri.as_str()
+            } else {
+                external.title.as_str()
+            };
+            format!("=> {} {}\n", external.uri, single_line(title))
+        }
+        Embed::Record(record) => render_record(record),
+        Embed::RecordWithMedia { record, media } => {
+            format!("{}{}", render_record(record), render_embed(media))
+        }
+    }
+}
+
+fn render_record(record: &EmbedRecord) -> String {
+    let mut out = String::new();
+    for line in record.text.lines() {
+        out.push_str(&format!("> {}\n", line));
+    }
+    let post_id = record.uri.rsplit('/').next().unwrap_or("");
+    out.push_str(&format!(
+        "=> https://bsky.app/profile/{}/post/{} Quoted post by @{}\n",
+        record.author.handle, post_id, record.author.handle
+    ));
+    out
+}
+
+/// Prevent a text line from being parsed as a gemtext link, heading, list
+/// item, quote or preformat toggle by prefixing a space.
+fn escape_line(line: &str) -> String {
+    const MARKERS: &[&str] = &["=>", "#", "*", ">", "```"];
+    if MARKERS.iter().any(|m| line.starts_with(m)) {
+        format!(" {}", line)
+    } else {
+        line.to_string()
+    }
+}
+
+/// Collapse a value onto one line for use in headings and link labels.
+fn single_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn find_urls(text: &str) -> Vec<&str> {
+    static URL_PATTERN: OnceLock<regex_lite::Regex> = OnceLock::new();
+    let pattern = URL_PATTERN
+        .get_or_init(|| regex_lite::Regex::new(r"https?://[^\s<>]+").expect("URL regex is valid"));
+    pattern.find_iter(text).map(|m| m.as_str()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bluesky::types::{Author, EmbedImage};
+    use chrono::Utc;
+
+    fn post(text: &str, embed: Option<Embed>) -> ThreadPost {
+        ThreadPost {
+            uri: "at://did:plc:abc/app.bsky.feed.post/3kxyz".to_string(),
+            cid: "bafycid".to_string(),
+            text: text.to_string(),
+            created_at: Utc::now(),
+            reply_count: None,
+            repost_count: None,
+            like_count: None,
+            embed,
+            langs: vec![],
+        }
+    }
+
+    fn thread(posts: Vec<ThreadPost>) -> Thread {
+        Thread {
+            posts,
+            author: Author {
+                did: "did:plc:abc".to_string(),
+                handle: "user.bsky.social".to_string(),
+                display_name: Some("Some\nUser".to_string()),
+                avatar_url: None,
+            },
+            truncated: false,
+        }
+    }
+
+    #[test]
+    fn test_escapes_gemtext_markers() {
+        let out = render_thread(&thread(vec![post(
+            "# not a heading\n=> not a link\nplain",
+            None,
+        )]));
+        assert!(out.contains("\n # not a heading\n"));
+        assert!(out.contains("\n => not a link\n"));
+        assert!(out.contains("\nplain\n"));
+    }
+
+    #[test]
+    fn test_urls_become_link_lines() {
+        let out = render_thread(&thread(vec![post("read https://example.com/a then", None)]));
+        assert!(out.contains("\n=> https://example.com/a\n"));
+    }
+
+    #[test]
+    fn test_images_and_heading() {
+        let image = EmbedImage {
+            thumb_url: "https://cdn/thumb".to_string(),
+            fullsize_url: "https://cdn/full".to_string(),
+            alt: "a cat".to_string(),
+            aspect_ratio: None,
+        };
+        let out = render_thread(&thread(vec![post("hi", Some(Embed::Images(vec![image])))]));
+        assert!(out.starts_with("# Thread by Some User\n"));
+        assert!(out.contains("=> https://cdn/full Image: a cat\n"));
+        assert!(out.contains(
+            "=> https://bsky.app/profile/user.bsky.social/post/3kxyz View original on Bluesky"
+        ));
+    }
+}
@@ -0,0 +1,9 @@
+//! Optional Gemini protocol frontend.
+//!
+//! Serves gemtext renderings of threads over TLS on a second listener,
+//! sharing the HTTP server's `BlueskyClient` and `Config`.
+
+pub mod gemtext;
+pub mod server;
+
+pub use server::{load_tls_acceptor, serve, GeminiError};
@@ -0,0 +1,315 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use percent_encoding::percent_decode_str;
+use rustls_pki_types::pem::PemObject;
+use rustls_pki_types::{CertificateDer, PrivateKeyDer};
+use thiserror::Error;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::rustls;
+use tokio_rustls::TlsAcceptor;
+use tracing::{debug, info, warn};
+use url::Url;
+
+use super::gemtext;
+use crate::bluesky::client::ClientError;
+use crate::bluesky::parse_bluesky_url;
+use crate::AppState;
+
+/// Requests are a single absolute URL of at most 1024 bytes followed by CRLF.
+const MAX_REQUEST_LEN: usize = 1024 + 2;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Pause after a failed accept (e.g. out of file descriptors) before retrying.
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+#[derive(Error, Debug)]
+pub enum GeminiError {
+    #[error("failed to read TLS certificate or key: {0}")]
+    Pem(#[from] rustls_pki_types::pem::Error),
+    #[error("invalid TLS configuration: {0}")]
+    Tls(#[from] rustls::Error),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("timed out waiting for request")]
+    Timeout,
+    #[error("malformed request")]
+    MalformedRequest,
+}
+
+/// Build a TLS acceptor from PEM files.
+/// Gemini clients use trust-on-first-use, so a self-signed certificate is fine.
+pub fn load_tls_acceptor(cert_path: &str, key_path: &str) -> Result<TlsAcceptor, GeminiError> {
+    let certs = CertificateDer::pem_file_iter(cert_path)?.collect::<Result<Vec<_>, _>>()?;
+    let key = PrivateKeyDer::from_pem_file(key_path)?;
+
+    let config = rustls::ServerConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()?
+    .with_no_client_auth()
+    .with_single_cert(certs, key)?;
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Accept Gemini connections for as long as the process runs.
+/// Each connection carries exactly one request, as the protocol requires.
+pub async fn serve(listener: TcpListener, acceptor: TlsAcceptor, state: AppState) {
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                warn!(error = %e, "failed to accept gemini connection");
+                tokio::time::sleep(ACCEPT_BACKOFF).await;
+                continue;
+            }
+        };
+        let acceptor = acceptor.clone();
+        let state = state.clone();
+
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, acceptor, &state).await {
+                debug!(peer = %peer, error = %e, "gemini connection failed");
+            }
+        });
+    }
+}
+
+async fn handle_connection(
+    stream: TcpStream,
+    acceptor: TlsAcceptor,
+    state: &AppState,
+) -> Result<(), GeminiError> {
+    let mut tls = tokio::time::timeout(REQUEST_TIMEOUT, acceptor.accept(stream))
+        .await
+        .map_err(|_| GeminiError::Timeout)??;
+
+    let response = match tokio::time::timeout(REQUEST_TIMEOUT, read_request_line(&mut tls)).await {
+        Ok(Ok(line)) => respond(state, &line).await,
+        Ok(Err(GeminiError::MalformedRequest)) => Response::new(59, "Bad request"),
+        Ok(Err(e)) => return Err(e),
+        Err(_) => return Err(GeminiError::Timeout),
+    };
+
+    tls.write_all(format!("{} {}\r\n", response.status, response.meta).as_bytes())
+        .await?;
+    if let Some(body) = response.body {
+        tls.write_all(body.as_bytes()).await?;
+    }
+    tls.shutdown().await?;
+    Ok(())
+}
+
+async fn read_request_line<R: AsyncReadExt + Unpin>(reader: &mut R) -> Result<String, GeminiError> {
+    let mut buf = Vec::with_capacity(128);
+    let mut byte = [0u8; 1];
+
+    while !buf.ends_with(b"\r\n") {
+        if buf.len() >= MAX_REQUEST_LEN || reader.read(&mut byte).await? == 0 {
+            return Err(GeminiError::MalformedRequest);
+        }
+        buf.push(byte[0]);
+    }
+    buf.truncate(buf.len() - 2);
+
+    String::from_utf8(buf).map_err(|_| GeminiError::MalformedRequest)
+}
+
+/// A Gemini response header plus optional body (only sent for 2x statuses).
+#[derive(Debug, PartialEq)]
+struct Response {
+    status: u8,
+    meta: String,
+    body: Option<String>,
+}
+
+impl Response {
+    fn new(status: u8, meta: &str) -> Self {
+        Self {
+            status,
+            meta: meta.to_string(),
+            body: None,
+        }
+    }
+
+    /// A 20 response with a gemtext body. `lang` comes from the post record,
+    /// so it is left out of the header unless it looks like a language tag.
+    fn gemtext(body: String, lang: Option<&str>) -> Self {
+        let meta = match lang.filter(|lang| is_language_tag(lang)) {
+            Some(lang) => format!("text/gemini; charset=utf-8; lang={}", lang),
+            None => "text/gemini; charset=utf-8".to_string(),
+        };
+        Self {
+            status: 20,
+            meta,
+            body: Some(body),
+        }
+    }
+}
+
+/// Whether `lang` has the shape of a BCP 47 tag: letters, digits and
+/// hyphens, at most 35 characters.
+fn is_language_tag(lang: &str) -> bool {
+    (1..=35).contains(&lang.len()) && lang.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-')
+}
+
+/// Gemini routes mirror the HTTP ones.
+#[derive(Debug, PartialEq)]
+enum Route {
+    Landing,
+    /// `/thread` without input: ask the client for a Bluesky URL
+    ThreadPrompt,
+    /// `/thread?<url>` or `/?<url>`: redirect to the thread path
+    ThreadLookup(String),
+    Thread {
+        handle: String,
+        post_id: String,
+    },
+    NotFound,
+}
+
+fn route(url: &Url) -> Route {
+    let query = url
+        .query()
+        .map(|q| percent_decode_str(q).decode_utf8_lossy().into_owned())
+        .filter(|q| !q.is_empty());
+
+    let segments: Vec<String> = url
+        .path_segments()
+        .map(|s| {
+            s.filter(|seg| !seg.is_empty())
+                .map(|seg| percent_decode_str(seg).decode_utf8_lossy().into_owned())
+                .collect()
+        })
+        .unwrap_or_default();
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+
+    match (segments.as_slice(), query) {
+        ([] | ["thread"], Some(input)) => Route::ThreadLookup(input),
+        ([], None) => Route::Landing,
+        (["thread"], None) => Route::ThreadPrompt,
+        (["profile", handle, "post", post_id], _)
+        | (["lite" | "embed", "profile", handle, "post", post_id], _) => Route::Thread {
+            handle: handle.to_string(),
+            post_id: post_id.to_string(),
+        },
+        _ => Route::NotFound,
+    }
+}
+
+async fn respond(state: &AppState, request_line: &str) -> Response {
+    let url = match Url::parse(request_line) {
+        Ok(url) => url,
+        Err(_) => return Response::new(59, "Bad request"),
+    };
+    if url.scheme() != "gemini" {
+        return Response::new(53, "Proxy request refused");
+    }
+
+    match route(&url) {
+        Route::Landing => Response::gemtext(gemtext::landing_page(), Some("en")),
+        Route::ThreadPrompt => Response::new(10, "Bluesky post URL"),
+        Route::ThreadLookup(input) => match parse_bluesky_url(input.trim()) {
+            Ok(parsed) => Response::new(
+                30,
+                &format!("/profile/{}/post/{}", parsed.handle, parsed.post_id),
+            ),
+            Err(e) => Response::new(59, &e.to_string()),
+        },
+        Route::Thread { handle, post_id } => {
+            info!(handle = %handle, post_id = %post_id, "fetching thread (gemini)");
+            match state.client.get_thread_by_handle(&handle, &post_id).await {
+                Ok(thread) => {
+                    Response::gemtext(gemtext::render_thread(&thread), thread.primary_language())
+                }
+                Err(e) => map_client_error(e),
+            }
+        }
+        Route::NotFound => Response::new(51, "Not found"),
+    }
+}
+
+fn map_client_error(e: ClientError) -> Response {
+    warn!(error = %e, "failed to fetch thread (gemini)");
+    match e {
+        ClientError::NotFound => Response::new(51, "Post not found or deleted"),
+        ClientError::Blocked => Response::new(51, "Post is blocked"),
+        ClientError::RateLimited => Response::new(44, "60"),
+        ClientError::CircuitOpen => Response::new(41, "Bluesky is having trouble right now"),
+        _ => Response::new(43, "Cannot reach Bluesky API"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route_of(url: &str) -> Route {
+        route(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn test_route_thread_paths() {
+        let expected = Route::Thread {
+            handle: "user.bsky.social".to_string(),
+            post_id: "abc123".to_string(),
+        };
+        assert_eq!(
+            route_of("gemini://host/profile/user.bsky.social/post/abc123"),
+            expected
+        );
+        assert_eq!(
+            route_of("gemini://host/lite/profile/user.bsky.social/post/abc123"),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_route_landing_and_prompt() {
+        assert_eq!(route_of("gemini://host/"), Route::Landing);
+        assert_eq!(route_of("gemini://host"), Route::Landing);
+        assert_eq!(route_of("gemini://host/thread"), Route::ThreadPrompt);
+        assert_eq!(route_of("gemini://host/nope"), Route::NotFound);
+    }
+
+    #[test]
+    fn test_route_decodes_input() {
+        assert_eq!(
+            route_of("gemini://host/thread?https%3A%2F%2Fbsky.app%2Fprofile%2Fa%2Fpost%2Fb"),
+            Route::ThreadLookup("https://bsky.app/profile/a/post/b".to_string())
+        );
+    }
+
+    #[test]
+    fn test_gemtext_lang_must_be_a_tag() {
+        let meta = |lang| Response::gemtext(String::new(), Some(lang)).meta;
+        assert_eq!(meta("pt-BR"), "text/gemini; charset=utf-8; lang=pt-BR");
+        assert_eq!(meta("en\r\n20 text/html"), "text/gemini; charset=utf-8");
+        assert_eq!(meta("en; charset=latin1"), "text/gemini; charset=utf-8");
+        assert_eq!(meta(""), "text/gemini; charset=utf-8");
+        assert_eq!(meta(&"a".repeat(36)), "text/gemini; charset=utf-8");
+    }
+
+    #[tokio::test]
+    async fn test_read_request_line() {
+        let mut input: &[u8] = b"gemini://host/\r\nextra";
+        assert_eq!(
+            read_request_line(&mut input).await.unwrap(),
+            "gemini://host/"
+        );
+
+        let mut unterminated: &[u8] = b"gemini://host/";
+        assert!(matches!(
+            read_request_line(&mut unterminated).await,
+            Err(GeminiError::MalformedRequest)
+        ));
+
+        let long = format!("gemini://host/{}\r\n", "a".repeat(2000));
+        let mut too_long = long.as_bytes();
+        assert!(matches!(
+            read_request_line(&mut too_long).await,
+            Err(GeminiError::MalformedRequest)
+        ));
+    }
+}
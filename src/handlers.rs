@@ -1,27 +1,60 @@
+use axum::response::sse::{Event, KeepAlive, Sse};
 use axum::{
     body::Body,
-    extract::{Path, Query, State},
-    http::{header::USER_AGENT, HeaderMap, StatusCode},
+    extract::{ConnectInfo, Path, Query, State},
+    http::{
+        header::{AUTHORIZATION, CACHE_CONTROL, CONTENT_SECURITY_POLICY, USER_AGENT},
+        HeaderMap, HeaderValue, StatusCode,
+    },
     response::{Html, IntoResponse, Redirect, Response},
+    Extension, Json,
 };
 use chrono::{DateTime, Utc};
-use serde::Deserialize;
-use tracing::{info, warn};
+use futures::stream::{Stream, StreamExt as _};
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tracing::{info, warn, Instrument};
 
 use crate::bluesky::client::ClientError;
 use crate::bluesky::parse_bluesky_url;
-use crate::bluesky::types::StreamEvent;
+use crate::bluesky::types::{StreamEvent, Thread, ThreadPost};
+use crate::conditional::Validators;
+use crate::config::{CacheBackend, Config};
 use crate::error::AppError;
+use crate::events::{
+    diff_thread_tail, thread_events, KnownPost, ThreadDiff, ThreadEvent, KNOWN_POSTS_WINDOW,
+};
+use crate::health::{AppViewHealth, CacheHealth};
 use crate::html::{
-    landing_page, render_post, render_thread, streaming_error, streaming_footer, streaming_head,
-    streaming_loading_indicator, streaming_post_before_indicator, PollingConfig,
+    landing_page, render_post, render_profile, render_thread, render_thread_embed,
+    render_thread_lite, streaming_error, streaming_footer, streaming_head,
+    streaming_loading_indicator, streaming_post_before_indicator, truncation_notice, PollingConfig,
     StreamingHeadOptions,
 };
+use crate::metrics::metrics;
+use crate::push::{PushError, PushService, PushSubscription};
+use crate::webhooks::{Subscription, WebhookError, Webhooks};
 use crate::AppState;
 
 #[derive(Deserialize)]
 pub struct ThreadQuery {
     pub url: Option<String>,
+    /// Redirect to the lite reader instead of the full page
+    pub lite: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct LiteQuery {
+    pub lite: Option<String>,
+    /// Show only the posts after this URI, to load more of a truncated thread
+    pub after: Option<String>,
+}
+
+/// Interpret a `lite` query flag (`?lite=1`, `?lite=true`, or bare `?lite`).
+fn is_lite_requested(flag: Option<&str>) -> bool {
+    matches!(flag, Some("" | "1" | "true" | "yes"))
 }
 
 #[derive(Deserialize)]
@@ -30,35 +63,86 @@ pub struct ThreadPath {
     pub post_id: String,
 }
 
+#[derive(Deserialize)]
+pub struct ProfilePath {
+    pub handle: String,
+}
+
+#[derive(Deserialize)]
+pub struct ProfileQuery {
+    /// Author feed cursor for older pages
+    pub cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct OgImagePath {
+    pub handle: String,
+    /// Post ID with a `.png` extension
+    pub image: String,
+}
+
 #[derive(Deserialize)]
 pub struct ThreadUpdatesQuery {
     pub handle: String,
     pub post_id: String,
     pub since_cid: String,
+    /// AT URI of the last post the page has; lets the thread be followed
+    /// forward from there instead of re-walked from the root
+    pub since_uri: Option<String>,
+    /// The last posts the page shows, as comma-separated `rkey:cid-suffix`
+    /// pairs. When present, changes to and deletions of those posts are
+    /// reported as well. Only the last `KNOWN_POSTS_WINDOW` are used.
+    pub known: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateWebhookRequest {
+    /// Bluesky URL of the thread to follow
+    pub thread_url: String,
+    /// Where signed notifications are POSTed
+    pub callback_url: String,
+}
+
+#[derive(Deserialize)]
+pub struct ThreadContinueQuery {
+    pub handle: String,
+    /// Record key of the thread's first post
+    pub post_id: String,
+    /// URI of the last post the page rendered
+    pub after: String,
+}
+
+#[derive(Deserialize)]
+pub struct PushSubscribeRequest {
+    pub handle: String,
+    pub post_id: String,
+    pub subscription: PushSubscription,
+}
+
+#[derive(Deserialize)]
+pub struct PushUnsubscribeRequest {
+    pub handle: String,
+    pub post_id: String,
+    pub endpoint: String,
+}
+
+#[derive(Deserialize)]
+pub struct ThreadEventsQuery {
+    pub handle: String,
+    pub post_id: String,
+    /// CID of the last post the page has; `Last-Event-ID` takes precedence on reconnect
+    pub since_cid: Option<String>,
 }
 
-/// Common social media crawler User-Agent patterns.
-/// These crawlers fetch pages to generate link previews.
-const SOCIAL_CRAWLER_PATTERNS: &[&str] = &[
-    "Twitterbot",
-    "facebookexternalhit",
-    "LinkedInBot",
-    "WhatsApp",
-    "Slackbot",
-    "TelegramBot",
-    "Discordbot",
-    // Some crawlers for general link previews
-    "Googlebot",
-    "bingbot",
-    "Applebot",
-];
-
-/// Check if a User-Agent string belongs to a social media crawler.
+/// Interval between SSE comment lines that keep idle connections open through proxies.
+const EVENTS_HEARTBEAT: Duration = Duration::from_secs(15);
+
+/// Check if a User-Agent string contains one of `patterns` (`CRAWLER_USER_AGENTS`).
 /// These crawlers are served the non-streaming path for proper Open Graph tags.
-fn is_social_crawler(user_agent: &str) -> bool {
-    SOCIAL_CRAWLER_PATTERNS
+pub(crate) fn is_social_crawler(user_agent: &str, patterns: &[String]) -> bool {
+    patterns
         .iter()
-        .any(|pattern| user_agent.contains(pattern))
+        .any(|pattern| user_agent.contains(pattern.as_str()))
 }
 
 fn map_client_error(e: ClientError) -> AppError {
@@ -66,7 +150,12 @@ fn map_client_error(e: ClientError) -> AppError {
     match &e {
         ClientError::NotFound => AppError::NotFound("post not found or deleted".to_string()),
         ClientError::Blocked => AppError::NotFound("post is blocked".to_string()),
-        ClientError::RateLimited => AppError::RateLimited,
+        ClientError::RateLimited => AppError::RateLimited { retry_after: None },
+        ClientError::CircuitOpen => AppError::ServiceUnavailable(
+            "Bluesky is having trouble right now, so this page can't be loaded. \
+             Please try again in a minute."
+                .to_string(),
+        ),
         ClientError::Http(err) if err.is_connect() => {
             AppError::ServiceUnavailable("cannot reach Bluesky API".to_string())
         }
@@ -85,15 +174,29 @@ pub async fn get_thread(Query(params): Query<ThreadQuery>) -> Result<Response, A
 
     let parsed = parse_bluesky_url(&url).map_err(|e| AppError::BadRequest(e.to_string()))?;
 
-    let redirect_path = format!("/profile/{}/post/{}", parsed.handle, parsed.post_id);
+    let prefix = if is_lite_requested(params.lite.as_deref()) {
+        "/lite"
+    } else {
+        ""
+    };
+    let redirect_path = format!(
+        "{}/profile/{}/post/{}",
+        prefix, parsed.handle, parsed.post_id
+    );
     Ok(Redirect::to(&redirect_path).into_response())
 }
 
+/// Rendered thread pages may be cached briefly by browsers and CDNs
+const THREAD_PAGE_CACHE_CONTROL: &str = "public, max-age=300";
+/// Updates are revalidated on every poll; the ETag makes that a cheap 304
+const UPDATES_CACHE_CONTROL: &str = "no-cache";
+
 async fn fetch_and_render_thread(
     state: &AppState,
     handle: &str,
     post_id: &str,
-) -> Result<Html<String>, AppError> {
+    headers: &HeaderMap,
+) -> Result<Response, AppError> {
     let thread = state
         .client
         .get_thread_by_handle(handle, post_id)
@@ -106,76 +209,597 @@ async fn fetch_and_render_thread(
         "thread fetched successfully"
     );
 
-    let html = render_thread(&thread, &state.config.public_url);
-    Ok(Html(html))
+    Ok(cacheable_page(&thread, headers, || {
+        render_thread(&thread, &state.config.load().public_url)
+    }))
 }
 
-/// Handler for polling thread updates.
-/// Returns new posts (if any) since the given CID as HTML fragments.
-pub async fn get_thread_updates(
+/// Respond with a rendered thread page carrying validators for the thread,
+/// or 304 if the client's copy is current.
+fn cacheable_page(
+    thread: &Thread,
+    headers: &HeaderMap,
+    render: impl FnOnce() -> String,
+) -> Response {
+    let validators = Validators::for_posts(&thread.posts);
+    let mut response = if validators.not_modified(headers) {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        Html(render()).into_response()
+    };
+    validators.apply(response.headers_mut());
+    response.headers_mut().insert(
+        CACHE_CONTROL,
+        HeaderValue::from_static(THREAD_PAGE_CACHE_CONTROL),
+    );
+    response
+}
+
+/// Handler for the no-JavaScript lite reader (`/lite/profile/...` or `?lite=1`).
+pub async fn get_thread_lite(
     State(state): State<AppState>,
-    Query(params): Query<ThreadUpdatesQuery>,
+    Path(params): Path<ThreadPath>,
+    Query(query): Query<LiteQuery>,
+    headers: HeaderMap,
 ) -> Result<Response, AppError> {
-    let thread = state
+    info!(handle = %params.handle, post_id = %params.post_id, "fetching thread (lite)");
+
+    let thread = match query.after {
+        Some(after) => {
+            let did = state
+                .client
+                .resolve_handle(&params.handle)
+                .await
+                .map_err(map_client_error)?;
+            if !is_lite_cursor(&after, &did, &params.post_id) {
+                return Err(AppError::BadRequest(
+                    "invalid continuation cursor".to_string(),
+                ));
+            }
+            let thread = state
+                .client
+                .get_thread_after(&after)
+                .await
+                .map_err(map_client_error)?;
+            if thread.truncated && thread.posts.is_empty() {
+                return Err(AppError::ServiceUnavailable(
+                    "request timed out".to_string(),
+                ));
+            }
+            thread
+        }
+        None => state
+            .client
+            .get_thread_by_handle(&params.handle, &params.post_id)
+            .await
+            .map_err(map_client_error)?,
+    };
+
+    Ok(cacheable_page(&thread, &headers, || {
+        render_thread_lite(&thread, &state.config.load().public_url)
+    }))
+}
+
+/// Whether `after` is the post the lite page is for. "Load more" links put
+/// the last loaded post both in the path and in `after`, so a cursor naming
+/// any other post (possibly from another thread) is rejected.
+fn is_lite_cursor(after: &str, did: &str, post_id: &str) -> bool {
+    after == format!("at://{}/app.bsky.feed.post/{}", did, post_id)
+}
+
+/// Handler for the iframe-embeddable thread view.
+/// Error pages are also frameable so a broken embed shows a message instead of a blank frame.
+pub async fn get_thread_embed(
+    State(state): State<AppState>,
+    Path(params): Path<ThreadPath>,
+) -> Response {
+    info!(handle = %params.handle, post_id = %params.post_id, "fetching thread (embed)");
+
+    let result = state
         .client
         .get_thread_by_handle(&params.handle, &params.post_id)
         .await
+        .map_err(map_client_error);
+
+    let mut response = match result {
+        Ok(thread) => Html(render_thread_embed(
+            &thread,
+            &state.config.load().public_url,
+        ))
+        .into_response(),
+        Err(e) => e.into_response(),
+    };
+
+    response.headers_mut().insert(
+        CONTENT_SECURITY_POLICY,
+        HeaderValue::from_static("frame-ancestors *"),
+    );
+    response
+}
+
+/// Serve a generated Open Graph preview card for a thread as a PNG.
+/// The route is `/og/profile/{handle}/post/{post_id}.png`.
+pub async fn get_thread_og_image(
+    State(state): State<AppState>,
+    Path(params): Path<OgImagePath>,
+) -> Result<Response, AppError> {
+    let post_id = params
+        .image
+        .strip_suffix(".png")
+        .filter(|id| !id.is_empty())
+        .ok_or_else(|| AppError::NotFound("preview image not found".to_string()))?;
+
+    let thread = state
+        .client
+        .get_thread_by_handle(&params.handle, post_id)
+        .await
         .map_err(map_client_error)?;
 
-    // Capture last post timestamp before posts are consumed by into_iter
-    let last_post_time = thread.posts.last().map(|p| p.created_at);
+    // Rasterizing is CPU-bound, so keep it off the async worker threads
+    let png = tokio::task::spawn_blocking(move || {
+        let author = &thread.author;
+        crate::og::render_thread_card(&crate::og::ThreadCard {
+            display_name: author.display_name.as_deref().unwrap_or(&author.handle),
+            handle: &author.handle,
+            excerpt: thread.posts.first().map(|p| p.text.as_str()).unwrap_or(""),
+            post_count: thread.posts.len(),
+        })
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.into()))?
+    .map_err(|e| AppError::Internal(e.into()))?;
+
+    Ok((
+        [
+            (axum::http::header::CONTENT_TYPE, "image/png"),
+            (axum::http::header::CACHE_CONTROL, "public, max-age=3600"),
+        ],
+        png,
+    )
+        .into_response())
+}
 
-    // Find posts after the since_cid
-    let since_idx = thread.posts.iter().position(|p| p.cid == params.since_cid);
+/// Handler for an author's page listing their recent threads.
+pub async fn get_profile(
+    State(state): State<AppState>,
+    Path(params): Path<ProfilePath>,
+    Query(query): Query<ProfileQuery>,
+) -> Result<Html<String>, AppError> {
+    info!(handle = %params.handle, cursor = ?query.cursor, "fetching profile");
 
-    let new_posts: Vec<_> = match since_idx {
-        Some(idx) => thread.posts.into_iter().skip(idx + 1).collect(),
-        None => {
-            // CID not found - return all posts (thread might have been restructured)
-            thread.posts
-        }
+    let cursor = query.cursor.as_deref().filter(|c| !c.is_empty());
+    let (profile, page) = tokio::join!(
+        state.client.get_profile(&params.handle),
+        state.client.get_author_threads(&params.handle, cursor),
+    );
+    let map_error = |e: ClientError| match e {
+        ClientError::NotFound => AppError::NotFound("profile not found".to_string()),
+        e => map_client_error(e),
     };
+    let profile = profile.map_err(map_error)?;
+    let page = page.map_err(map_error)?;
+
+    Ok(Html(render_profile(
+        &profile,
+        &page,
+        cursor,
+        &state.config.load().public_url,
+    )))
+}
 
-    if new_posts.is_empty() {
-        // Check if the thread has gone stale (last post older than threshold)
-        let is_stale = last_post_time
-            .map(|ts| {
-                Utc::now().signed_duration_since(ts).num_seconds()
-                    >= state.config.poll_disable_after as i64
-            })
-            .unwrap_or(true);
+/// Handler for polling thread updates.
+///
+/// Returns new posts (if any) since the given post as HTML fragments. With
+/// `known`, those of its posts whose CID changed are included too (the page
+/// replaces them by `data-uri`) and deleted ones are listed in `X-Removed-URIs`.
+///
+/// If a watcher is already running for the thread its snapshot is used.
+/// Otherwise, with `since_uri` the thread is followed forward from the page's
+/// posts (a few upstream calls); without it, or when those posts are gone, a
+/// watcher is started from a full walk of the thread.
+pub async fn get_thread_updates(
+    State(state): State<AppState>,
+    Query(params): Query<ThreadUpdatesQuery>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let since_uri = params.since_uri.as_deref().filter(|uri| !uri.is_empty());
+    let known = params.known.as_deref().map(parse_known_posts);
+
+    let current = current_thread_posts(&state, &params, since_uri, known.as_deref()).await?;
+    let posts = &current.posts;
+    let is_stale = is_thread_stale(posts, state.config.load().poll_disable_after);
+
+    // The response is fully determined by the query and the thread's version,
+    // so a client that already has it needs nothing rendered
+    let validators = Validators::for_posts(posts);
+    if validators.not_modified(&headers) {
+        let mut response = Response::builder()
+            .status(StatusCode::NOT_MODIFIED)
+            .header(CACHE_CONTROL, UPDATES_CACHE_CONTROL)
+            .header("X-Thread-Stale", if is_stale { "true" } else { "false" })
+            .body(Body::empty())
+            .unwrap();
+        validators.apply(response.headers_mut());
+        return Ok(response);
+    }
+    // Posts are all by the thread author, so they share a URI prefix
+    let uri_prefix = posts
+        .first()
+        .and_then(|p| p.uri.rsplit_once('/'))
+        .map(|(prefix, _)| prefix.to_string())
+        .unwrap_or_default();
+
+    let diff = match &known {
+        Some(known) => {
+            let known: Vec<KnownPost> = known
+                .iter()
+                .map(|(rkey, cid)| KnownPost {
+                    uri: format!("{}/{}", uri_prefix, rkey),
+                    cid: cid.clone(),
+                })
+                .collect();
+            diff_thread_tail(&known, posts)
+        }
+        None => ThreadDiff {
+            appended: posts_after(posts, since_uri, &params.since_cid).to_vec(),
+            ..Default::default()
+        },
+    };
 
-        return Ok(Response::builder()
+    if diff.is_empty() {
+        let mut response = Response::builder()
             .status(StatusCode::NO_CONTENT)
+            .header(CACHE_CONTROL, UPDATES_CACHE_CONTROL)
             .header("X-Thread-Stale", if is_stale { "true" } else { "false" })
             .body(Body::empty())
-            .unwrap());
+            .unwrap();
+        validators.apply(response.headers_mut());
+        return Ok(response);
     }
 
-    // Safe to unwrap: we already checked that new_posts is not empty
-    let last_cid = &new_posts.last().unwrap().cid;
-    let post_count = new_posts.len();
-
-    // Render posts as HTML fragments
-    let html: String = new_posts
+    // Render changed and new posts as HTML fragments
+    let html: String = diff
+        .changed
         .iter()
-        .map(|post| render_post(post, &thread.author.handle))
+        .chain(&diff.appended)
+        .map(|post| render_post(post, &current.author_handle))
         .collect();
 
     info!(
         handle = %params.handle,
-        post_count = post_count,
+        post_count = diff.appended.len(),
+        changed = diff.changed.len(),
+        removed = diff.removed.len(),
         "returning thread updates"
     );
 
-    // New posts arrived, so the thread is active
-    Ok(Response::builder()
+    let mut response = Response::builder()
         .status(StatusCode::OK)
         .header("Content-Type", "text/html; charset=utf-8")
-        .header("X-Last-CID", last_cid.as_str())
-        .header("X-Post-Count", post_count.to_string())
-        .header("X-Thread-Stale", "false")
-        .body(Body::from(html))
+        .header(CACHE_CONTROL, UPDATES_CACHE_CONTROL)
+        .header("X-Post-Count", diff.appended.len().to_string())
+        .header("X-Thread-Stale", if is_stale { "true" } else { "false" });
+    if let Some(last_post) = posts.last() {
+        response = response
+            .header("X-Last-CID", last_post.cid.as_str())
+            .header("X-Last-URI", last_post.uri.as_str());
+    }
+    if !diff.changed.is_empty() {
+        let uris: Vec<&str> = diff.changed.iter().map(|p| p.uri.as_str()).collect();
+        response = response.header("X-Changed-URIs", uris.join(","));
+    }
+    if !diff.removed.is_empty() {
+        response = response.header("X-Removed-URIs", diff.removed.join(","));
+    }
+    let mut response = response.body(Body::from(html)).unwrap();
+    validators.apply(response.headers_mut());
+    Ok(response)
+}
+
+/// Whether the last post is older than the polling window (or there are no posts).
+fn is_thread_stale(posts: &[ThreadPost], disable_after: u64) -> bool {
+    posts
+        .last()
+        .map(|p| {
+            Utc::now().signed_duration_since(p.created_at).num_seconds() >= disable_after as i64
+        })
+        .unwrap_or(true)
+}
+
+/// The current posts of a thread as seen by the updates API.
+struct CurrentPosts {
+    posts: Vec<ThreadPost>,
+    author_handle: String,
+}
+
+async fn current_thread_posts(
+    state: &AppState,
+    params: &ThreadUpdatesQuery,
+    since_uri: Option<&str>,
+    known: Option<&[(String, String)]>,
+) -> Result<CurrentPosts, AppError> {
+    if let Some(snapshot) = state
+        .watchers
+        .running_snapshot(&params.handle, &params.post_id)
+    {
+        return Ok(CurrentPosts {
+            posts: snapshot.thread.posts.clone(),
+            author_handle: snapshot.thread.author.handle.clone(),
+        });
+    }
+
+    if let Some(since_uri) = since_uri {
+        let refreshed = match (known, since_uri.rsplit_once('/')) {
+            (Some(known), Some((prefix, _))) => {
+                let uris: Vec<String> = known
+                    .iter()
+                    .map(|(rkey, _)| format!("{}/{}", prefix, rkey))
+                    .collect();
+                state.client.refresh_thread(&uris).await
+            }
+            _ => match state.client.get_thread_from(since_uri).await {
+                Ok(chain) => Ok(Some(chain)),
+                Err(ClientError::NotFound) => Ok(None),
+                Err(e) => Err(e),
+            },
+        };
+
+        if let Some(posts) = refreshed.map_err(map_client_error)? {
+            return Ok(CurrentPosts {
+                posts,
+                author_handle: params.handle.clone(),
+            });
+        }
+    }
+
+    let snapshot = state
+        .watchers
+        .snapshot(&params.handle, &params.post_id)
+        .await
+        .map_err(map_client_error)?;
+    Ok(CurrentPosts {
+        posts: snapshot.thread.posts.clone(),
+        author_handle: snapshot.thread.author.handle.clone(),
+    })
+}
+
+/// Parse the `known` parameter: comma-separated `rkey:cid-suffix` pairs.
+/// Record keys may contain colons, so the CID suffix follows the last one.
+/// Only the last `KNOWN_POSTS_WINDOW` entries are kept.
+fn parse_known_posts(known: &str) -> Vec<(String, String)> {
+    let mut posts: Vec<(String, String)> = known
+        .rsplit(',')
+        .filter(|entry| !entry.is_empty())
+        .take(KNOWN_POSTS_WINDOW)
+        .map(|entry| match entry.rsplit_once(':') {
+            Some((rkey, cid)) => (rkey.to_string(), cid.to_string()),
+            None => (entry.to_string(), String::new()),
+        })
+        .collect();
+    posts.reverse();
+    posts
+}
+
+/// Posts after the reader's last post, located by URI when given and by CID
+/// otherwise.
+fn posts_after<'a>(
+    posts: &'a [ThreadPost],
+    since_uri: Option<&str>,
+    since_cid: &str,
+) -> &'a [ThreadPost] {
+    let since_idx = match since_uri {
+        Some(uri) => posts.iter().position(|p| p.uri == uri),
+        None => posts.iter().position(|p| p.cid == since_cid),
+    };
+
+    match since_idx {
+        Some(idx) => &posts[idx + 1..],
+        None => {
+            // Post not found - return all posts (thread might have been restructured)
+            posts
+        }
+    }
+}
+
+/// Handler for the Server-Sent Events stream of thread updates.
+/// Sends `new-post` (HTML fragment, event id = post CID), `changed-post` (HTML
+/// fragment), `deleted-post` (post URI) and `stale` events; the polling endpoint
+/// remains as a fallback.
+pub async fn get_thread_events(
+    State(state): State<AppState>,
+    Query(params): Query<ThreadEventsQuery>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, AppError> {
+    let resume_cid = headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .map(String::from)
+        .or(params.since_cid)
+        .filter(|cid| !cid.is_empty());
+
+    info!(
+        handle = %params.handle,
+        post_id = %params.post_id,
+        resume_cid = ?resume_cid,
+        "opening thread event stream"
+    );
+
+    let rx = state
+        .watchers
+        .subscribe(&params.handle, &params.post_id)
+        .await
+        .map_err(map_client_error)?;
+    let author_handle = rx.borrow().thread.author.handle.clone();
+
+    let open = metrics().stream_started("events");
+    let events = thread_events(rx, resume_cid).map(move |event| {
+        let _open = &open;
+        Ok(match event {
+            ThreadEvent::NewPost(post) => Event::default()
+                .event("new-post")
+                .id(post.cid.as_str())
+                .data(render_post(&post, &author_handle)),
+            ThreadEvent::ChangedPost(post) => Event::default()
+                .event("changed-post")
+                .data(render_post(&post, &author_handle)),
+            ThreadEvent::DeletedPost { uri } => Event::default().event("deleted-post").data(uri),
+            ThreadEvent::Stale => Event::default().event("stale").data("true"),
+        })
+    });
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::new().interval(EVENTS_HEARTBEAT)))
+}
+
+/// What has been rendered so far in a streamed thread page, used to build
+/// the footer (and its polling config) or a resumable error.
+struct StreamProgress {
+    author_handle: String,
+    first_post_id: Option<String>,
+    post_count: usize,
+    last_cid: String,
+    last_uri: String,
+    last_post_timestamp: Option<DateTime<Utc>>,
+}
+
+impl StreamProgress {
+    fn new(author_handle: &str) -> Self {
+        Self {
+            author_handle: author_handle.to_string(),
+            first_post_id: None,
+            post_count: 0,
+            last_cid: String::new(),
+            last_uri: String::new(),
+            last_post_timestamp: None,
+        }
+    }
+
+    fn record(&mut self, post: &ThreadPost) {
+        if self.first_post_id.is_none() {
+            self.first_post_id = post.uri.rsplit('/').next().map(String::from);
+        }
+        self.post_count += 1;
+        self.last_cid = post.cid.clone();
+        self.last_uri = post.uri.clone();
+        self.last_post_timestamp = Some(post.created_at);
+    }
+
+    /// URI of the last rendered post, where a resumed stream continues from.
+    fn cursor(&self) -> Option<&str> {
+        Some(self.last_uri.as_str()).filter(|uri| !uri.is_empty())
+    }
+
+    fn footer(&self, config: &Config) -> String {
+        let post_id_str = self.first_post_id.as_deref().unwrap_or("");
+        let original_url = format!(
+            "https://bsky.app/profile/{}/post/{}",
+            self.author_handle, post_id_str
+        );
+
+        // Enable polling if configured; mark stale threads so
+        // auto-refresh starts off but can be toggled on by the user.
+        let is_thread_recent = self
+            .last_post_timestamp
+            .map(|ts| {
+                let age = Utc::now().signed_duration_since(ts).num_seconds();
+                age < config.poll_disable_after as i64
+            })
+            .unwrap_or(false);
+
+        let polling_config = if config.poll_enabled {
+            Some(PollingConfig {
+                handle: self.author_handle.clone(),
+                post_id: post_id_str.to_string(),
+                last_cid: self.last_cid.clone(),
+                last_uri: self.last_uri.clone(),
+                initial_interval: config.poll_initial_interval,
+                max_interval: config.poll_max_interval,
+                disable_after: config.poll_disable_after,
+                stale: !is_thread_recent,
+                last_post_iso: self
+                    .last_post_timestamp
+                    .map(|ts| ts.to_rfc3339())
+                    .unwrap_or_default(),
+            })
+        } else {
+            None
+        };
+
+        streaming_footer(&original_url, &self.last_uri, polling_config.as_ref())
+    }
+}
+
+/// Continue a streamed thread page after its connection dropped, or load
+/// more of a truncated one.
+///
+/// Streams the posts following `after` (the last post the page rendered) as
+/// HTML fragments, within the fetch budget, then the same footer a complete
+/// stream ends with. `post_id`
+/// is the thread's first post, needed for the footer's polling config.
+pub async fn get_thread_continue(
+    State(state): State<AppState>,
+    Query(params): Query<ThreadContinueQuery>,
+) -> Result<Response, AppError> {
+    if !params.after.starts_with("at://") {
+        return Err(AppError::BadRequest(
+            "invalid continuation cursor".to_string(),
+        ));
+    }
+    info!(handle = %params.handle, after = %params.after, "resuming thread stream");
+
+    let mut stream = Box::pin(
+        state
+            .client
+            .clone()
+            .stream_thread_from(params.after.clone()),
+    );
+    // The cursor post itself comes first; fail early if it is gone
+    let start = stream
+        .next()
+        .await
+        .ok_or_else(|| AppError::NotFound("post not found or deleted".to_string()))?
+        .map_err(map_client_error)?;
+
+    let config = state.config.load_full();
+    let limit = state.client.fetch_budget().start();
+    let body = async_stream::stream! {
+        let _open = metrics().stream_started("continue");
+        let mut progress = StreamProgress::new(&params.handle);
+        progress.first_post_id = Some(params.post_id.clone());
+        progress.record(&start);
+
+        // Counted from the cursor, so each continuation gets a fresh budget
+        let mut fetched = 0;
+        loop {
+            let next = if limit.allows(fetched) {
+                limit.run(stream.next()).await
+            } else {
+                None
+            };
+            match next {
+                Some(Some(Ok(post))) => {
+                    fetched += 1;
+                    progress.record(&post);
+                    yield Ok::<_, Infallible>(render_post(&post, &progress.author_handle));
+                }
+                Some(Some(Err(e))) => {
+                    warn!(error = %e, "streaming error while resuming");
+                    yield Ok(streaming_error(&e.to_string(), progress.cursor()));
+                    return;
+                }
+                Some(None) => break,
+                None => {
+                    yield Ok(truncation_notice(&progress.author_handle, &progress.last_uri));
+                    break;
+                }
+            }
+        }
+        yield Ok(progress.footer(&config));
+    };
+
+    Ok(Response::builder()
+        .header("Content-Type", "text/html; charset=utf-8")
+        .header("Cache-Control", "no-store")
+        .body(Body::from_stream(body))
         .unwrap())
 }
 
@@ -187,11 +811,17 @@ pub async fn get_thread_updates(
 pub async fn get_thread_streaming(
     State(state): State<AppState>,
     Path(params): Path<ThreadPath>,
+    Query(query): Query<LiteQuery>,
     headers: HeaderMap,
 ) -> Response {
-    use futures::stream::StreamExt as _;
     use tokio::sync::mpsc;
 
+    if is_lite_requested(query.lite.as_deref()) {
+        return get_thread_lite(State(state), Path(params), Query(query), headers)
+            .await
+            .into_response();
+    }
+
     // Check if this is a social media crawler requesting link preview data.
     // Crawlers don't benefit from streaming and need the full HTML with OG tags.
     let user_agent = headers
@@ -199,15 +829,17 @@ pub async fn get_thread_streaming(
         .and_then(|v| v.to_str().ok())
         .unwrap_or("");
 
-    if is_social_crawler(user_agent) {
+    if is_social_crawler(user_agent, &state.config.load().crawler_user_agents) {
         info!(
             handle = %params.handle,
             post_id = %params.post_id,
             user_agent = %user_agent,
             "serving non-streaming response for social crawler"
         );
-        return match fetch_and_render_thread(&state, &params.handle, &params.post_id).await {
-            Ok(html) => html.into_response(),
+        return match fetch_and_render_thread(&state, &params.handle, &params.post_id, &headers)
+            .await
+        {
+            Ok(response) => response,
             Err(e) => e.into_response(),
         };
     }
@@ -217,16 +849,14 @@ pub async fn get_thread_streaming(
     let (tx, rx) = mpsc::channel::<Result<String, std::convert::Infallible>>(16);
 
     let client = state.client.clone();
-    let config = state.config.clone();
+    let config = state.config.load_full();
     let handle = params.handle.clone();
     let post_id = params.post_id.clone();
 
-    tokio::spawn(async move {
-        let mut author_handle = handle.clone();
-        let mut first_post_id: Option<String> = None;
-        let mut post_count = 0;
-        let mut last_cid = String::new();
-        let mut last_post_timestamp: Option<DateTime<Utc>> = None;
+    // The task outlives the handler; keep its upstream calls in the request's trace
+    let task = async move {
+        let _open = metrics().stream_started("page");
+        let mut progress = StreamProgress::new(&handle);
 
         let post_id_for_url = post_id.clone();
         let stream = client.get_thread_streaming(handle, post_id);
@@ -235,7 +865,7 @@ pub async fn get_thread_streaming(
         while let Some(event) = stream.next().await {
             let chunk = match event {
                 Ok(StreamEvent::Header(author)) => {
-                    author_handle = author.handle.clone();
+                    progress.author_handle = author.handle.clone();
                     let thread_url = format!(
                         "{}/profile/{}/post/{}",
                         config.public_url, author.handle, post_id_for_url
@@ -251,59 +881,23 @@ pub async fn get_thread_streaming(
                     })
                 }
                 Ok(StreamEvent::Post(post)) => {
-                    if first_post_id.is_none() {
-                        first_post_id = post.uri.rsplit('/').next().map(String::from);
-                    }
-                    last_cid = post.cid.clone();
-                    last_post_timestamp = Some(post.created_at);
+                    progress.record(&post);
+                    let post_html = render_post(&post, &progress.author_handle);
 
-                    let post_html = render_post(&post, &author_handle);
-                    post_count += 1;
-
-                    if post_count == 1 {
+                    if progress.post_count == 1 {
                         format!("{}{}", post_html, streaming_loading_indicator())
                     } else {
                         streaming_post_before_indicator(&post_html)
                     }
                 }
-                Ok(StreamEvent::Done) => {
-                    let post_id_str = first_post_id.as_deref().unwrap_or("");
-                    let original_url = format!(
-                        "https://bsky.app/profile/{}/post/{}",
-                        author_handle, post_id_str
-                    );
-
-                    // Enable polling if configured; mark stale threads so
-                    // auto-refresh starts off but can be toggled on by the user.
-                    let is_thread_recent = last_post_timestamp
-                        .map(|ts| {
-                            let age = Utc::now().signed_duration_since(ts).num_seconds();
-                            age < config.poll_disable_after as i64
-                        })
-                        .unwrap_or(false);
-
-                    let polling_config = if config.poll_enabled {
-                        Some(PollingConfig {
-                            handle: author_handle.clone(),
-                            post_id: post_id_str.to_string(),
-                            last_cid: last_cid.clone(),
-                            initial_interval: config.poll_initial_interval,
-                            max_interval: config.poll_max_interval,
-                            disable_after: config.poll_disable_after,
-                            stale: !is_thread_recent,
-                            last_post_iso: last_post_timestamp
-                                .map(|ts| ts.to_rfc3339())
-                                .unwrap_or_default(),
-                        })
-                    } else {
-                        None
-                    };
-
-                    streaming_footer(&original_url, polling_config.as_ref())
-                }
+                Ok(StreamEvent::Truncated) => progress
+                    .cursor()
+                    .map(|cursor| truncation_notice(&progress.author_handle, cursor))
+                    .unwrap_or_default(),
+                Ok(StreamEvent::Done) => progress.footer(&config),
                 Err(e) => {
                     warn!(error = %e, "streaming error");
-                    streaming_error(&e.to_string())
+                    streaming_error(&e.to_string(), progress.cursor())
                 }
             };
 
@@ -311,7 +905,8 @@ pub async fn get_thread_streaming(
                 break;
             }
         }
-    });
+    };
+    tokio::spawn(task.in_current_span());
 
     let body_stream = tokio_stream::wrappers::ReceiverStream::new(rx);
 
@@ -328,13 +923,84 @@ pub async fn health_live() -> impl IntoResponse {
     StatusCode::OK
 }
 
+/// Ready while the background health check finds upstream reachable.
 pub async fn health_ready(State(state): State<AppState>) -> impl IntoResponse {
-    match state.client.resolve_handle("bsky.app").await {
-        Ok(_) => StatusCode::OK,
-        Err(_) => StatusCode::SERVICE_UNAVAILABLE,
+    if state.health.is_ready() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
     }
 }
 
+#[derive(Serialize)]
+struct HealthDetails {
+    ready: bool,
+    appview: AppViewHealth,
+    cache: CacheDetails,
+    watchers: WatcherDetails,
+    build: BuildInfo,
+}
+
+#[derive(Serialize)]
+struct CacheDetails {
+    backend: CacheBackend,
+    #[serde(flatten)]
+    health: CacheHealth,
+}
+
+#[derive(Serialize)]
+struct WatcherDetails {
+    /// Threads being watched
+    active: usize,
+    /// Whether the Jetstream consumer is connected
+    live_updates: bool,
+}
+
+#[derive(Serialize)]
+struct BuildInfo {
+    version: &'static str,
+    /// Set from `GIT_COMMIT` at build time
+    git_commit: Option<&'static str>,
+}
+
+/// Dependency health and build info as JSON, for operators. Always 200; the
+/// `ready` field says what `/health/ready` would answer.
+pub async fn health_details(State(state): State<AppState>) -> impl IntoResponse {
+    let config = state.config.load();
+    (
+        [(CACHE_CONTROL, "no-store")],
+        Json(HealthDetails {
+            ready: state.health.is_ready(),
+            appview: state.health.appview(),
+            cache: CacheDetails {
+                backend: config.cache_backend,
+                health: state.health.cache(),
+            },
+            watchers: WatcherDetails {
+                active: state.watchers.active_count(),
+                live_updates: state.watchers.is_live(),
+            },
+            build: BuildInfo {
+                version: env!("CARGO_PKG_VERSION"),
+                git_commit: option_env!("GIT_COMMIT"),
+            },
+        }),
+    )
+}
+
+/// Prometheus metrics in the text exposition format.
+pub async fn get_metrics(State(state): State<AppState>) -> impl IntoResponse {
+    let metrics = metrics();
+    metrics.set_active_pollers(state.watchers.active_count());
+    (
+        [(
+            axum::http::header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        metrics.encode(),
+    )
+}
+
 // PWA handlers
 
 /// Serve the PWA manifest for Android Web Share Target support.
@@ -418,6 +1084,131 @@ fn find_bluesky_url_in_text(text: &str) -> Option<String> {
     re.find(text).map(|m| m.as_str().to_string())
 }
 
+/// The webhook registry, if enabled and the request carries the shared
+/// secret as a bearer token.
+fn authorized_webhooks<'a>(
+    state: &'a AppState,
+    headers: &HeaderMap,
+) -> Result<&'a Webhooks, AppError> {
+    let webhooks = state
+        .webhooks
+        .as_ref()
+        .ok_or_else(|| AppError::NotFound("webhooks are not enabled".to_string()))?;
+    let token = headers
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .ok_or(AppError::Unauthorized)?;
+    if !webhooks.authorizes(token) {
+        return Err(AppError::Unauthorized);
+    }
+    Ok(webhooks)
+}
+
+/// Subscribe a callback URL to new posts in a live thread.
+pub async fn create_webhook(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<CreateWebhookRequest>,
+) -> Result<Response, AppError> {
+    let webhooks = authorized_webhooks(&state, &headers)?;
+
+    let parsed = parse_bluesky_url(&request.thread_url).map_err(|e| {
+        warn!(url = %request.thread_url, error = %e, "invalid thread URL for webhook");
+        AppError::BadRequest(format!("invalid thread URL: {}", e))
+    })?;
+    match url::Url::parse(&request.callback_url) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+        _ => return Err(AppError::BadRequest("invalid callback URL".to_string())),
+    }
+
+    let subscription = webhooks
+        .subscribe(&parsed.handle, &parsed.post_id, &request.callback_url)
+        .await
+        .map_err(|e| match e {
+            WebhookError::Client(e) => map_client_error(e),
+            WebhookError::Stale => {
+                AppError::BadRequest("thread is no longer receiving posts".to_string())
+            }
+            e => AppError::Internal(e.into()),
+        })?;
+
+    Ok((StatusCode::CREATED, Json(subscription)).into_response())
+}
+
+pub async fn list_webhooks(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<Subscription>>, AppError> {
+    Ok(Json(authorized_webhooks(&state, &headers)?.list()))
+}
+
+pub async fn delete_webhook(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<StatusCode, AppError> {
+    if authorized_webhooks(&state, &headers)?.unsubscribe(&id) {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::NotFound("no such webhook".to_string()))
+    }
+}
+
+fn push_service(state: &AppState) -> Result<&PushService, AppError> {
+    state
+        .push
+        .as_ref()
+        .ok_or_else(|| AppError::NotFound("push notifications are not enabled".to_string()))
+}
+
+/// VAPID public key for `PushManager.subscribe()`; 404 when push is disabled.
+pub async fn get_push_key(State(state): State<AppState>) -> Result<String, AppError> {
+    Ok(push_service(&state)?.public_key())
+}
+
+/// Notify a browser push subscription when a thread gets new posts.
+pub async fn push_subscribe(
+    State(state): State<AppState>,
+    peer: Option<Extension<ConnectInfo<SocketAddr>>>,
+    headers: HeaderMap,
+    Json(request): Json<PushSubscribeRequest>,
+) -> Result<StatusCode, AppError> {
+    // Served without connect info there is no address; the push service
+    // then falls back to a per-handle limit
+    let client = crate::ratelimit::client_ip(
+        &headers,
+        peer.map(|Extension(ConnectInfo(peer))| peer.ip()),
+        state.config.load().rate_limit_trusted_proxies,
+    );
+    push_service(&state)?
+        .subscribe(
+            &request.handle,
+            &request.post_id,
+            request.subscription,
+            client,
+        )
+        .await
+        .map_err(|e| match e {
+            PushError::Client(e) => map_client_error(e),
+            PushError::InvalidSubscription(reason) => AppError::BadRequest(reason.to_string()),
+            PushError::Limit(_) => AppError::RateLimited { retry_after: None },
+            PushError::Stale => {
+                AppError::BadRequest("thread is no longer receiving posts".to_string())
+            }
+            e => AppError::Internal(e.into()),
+        })?;
+    Ok(StatusCode::CREATED)
+}
+
+pub async fn push_unsubscribe(
+    State(state): State<AppState>,
+    Json(request): Json<PushUnsubscribeRequest>,
+) -> Result<StatusCode, AppError> {
+    push_service(&state)?.unsubscribe(&request.handle, &request.post_id, &request.endpoint);
+    Ok(StatusCode::NO_CONTENT)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -428,6 +1219,31 @@ mod tests {
         Router::new().route("/health/live", get(health_live))
     }
 
+    #[tokio::test]
+    async fn test_push_subscribe_without_connect_info() {
+        // Embedders serve the router without `into_make_service_with_connect_info`
+        let app = crate::create_app(&crate::config::Config::default()).unwrap();
+        let body = serde_json::json!({
+            "handle": "alice.test",
+            "post_id": "1",
+            "subscription": {
+                "endpoint": "https://fcm.googleapis.com/fcm/send/abc",
+                "keys": { "p256dh": "key", "auth": "auth" },
+            },
+        });
+        let response = app
+            .oneshot(
+                Request::post("/api/push/subscribe")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        // Push is off by default; what matters is that the request gets that far
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
     #[tokio::test]
     async fn test_health_live_returns_ok() {
         let response = health_app()
@@ -443,6 +1259,117 @@ mod tests {
         assert_eq!(response.status(), StatusCode::OK);
     }
 
+    #[test]
+    fn test_stream_progress_cursor() {
+        let config = Config::from_env().expect("default config");
+        let mut progress = StreamProgress::new("user.bsky.social");
+        assert_eq!(progress.cursor(), None);
+        assert!(!streaming_error("boom", progress.cursor()).contains("data-cursor"));
+
+        for id in ["3kroot", "3klast"] {
+            progress.record(&ThreadPost {
+                uri: format!("at://did:plc:abc/app.bsky.feed.post/{}", id),
+                cid: format!("cid-{}", id),
+                text: String::new(),
+                created_at: Utc::now(),
+                reply_count: None,
+                repost_count: None,
+                like_count: None,
+                embed: None,
+                langs: vec![],
+            });
+        }
+        let cursor = "at://did:plc:abc/app.bsky.feed.post/3klast";
+        assert_eq!(progress.cursor(), Some(cursor));
+        assert!(streaming_error("boom", progress.cursor())
+            .contains(&format!(r#"data-cursor="{}""#, cursor)));
+
+        let footer = progress.footer(&config);
+        assert!(footer.contains(&format!(r#"<footer data-cursor="{}">"#, cursor)));
+        assert!(footer.contains("/post/3kroot"));
+    }
+
+    #[test]
+    fn test_is_lite_requested() {
+        assert!(is_lite_requested(Some("1")));
+        assert!(is_lite_requested(Some("true")));
+        assert!(is_lite_requested(Some("")));
+        assert!(!is_lite_requested(Some("0")));
+        assert!(!is_lite_requested(None));
+    }
+
+    #[test]
+    fn test_is_lite_cursor() {
+        let did = "did:plc:abc";
+        assert!(is_lite_cursor(
+            "at://did:plc:abc/app.bsky.feed.post/3k2",
+            did,
+            "3k2"
+        ));
+        // Another post, another author, or another collection
+        assert!(!is_lite_cursor(
+            "at://did:plc:abc/app.bsky.feed.post/3k1",
+            did,
+            "3k2"
+        ));
+        assert!(!is_lite_cursor(
+            "at://did:plc:xyz/app.bsky.feed.post/3k2",
+            did,
+            "3k2"
+        ));
+        assert!(!is_lite_cursor(
+            "at://did:plc:abc/app.bsky.feed.like/3k2",
+            did,
+            "3k2"
+        ));
+    }
+
+    #[test]
+    fn test_posts_after() {
+        let post = |id: &str| ThreadPost {
+            uri: format!("at://did:plc:abc/app.bsky.feed.post/{}", id),
+            cid: format!("cid-{}", id),
+            text: String::new(),
+            created_at: Utc::now(),
+            reply_count: None,
+            repost_count: None,
+            like_count: None,
+            embed: None,
+            langs: vec![],
+        };
+        let posts = vec![post("a"), post("b"), post("c")];
+
+        // The URI takes precedence over the CID
+        let after = posts_after(
+            &posts,
+            Some("at://did:plc:abc/app.bsky.feed.post/b"),
+            "cid-a",
+        );
+        assert_eq!(after.len(), 1);
+        assert_eq!(after[0].cid, "cid-c");
+
+        assert_eq!(posts_after(&posts, None, "cid-a").len(), 2);
+        assert_eq!(posts_after(&posts, None, "cid-gone").len(), 3);
+    }
+
+    #[test]
+    fn test_parse_known_posts() {
+        assert_eq!(
+            parse_known_posts("3kabc:xyz12345,odd:key:abcd,bare,"),
+            vec![
+                ("3kabc".to_string(), "xyz12345".to_string()),
+                ("odd:key".to_string(), "abcd".to_string()),
+                ("bare".to_string(), String::new()),
+            ]
+        );
+
+        let many: Vec<String> = (0..50).map(|i| format!("k{}:c", i)).collect();
+        let known = parse_known_posts(&many.join(","));
+        assert_eq!(known.len(), KNOWN_POSTS_WINDOW);
+        assert_eq!(known[0].0, format!("k{}", 50 - KNOWN_POSTS_WINDOW));
+        assert_eq!(known.last().unwrap().0, "k49");
+    }
+
     #[test]
     fn test_extract_bluesky_url_from_url_param() {
         let params = ShareQuery {
@@ -0,0 +1,312 @@
+//! Background health checks behind `/health/ready` and `/health/details`.
+//!
+//! Calling upstream on every readiness probe adds load, and a single slow
+//! call takes the pod out of the Service. Instead a background task probes
+//! the AppView on an interval and keeps a window of recent results.
+//! Readiness is answered from that state and only flips after several
+//! probes in a row disagree with it. The client's circuit breaker is
+//! reported but does not affect readiness: every replica shares the same
+//! upstream, so it would take them all out of the Service at once, and
+//! readers would get connection errors instead of the 503 page.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::bluesky::client::ClientError;
+use crate::bluesky::BlueskyClient;
+use crate::cache::Cache;
+
+/// Probe results kept for latency and error rate
+const WINDOW: usize = 12;
+
+/// Consecutive failed probes before a ready pod reports not ready
+const FAIL_AFTER: usize = 3;
+
+/// Consecutive successful probes before it reports ready again
+const RECOVER_AFTER: usize = 2;
+
+/// Longest a probe may take before it counts as failed
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Handle resolved by each probe
+const PROBE_HANDLE: &str = "bsky.app";
+
+struct Probe {
+    ok: bool,
+    latency: Duration,
+}
+
+#[derive(Default)]
+struct State {
+    probes: VecDeque<Probe>,
+    ready: bool,
+    /// Consecutive probes whose outcome differs from `ready`
+    streak: usize,
+    last_checked: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+    /// Outcome of the last cache ping, when a cache is configured
+    cache: Option<Result<(), String>>,
+}
+
+/// Upstream health as seen by the background probes.
+#[derive(Clone)]
+pub struct HealthChecker {
+    client: BlueskyClient,
+    cache: Option<Cache>,
+    interval: Duration,
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AppViewHealth {
+    /// `up`, `down`, or `unknown` before the first probe
+    pub status: &'static str,
+    pub last_checked: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    /// Probes in the window the figures below cover
+    pub probes: usize,
+    pub error_rate: f64,
+    pub latency_ms: Option<LatencySummary>,
+    /// Circuit breaker state: `closed`, `open`, or `half_open`
+    pub circuit: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LatencySummary {
+    pub last: u64,
+    pub avg: u64,
+    pub max: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CacheHealth {
+    /// `ok`, `unavailable`, `disabled`, or `unknown` before the first ping
+    pub status: &'static str,
+    pub error: Option<String>,
+}
+
+impl HealthChecker {
+    pub fn new(client: BlueskyClient, cache: Option<Cache>, interval: Duration) -> Self {
+        Self {
+            client,
+            cache,
+            interval,
+            state: Arc::new(Mutex::new(State::default())),
+        }
+    }
+
+    /// Probe upstream (and the cache) every interval, starting now.
+    pub fn start(&self) {
+        let checker = self.clone();
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(checker.interval);
+            loop {
+                ticks.tick().await;
+                checker.check().await;
+            }
+        });
+    }
+
+    async fn check(&self) {
+        let started = Instant::now();
+        let result =
+            match tokio::time::timeout(PROBE_TIMEOUT, self.client.resolve_handle(PROBE_HANDLE))
+                .await
+            {
+                Ok(Ok(_)) => Some(Ok(started.elapsed())),
+                // Failed fast without reaching upstream, so there is nothing to learn
+                Ok(Err(ClientError::CircuitOpen)) => None,
+                Ok(Err(e)) => Some(Err(e.to_string())),
+                Err(_) => Some(Err(format!("timed out after {}s", PROBE_TIMEOUT.as_secs()))),
+            };
+        if let Some(result) = result {
+            self.record(result, Utc::now());
+        }
+
+        if let Some(cache) = &self.cache {
+            let result = cache.ping().await.map_err(|e| e.to_string());
+            self.state.lock().unwrap().cache = Some(result);
+        }
+    }
+
+    /// Record one probe: its latency, or why it failed.
+    fn record(&self, result: Result<Duration, String>, at: DateTime<Utc>) {
+        let mut state = self.state.lock().unwrap();
+        let first = state.last_checked.is_none();
+        let ok = result.is_ok();
+
+        match result {
+            Ok(latency) => state.probes.push_back(Probe { ok, latency }),
+            Err(error) => {
+                state.probes.push_back(Probe {
+                    ok,
+                    latency: PROBE_TIMEOUT,
+                });
+                state.last_error = Some(error);
+            }
+        }
+        if state.probes.len() > WINDOW {
+            state.probes.pop_front();
+        }
+        state.last_checked = Some(at);
+
+        if first {
+            state.ready = ok;
+            return;
+        }
+        if ok == state.ready {
+            state.streak = 0;
+            return;
+        }
+        state.streak += 1;
+        let needed = if ok { RECOVER_AFTER } else { FAIL_AFTER };
+        if state.streak >= needed {
+            state.ready = ok;
+            state.streak = 0;
+            if ok {
+                info!("upstream recovered; ready");
+            } else {
+                warn!(error = ?state.last_error, "upstream failing; not ready");
+            }
+        }
+    }
+
+    /// Whether the pod should receive traffic.
+    pub fn is_ready(&self) -> bool {
+        self.state.lock().unwrap().ready
+    }
+
+    pub fn appview(&self) -> AppViewHealth {
+        let state = self.state.lock().unwrap();
+        let probes = state.probes.len();
+        let failures = state.probes.iter().filter(|p| !p.ok).count();
+        let latencies: Vec<u64> = state
+            .probes
+            .iter()
+            .filter(|p| p.ok)
+            .map(|p| p.latency.as_millis() as u64)
+            .collect();
+
+        AppViewHealth {
+            status: match (state.last_checked, state.ready) {
+                (None, _) => "unknown",
+                (Some(_), true) => "up",
+                (Some(_), false) => "down",
+            },
+            last_checked: state.last_checked,
+            last_error: state.last_error.clone(),
+            probes,
+            error_rate: if probes == 0 {
+                0.0
+            } else {
+                failures as f64 / probes as f64
+            },
+            latency_ms: latencies.last().map(|&last| LatencySummary {
+                last,
+                avg: latencies.iter().sum::<u64>() / latencies.len() as u64,
+                max: latencies.iter().copied().max().unwrap_or(last),
+            }),
+            circuit: self.client.circuit_state().as_str(),
+        }
+    }
+
+    pub fn cache(&self) -> CacheHealth {
+        if self.cache.is_none() {
+            return CacheHealth {
+                status: "disabled",
+                error: None,
+            };
+        }
+        match &self.state.lock().unwrap().cache {
+            None => CacheHealth {
+                status: "unknown",
+                error: None,
+            },
+            Some(Ok(())) => CacheHealth {
+                status: "ok",
+                error: None,
+            },
+            Some(Err(e)) => CacheHealth {
+                status: "unavailable",
+                error: Some(e.clone()),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checker() -> HealthChecker {
+        let client = BlueskyClient::new("http://127.0.0.1:1", Duration::from_secs(1)).unwrap();
+        HealthChecker::new(client, None, Duration::from_secs(10))
+    }
+
+    #[test]
+    fn test_readiness_hysteresis() {
+        let checker = checker();
+        let ok = || Ok(Duration::from_millis(100));
+        let fail = || Err("boom".to_string());
+        assert!(!checker.is_ready());
+        assert_eq!(checker.appview().status, "unknown");
+
+        // The first probe decides at once
+        checker.record(ok(), Utc::now());
+        assert!(checker.is_ready());
+
+        // Isolated failures do not flap readiness
+        checker.record(fail(), Utc::now());
+        checker.record(fail(), Utc::now());
+        checker.record(ok(), Utc::now());
+        checker.record(fail(), Utc::now());
+        checker.record(fail(), Utc::now());
+        assert!(checker.is_ready());
+        checker.record(fail(), Utc::now());
+        assert!(!checker.is_ready());
+
+        checker.record(ok(), Utc::now());
+        assert!(!checker.is_ready());
+        checker.record(ok(), Utc::now());
+        assert!(checker.is_ready());
+
+        let appview = checker.appview();
+        assert_eq!(appview.status, "up");
+        assert_eq!(appview.probes, 9);
+        assert!((appview.error_rate - 5.0 / 9.0).abs() < 1e-9);
+        assert_eq!(appview.latency_ms.unwrap().avg, 100);
+        assert_eq!(appview.last_error.as_deref(), Some("boom"));
+        assert_eq!(appview.circuit, "closed");
+        assert_eq!(checker.cache().status, "disabled");
+    }
+
+    #[tokio::test]
+    async fn test_open_circuit_does_not_affect_readiness() {
+        let client = BlueskyClient::new("http://127.0.0.1:1", Duration::from_secs(1))
+            .unwrap()
+            .with_circuit_breaker(crate::bluesky::BreakerSettings {
+                error_rate: 0.5,
+                min_calls: 1,
+                open_for: Duration::from_secs(30),
+            });
+        let checker = HealthChecker::new(client, None, Duration::from_secs(10));
+        checker.record(Ok(Duration::from_millis(100)), Utc::now());
+
+        // The first check reaches the (unreachable) upstream and opens the circuit
+        checker.check().await;
+        assert_eq!(checker.appview().circuit, "open");
+        assert_eq!(checker.appview().probes, 2);
+
+        // Later checks fail fast and are not counted against readiness
+        for _ in 0..FAIL_AFTER {
+            checker.check().await;
+        }
+        assert_eq!(checker.appview().probes, 2);
+        assert!(checker.is_ready());
+    }
+}
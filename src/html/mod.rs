@@ -1,9 +1,11 @@
 pub mod renderer;
 pub mod templates;
 
-pub use renderer::{render_post, render_thread};
+pub use renderer::{
+    render_post, render_profile, render_thread, render_thread_embed, render_thread_lite,
+};
 pub use templates::{
     landing_page, streaming_error, streaming_footer, streaming_head, streaming_loading_indicator,
-    streaming_post_before_indicator, PollingConfig, SocialMeta, StreamingHeadOptions,
-    TemplateOptions,
+    streaming_post_before_indicator, truncation_notice, PollingConfig, SocialMeta,
+    StreamingHeadOptions, TemplateOptions,
 };
@@ -1,7 +1,10 @@
-use crate::bluesky::types::{Author, Embed, EmbedImage, EmbedRecord, Thread, ThreadPost};
+use crate::bluesky::types::{
+    Author, AuthorThreadsPage, Embed, EmbedImage, EmbedRecord, Profile, Thread, ThreadPost,
+    ThreadSummary,
+};
 use crate::html::templates::{
-    base_template_with_options, render_avatar_html, render_footer_content, SocialMeta,
-    TemplateOptions, HEADER_TEMPLATE,
+    base_template_with_options, embed_template, lite_template, render_avatar_html,
+    render_footer_content, render_header_html, truncation_notice, SocialMeta, TemplateOptions,
 };
 
 pub fn render_thread(thread: &Thread, public_url: &str) -> String {
@@ -13,6 +16,7 @@ pub fn render_thread(thread: &Thread, public_url: &str) -> String {
     for post in &thread.posts {
         content.push_str(&render_post(post, &thread.author.handle));
     }
+    content.push_str(&render_truncation(thread));
     content.push_str("</main>\n");
 
     content.push_str(&render_footer(thread));
@@ -25,20 +29,19 @@ pub fn render_thread(thread: &Thread, public_url: &str) -> String {
     // Build social meta for Open Graph tags
     let og_title = format!("Thread by @{}", thread.author.handle);
     let first_post_text = thread.posts.first().map(|p| p.text.as_str());
-    let post_id = thread.posts.first().and_then(|p| p.uri.rsplit('/').next());
-    let thread_url = post_id.map(|id| {
-        format!(
-            "{}/profile/{}/post/{}",
-            public_url, thread.author.handle, id
-        )
+    let thread_url = sklonger_thread_url(thread, public_url);
+    let og_image_url = thread_url.as_ref().map(|url| {
+        // /profile/{handle}/post/{id} -> /og/profile/{handle}/post/{id}.png
+        format!("{}/og{}.png", public_url, &url[public_url.len()..])
     });
 
     let social = SocialMeta {
         title: Some(&og_title),
         description: first_post_text,
         url: thread_url.as_deref(),
-        image_url: thread.author.avatar_url.as_deref(),
+        image_url: og_image_url.as_deref(),
         og_type: Some("article"),
+        large_image: true,
     };
 
     let options = TemplateOptions {
@@ -49,25 +52,417 @@ pub fn render_thread(thread: &Thread, public_url: &str) -> String {
     base_template_with_options(&title, &content, options)
 }
 
+/// Render a thread for embedding in an iframe on third-party pages.
+/// Only the author line, posts and a link back to the full thread are shown.
+pub fn render_thread_embed(thread: &Thread, public_url: &str) -> String {
+    let thread_url =
+        sklonger_thread_url(thread, public_url).unwrap_or_else(|| public_url.to_string());
+    let original_url = thread
+        .original_post_url()
+        .unwrap_or_else(|| "https://bsky.app".to_string());
+
+    let author = &thread.author;
+    let author_name = author.display_name.as_deref().unwrap_or(&author.handle);
+    let avatar = render_avatar_html(author.avatar_url.as_deref(), author_name);
+
+    let mut content = format!(
+        r#"<header>
+    <a href="{thread_url}" class="author" target="_blank" rel="noopener">
+        {avatar}
+        <div class="author-info">
+            <span class="display-name">{display_name}</span>
+            <span class="handle">@{handle}</span>
+        </div>
+    </a>
+</header>
+"#,
+        thread_url = html_escape::encode_quoted_attribute(&thread_url),
+        avatar = avatar,
+        display_name = html_escape::encode_text(author_name),
+        handle = html_escape::encode_text(&author.handle),
+    );
+
+    content.push_str("<main class=\"thread\">\n");
+    for post in &thread.posts {
+        content.push_str(&render_post(post, &author.handle));
+    }
+    content.push_str(&render_truncation(thread));
+    content.push_str("</main>\n");
+
+    content.push_str(&format!(
+        r#"<footer>
+    <a href="{thread_url}" target="_blank" rel="noopener">Read on sklonger</a> &middot;
+    <a href="{original_url}" target="_blank" rel="noopener">View on Bluesky</a>
+</footer>
+"#,
+        thread_url = html_escape::encode_quoted_attribute(&thread_url),
+        original_url = html_escape::encode_quoted_attribute(&original_url),
+    ));
+
+    let title = format!(
+        "Thread by @{} - sklonger",
+        html_escape::encode_text(&author.handle)
+    );
+    let options = TemplateOptions {
+        lang: thread.primary_language(),
+        ..Default::default()
+    };
+    embed_template(&title, &content, options)
+}
+
+/// Render a thread for the lite reader: semantic HTML only, no scripts.
+/// Media is reduced to plain links and images so text browsers stay usable.
+pub fn render_thread_lite(thread: &Thread, public_url: &str) -> String {
+    let author = &thread.author;
+    let author_name = author.display_name.as_deref().unwrap_or(&author.handle);
+    let original_url = thread
+        .original_post_url()
+        .unwrap_or_else(|| "https://bsky.app".to_string());
+    let full_url = sklonger_thread_url(thread, public_url).unwrap_or_else(|| "/".to_string());
+
+    let mut content = format!(
+        r#"<header>
+    <h1>{display_name}</h1>
+    <p><a href="{profile_url}">@{handle}</a> &middot; {count} posts &middot; <a href="{full_url}">Full version</a></p>
+</header>
+<main>
+"#,
+        display_name = html_escape::encode_text(author_name),
+        profile_url = html_escape::encode_quoted_attribute(&author.profile_url()),
+        handle = html_escape::encode_text(&author.handle),
+        count = thread.posts.len(),
+        full_url = html_escape::encode_quoted_attribute(&full_url),
+    );
+
+    for post in &thread.posts {
+        content.push_str(&render_post_lite(post, &author.handle));
+    }
+    content.push_str(&render_truncation(thread));
+
+    content.push_str(&format!(
+        r#"</main>
+<footer>
+    <a href="{original_url}">View original on Bluesky</a> &middot; <a href="/">sklonger</a>
+</footer>
+"#,
+        original_url = html_escape::encode_quoted_attribute(&original_url),
+    ));
+
+    let title = format!("Thread by @{} - sklonger", author.handle);
+    let og_title = format!("Thread by @{}", author.handle);
+    let social = SocialMeta {
+        title: Some(&og_title),
+        description: thread.posts.first().map(|p| p.text.as_str()),
+        url: Some(&full_url),
+        og_type: Some("article"),
+        ..Default::default()
+    };
+    let options = TemplateOptions {
+        lang: thread.primary_language(),
+        social: Some(social),
+        ..Default::default()
+    };
+    lite_template(&title, &content, options)
+}
+
+/// The "load more" notice for a thread cut short by the fetch budget.
+fn render_truncation(thread: &Thread) -> String {
+    match thread.posts.last() {
+        Some(last) if thread.truncated => truncation_notice(&thread.author.handle, &last.uri),
+        _ => String::new(),
+    }
+}
+
+fn render_post_lite(post: &ThreadPost, author_handle: &str) -> String {
+    let post_id = post.uri.rsplit('/').next().unwrap_or("");
+    let post_url = format!(
+        "https://bsky.app/profile/{}/post/{}",
+        author_handle, post_id
+    );
+
+    // Blank lines become paragraphs, single newlines become <br>
+    let paragraphs: String = post
+        .text
+        .split("\n\n")
+        .filter(|p| !p.trim().is_empty())
+        .map(|p| {
+            let html = linkify_text(&html_escape::encode_text(p.trim())).replace('\n', "<br>\n");
+            format!("<p>{}</p>\n", html)
+        })
+        .collect();
+
+    let embed_html = post
+        .embed
+        .as_ref()
+        .map(render_embed_lite)
+        .unwrap_or_default();
+
+    format!(
+        r#"<article>
+{paragraphs}{embed}<footer><a href="{post_url}"><time datetime="{datetime}">{timestamp}</time></a></footer>
+</article>
+"#,
+        paragraphs = paragraphs,
+        embed = embed_html,
+        post_url = html_escape::encode_quoted_attribute(&post_url),
+        datetime = post.created_at.to_rfc3339(),
+        timestamp = post.created_at.format("%b %d, %Y at %H:%M UTC"),
+    )
+}
+
+fn render_embed_lite(embed: &Embed) -> String {
+    match embed {
+        Embed::Images(images) => images
+            .iter()
+            .map(|img| {
+                format!(
+                    r#"<figure><a href="{fullsize}"><img src="{thumb}" alt="{alt}"></a></figure>
+"#,
+                    fullsize = html_escape::encode_quoted_attribute(&img.fullsize_url),
+                    thumb = html_escape::encode_quoted_attribute(&img.thumb_url),
+                    alt = html_escape::encode_quoted_attribute(&img.alt),
+                )
+            })
+            .collect(),
+        Embed::Video(video) => format!(
+            r#"<p><a href="{playlist}">[Video{alt}]</a></p>
+"#,
+            playlist = html_escape::encode_quoted_attribute(&video.playlist_url),
+            alt = video
+                .alt
+                .as_deref()
+                .filter(|a| !a.is_empty())
+                .map(|a| format!(": {}", html_escape::encode_text(a)))
+                .unwrap_or_default(),
+        ),
+        Embed::External(external) => format!(
+            r#"<p><a href="{uri}">{title}</a></p>
+"#,
+            uri = html_escape::encode_quoted_attribute(&external.uri),
+            title = html_escape::encode_text(if external.title.is_empty() {
+                &external.uri
+            } else {
+                &external.title
+            }),
+        ),
+        Embed::Record(record) => render_record_lite(record),
+        Embed::RecordWithMedia { record, media } => {
+            format!("{}{}", render_record_lite(record), render_embed_lite(media))
+        }
+    }
+}
+
+fn render_record_lite(record: &EmbedRecord) -> String {
+    let post_id = record.uri.rsplit('/').next().unwrap_or("");
+    let post_url = format!(
+        "https://bsky.app/profile/{}/post/{}",
+        record.author.handle, post_id
+    );
+
+    format!(
+        r#"<blockquote>
+<p>{text}</p>
+<p>&mdash; <a href="{post_url}">@{handle}</a></p>
+</blockquote>
+"#,
+        text = html_escape::encode_text(&record.text).replace('\n', "<br>\n"),
+        post_url = html_escape::encode_quoted_attribute(&post_url),
+        handle = html_escape::encode_text(&record.author.handle),
+    )
+}
+
+/// Render an author page: profile card plus one page of recent threads.
+/// `cursor` is the cursor this page was fetched with, used to offer a link back to the newest page.
+pub fn render_profile(
+    profile: &Profile,
+    page: &AuthorThreadsPage,
+    cursor: Option<&str>,
+    public_url: &str,
+) -> String {
+    let author = &profile.author;
+    let author_name = author.display_name.as_deref().unwrap_or(&author.handle);
+    let avatar = render_avatar_html(author.avatar_url.as_deref(), author_name);
+
+    let mut content = render_header_html(
+        &author.profile_url(),
+        &avatar,
+        author_name,
+        &author.handle,
+        false,
+    );
+
+    content.push_str("<main class=\"profile\">\n");
+    content.push_str(&render_profile_card(profile));
+
+    content.push_str("<h2 class=\"profile-section-title\">Recent threads</h2>\n");
+    if page.threads.is_empty() {
+        content.push_str("<p class=\"thread-list-empty\">No threads on this page.</p>\n");
+    } else {
+        content.push_str("<ol class=\"thread-list\">\n");
+        for summary in &page.threads {
+            content.push_str(&render_thread_summary(summary, &author.handle));
+        }
+        content.push_str("</ol>\n");
+    }
+
+    let profile_path = format!("/profile/{}", author.handle);
+    let mut pagination = Vec::new();
+    if cursor.is_some() {
+        pagination.push(format!(
+            r#"<a href="{}">Newest threads</a>"#,
+            html_escape::encode_quoted_attribute(&profile_path)
+        ));
+    }
+    if let Some(next) = &page.cursor {
+        let next_url = format!(
+            "{}?cursor={}",
+            profile_path,
+            url::form_urlencoded::byte_serialize(next.as_bytes()).collect::<String>()
+        );
+        pagination.push(format!(
+            r#"<a href="{}" rel="next">Older threads</a>"#,
+            html_escape::encode_quoted_attribute(&next_url)
+        ));
+    }
+    if !pagination.is_empty() {
+        content.push_str(&format!(
+            "<nav class=\"pagination\">{}</nav>\n",
+            pagination.join(" ")
+        ));
+    }
+    content.push_str("</main>\n");
+
+    content.push_str(&format!(
+        "<footer>\n    {}\n</footer>\n",
+        render_footer_content(&author.profile_url())
+    ));
+
+    let title = format!("@{} - sklonger", author.handle);
+    let og_title = format!("Threads by @{}", author.handle);
+    let page_url = format!("{}{}", public_url, profile_path);
+    let social = SocialMeta {
+        title: Some(&og_title),
+        description: profile.description.as_deref(),
+        url: Some(&page_url),
+        image_url: author.avatar_url.as_deref(),
+        og_type: Some("profile"),
+        large_image: false,
+    };
+    let options = TemplateOptions {
+        favicon_url: author.avatar_url.as_deref(),
+        lang: None,
+        social: Some(social),
+    };
+    base_template_with_options(&title, &content, options)
+}
+
+fn render_profile_card(profile: &Profile) -> String {
+    let banner = profile
+        .banner_url
+        .as_deref()
+        .map(|url| {
+            format!(
+                r#"<img class="profile-banner" src="{}" alt="">"#,
+                html_escape::encode_quoted_attribute(url)
+            )
+        })
+        .unwrap_or_default();
+
+    let bio = profile
+        .description
+        .as_deref()
+        .filter(|d| !d.trim().is_empty())
+        .map(|d| {
+            format!(
+                r#"<div class="profile-bio">{}</div>"#,
+                linkify_text(&html_escape::encode_text(d.trim()))
+            )
+        })
+        .unwrap_or_default();
+
+    let stats: Vec<String> = [
+        (profile.followers_count, "followers"),
+        (profile.follows_count, "following"),
+        (profile.posts_count, "posts"),
+    ]
+    .iter()
+    .filter_map(|(count, label)| {
+        count.map(|n| format!("<span><strong>{}</strong> {}</span>", n, label))
+    })
+    .collect();
+
+    format!(
+        r#"<section class="profile-card">
+    {banner}
+    {bio}
+    <div class="profile-stats">{stats}</div>
+</section>
+"#,
+        banner = banner,
+        bio = bio,
+        stats = stats.join(" "),
+    )
+}
+
+fn render_thread_summary(summary: &ThreadSummary, author_handle: &str) -> String {
+    let post = &summary.first_post;
+    let post_id = post.uri.rsplit('/').next().unwrap_or("");
+    let thread_path = format!("/profile/{}/post/{}", author_handle, post_id);
+    let count = format!(
+        "{}{} posts",
+        summary.post_count,
+        if summary.post_count_capped { "+" } else { "" }
+    );
+
+    format!(
+        r#"<li class="thread-summary">
+    <a href="{path}" class="thread-summary-link">
+        <div class="thread-summary-text">{text}</div>
+        <div class="thread-summary-meta"><time datetime="{datetime}">{date}</time> &middot; {count}</div>
+    </a>
+</li>
+"#,
+        path = html_escape::encode_quoted_attribute(&thread_path),
+        text = html_escape::encode_text(&excerpt(&post.text, 280)),
+        datetime = post.created_at.to_rfc3339(),
+        date = post.created_at.format("%b %d, %Y"),
+        count = count,
+    )
+}
+
+/// Shorten text to at most `max_chars` characters, breaking on a word boundary.
+fn excerpt(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let cut: String = text.chars().take(max_chars).collect();
+    let cut = match cut.rfind(char::is_whitespace) {
+        Some(idx) if idx > 0 => &cut[..idx],
+        _ => cut.as_str(),
+    };
+    format!("{}…", cut.trim_end())
+}
+
+/// Build the sklonger URL for a thread from its first post.
+fn sklonger_thread_url(thread: &Thread, public_url: &str) -> Option<String> {
+    let post_id = thread.posts.first()?.uri.rsplit('/').next()?;
+    Some(format!(
+        "{}/profile/{}/post/{}",
+        public_url, thread.author.handle, post_id
+    ))
+}
+
 fn render_header(author: &Author) -> String {
     let author_name = author.display_name.as_deref().unwrap_or(&author.handle);
     let avatar = render_avatar_html(author.avatar_url.as_deref(), author_name);
-    let profile_url = author.profile_url();
-
-    HEADER_TEMPLATE
-        .replace(
-            "{profile_url}",
-            html_escape::encode_quoted_attribute(&profile_url).as_ref(),
-        )
-        .replace("{avatar}", &avatar)
-        .replace(
-            "{display_name}",
-            html_escape::encode_text(author_name).as_ref(),
-        )
-        .replace(
-            "{handle}",
-            html_escape::encode_text(&author.handle).as_ref(),
-        )
+
+    render_header_html(
+        &author.profile_url(),
+        &avatar,
+        author_name,
+        &author.handle,
+        true,
+    )
 }
 
 /// Render a single post as an HTML article element.
@@ -99,12 +494,14 @@ pub fn render_post(post: &ThreadPost, author_handle: &str) -> String {
     }
 
     format!(
-        r#"<article class="post">
+        r#"<article class="post" data-uri="{uri}" data-cid="{cid}">
     <div class="post-text">{text}</div>
     {embed}
     <a href="{post_url}" target="_blank" rel="noopener" class="post-meta">{meta}</a>
 </article>
 "#,
+        uri = html_escape::encode_quoted_attribute(&post.uri),
+        cid = html_escape::encode_quoted_attribute(&post.cid),
         text = text,
         embed = embed_html,
         post_url = html_escape::encode_quoted_attribute(&post_url),
@@ -296,3 +693,136 @@ fn linkify_text(text: &str) -> String {
         })
         .to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn author() -> Author {
+        Author {
+            did: "did:plc:abc".to_string(),
+            handle: "user.bsky.social".to_string(),
+            display_name: Some("User".to_string()),
+            avatar_url: None,
+        }
+    }
+
+    fn post(id: &str, text: &str) -> ThreadPost {
+        ThreadPost {
+            uri: format!("at://did:plc:abc/app.bsky.feed.post/{}", id),
+            cid: format!("cid-{}", id),
+            text: text.to_string(),
+            created_at: Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap(),
+            reply_count: None,
+            repost_count: None,
+            like_count: None,
+            embed: None,
+            langs: vec![],
+        }
+    }
+
+    fn profile() -> Profile {
+        Profile {
+            author: author(),
+            description: Some("Writes <long> threads".to_string()),
+            banner_url: None,
+            followers_count: Some(12),
+            follows_count: None,
+            posts_count: Some(340),
+        }
+    }
+
+    #[test]
+    fn test_render_thread_lite_has_no_scripts_or_external_assets() {
+        let mut with_image = post("b", "Look");
+        with_image.embed = Some(Embed::Images(vec![EmbedImage {
+            thumb_url: "https://cdn.bsky.app/thumb.jpg".to_string(),
+            fullsize_url: "https://cdn.bsky.app/full.jpg".to_string(),
+            alt: "A cat".to_string(),
+            aspect_ratio: None,
+        }]));
+        let thread = Thread::truncated(
+            vec![post("a", "<script>alert(1)</script>"), with_image],
+            author(),
+        );
+        let html = render_thread_lite(&thread, "https://sklonger.app");
+
+        assert!(html.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+        assert!(html.contains("<style>"));
+        assert!(html.contains("Load more"));
+        let lower = html.to_lowercase();
+        assert!(!lower.contains("<script"));
+        assert!(!lower.contains("<link"));
+        assert!(!lower.contains(".js"));
+        assert!(!lower.contains(".css"));
+        // No inline event handlers either
+        let handler = lower.match_indices(" on").any(|(at, _)| {
+            let rest = &lower[at + 3..];
+            let name = rest.len()
+                - rest
+                    .trim_start_matches(|c: char| c.is_ascii_lowercase())
+                    .len();
+            name > 0 && rest[name..].starts_with('=')
+        });
+        assert!(!handler);
+    }
+
+    #[test]
+    fn test_excerpt() {
+        assert_eq!(excerpt("short", 10), "short");
+        assert_eq!(excerpt("one two three", 9), "one two…");
+        // No space to break on: cut mid-word
+        assert_eq!(excerpt("abcdefghij", 4), "abcd…");
+        // Counts characters, not bytes
+        assert_eq!(excerpt("héllo wörld", 8), "héllo…");
+    }
+
+    #[test]
+    fn test_render_profile() {
+        let page = AuthorThreadsPage {
+            threads: vec![
+                ThreadSummary {
+                    first_post: post("a1", "First <b>thread</b>"),
+                    post_count: 3,
+                    post_count_capped: false,
+                },
+                ThreadSummary {
+                    first_post: post("b2", "Second thread"),
+                    post_count: 26,
+                    post_count_capped: true,
+                },
+            ],
+            cursor: Some("2024 & older".to_string()),
+        };
+        let html = render_profile(&profile(), &page, None, "https://sklonger.app");
+
+        assert!(html.contains("Writes &lt;long&gt; threads"));
+        assert!(html.contains("<strong>12</strong> followers"));
+        assert!(!html.contains("following"));
+        assert!(html.contains(r#"href="/profile/user.bsky.social/post/a1""#));
+        assert!(html.contains("First &lt;b&gt;thread&lt;/b&gt;"));
+        assert!(html.contains("3 posts"));
+        assert!(html.contains("26+ posts"));
+        assert!(html.contains(r#"<meta property="og:type" content="profile">"#));
+
+        // The next cursor is URL-encoded; the first page has no link back
+        assert!(
+            html.contains(r#"href="/profile/user.bsky.social?cursor=2024+%26+older" rel="next""#)
+        );
+        assert!(!html.contains("Newest threads"));
+    }
+
+    #[test]
+    fn test_render_profile_later_pages() {
+        let page = AuthorThreadsPage {
+            threads: vec![],
+            cursor: None,
+        };
+        let html = render_profile(&profile(), &page, Some("abc"), "https://sklonger.app");
+
+        assert!(html.contains("No threads on this page."));
+        assert!(html.contains(r#"<a href="/profile/user.bsky.social">Newest threads</a>"#));
+        assert!(!html.contains("Older threads"));
+    }
+}
@@ -1,10 +1,12 @@
 // Static assets loaded from external files at compile time
 pub const CSS_STYLES: &str = include_str!("templates/styles.css");
 pub const HEADER_TEMPLATE: &str = include_str!("templates/header.html");
+const REFRESH_BUTTON: &str = include_str!("templates/refresh-button.html");
 
 const THEME_SCRIPT: &str = include_str!("templates/theme-init.js");
 const THEME_TOGGLE_SCRIPT: &str = include_str!("templates/theme-toggle.js");
 const FONT_SIZE_INIT_SCRIPT: &str = include_str!("templates/font-size-init.js");
+const STREAM_RESUME_SCRIPT: &str = include_str!("templates/stream-resume.js");
 const FONT_SIZE_CONTROL_SCRIPT: &str = include_str!("templates/font-size-control.js");
 const PWA_META_TAGS: &str = include_str!("templates/pwa-meta.html");
 const SERVICE_WORKER_REGISTRATION: &str = include_str!("templates/sw-register.js");
@@ -13,6 +15,9 @@ const GALLERY_SCRIPT: &str = include_str!("templates/gallery.js");
 const OPTIONS_MENU_TEMPLATE: &str = include_str!("templates/options-menu.html");
 const OPTIONS_MENU_SCRIPT: &str = include_str!("templates/options-menu.js");
 const LOCAL_TIME_SCRIPT: &str = include_str!("templates/local-time.js");
+const EMBED_STYLES: &str = include_str!("templates/embed.css");
+const EMBED_RESIZE_SCRIPT: &str = include_str!("templates/embed-resize.js");
+const LITE_STYLES: &str = include_str!("templates/lite.css");
 
 /// Social media meta tags for Open Graph and Twitter Cards
 #[derive(Default)]
@@ -27,6 +32,9 @@ pub struct SocialMeta<'a> {
     pub image_url: Option<&'a str>,
     /// Content type (e.g., "article", "website")
     pub og_type: Option<&'a str>,
+    /// Whether image_url is a generated 1200x630 card rather than a square avatar.
+    /// Switches twitter:card to summary_large_image and adds og:image dimensions.
+    pub large_image: bool,
 }
 
 /// Template options for customizing the HTML output
@@ -72,12 +80,44 @@ pub fn render_avatar_html(avatar_url: Option<&str>, author_name: &str) -> String
     }
 }
 
+/// Render the page header from HEADER_TEMPLATE.
+/// The refresh button is only included on pages that poll for updates.
+pub fn render_header_html(
+    profile_url: &str,
+    avatar_html: &str,
+    display_name: &str,
+    handle: &str,
+    with_refresh: bool,
+) -> String {
+    HEADER_TEMPLATE
+        .replace(
+            "{refresh_button}",
+            if with_refresh {
+                REFRESH_BUTTON.trim_end()
+            } else {
+                ""
+            },
+        )
+        .replace(
+            "{profile_url}",
+            html_escape::encode_quoted_attribute(profile_url).as_ref(),
+        )
+        .replace("{avatar}", avatar_html)
+        .replace(
+            "{display_name}",
+            html_escape::encode_text(display_name).as_ref(),
+        )
+        .replace("{handle}", html_escape::encode_text(handle).as_ref())
+}
+
 /// Configuration for client-side polling of thread updates
 #[derive(Debug, Clone)]
 pub struct PollingConfig {
     pub handle: String,
     pub post_id: String,
     pub last_cid: String,
+    /// AT URI of the last post, so updates can follow the thread forward from it
+    pub last_uri: String,
     pub initial_interval: u64,
     pub max_interval: u64,
     pub disable_after: u64,
@@ -97,6 +137,7 @@ fn render_poll_script(config: &PollingConfig) -> String {
         handle: '{handle}',
         postId: '{post_id}',
         lastCid: '{last_cid}',
+        lastUri: '{last_uri}',
         interval: {initial_interval} * 1000,
         maxInterval: {max_interval} * 1000,
         disableAfter: {disable_after} * 1000,
@@ -110,13 +151,72 @@ fn render_poll_script(config: &PollingConfig) -> String {
     var timerId = null;
     var stopped = false;
     var refreshBtn = null;
+    // Server-Sent Events stream; polling is only used when it is unavailable
+    var source = null;
+    var sseUnavailable = !window.EventSource;
+    var sseFailures = 0;
 
     function buildUrl() {{
         return '/api/thread/updates?handle=' + encodeURIComponent(cfg.handle) +
+               '&post_id=' + encodeURIComponent(cfg.postId) +
+               '&since_cid=' + encodeURIComponent(cfg.lastCid) +
+               '&since_uri=' + encodeURIComponent(cfg.lastUri) +
+               '&known=' + encodeURIComponent(knownPosts());
+    }}
+
+    function buildEventsUrl() {{
+        return '/api/thread/events?handle=' + encodeURIComponent(cfg.handle) +
                '&post_id=' + encodeURIComponent(cfg.postId) +
                '&since_cid=' + encodeURIComponent(cfg.lastCid);
     }}
 
+    function openStream() {{
+        if (source || stopped) return;
+        clearTimer();
+        source = new EventSource(buildEventsUrl());
+        source.addEventListener('open', function() {{
+            sseFailures = 0;
+        }});
+        source.addEventListener('new-post', function(e) {{
+            cfg.lastCid = e.lastEventId || cfg.lastCid;
+            insertPosts(e.data);
+            noUpdateSince = Date.now();
+        }});
+        source.addEventListener('changed-post', function(e) {{
+            insertPosts(e.data);
+        }});
+        source.addEventListener('deleted-post', function(e) {{
+            markDeleted(e.data);
+        }});
+        source.addEventListener('stale', function() {{
+            markStale();
+        }});
+        // The browser reconnects by itself (sending Last-Event-ID) unless the
+        // server rejected the stream; fall back to polling after repeated failures
+        source.addEventListener('error', function() {{
+            sseFailures++;
+            if (source && (source.readyState === EventSource.CLOSED || sseFailures >= 3)) {{
+                closeStream();
+                sseUnavailable = true;
+                schedule();
+            }}
+        }});
+    }}
+
+    function closeStream() {{
+        if (source) {{ source.close(); source = null; }}
+    }}
+
+    // Start receiving updates: prefer the event stream, otherwise poll
+    function start() {{
+        if (stopped) return;
+        if (sseUnavailable) {{
+            schedule();
+        }} else {{
+            openStream();
+        }}
+    }}
+
     function clearTimer() {{
         if (timerId) {{ clearTimeout(timerId); timerId = null; }}
     }}
@@ -145,6 +245,7 @@ fn render_poll_script(config: &PollingConfig) -> String {
                     if (r.status === 204) return null;
                     if (!r.ok) throw new Error('Refresh failed: ' + r.status);
                     cfg.lastCid = r.headers.get('X-Last-CID') || cfg.lastCid;
+                    applyRemovals(r.headers.get('X-Removed-URIs'));
                     return r.text();
                 }})
                 .then(function(html) {{
@@ -155,13 +256,13 @@ fn render_poll_script(config: &PollingConfig) -> String {
                     }}
                     refreshBtn.classList.remove('spinning');
                     refreshBtn.disabled = false;
-                    if (!stopped) schedule();
+                    start();
                 }})
                 .catch(function(e) {{
                     console.error('Refresh error:', e);
                     refreshBtn.classList.remove('spinning');
                     refreshBtn.disabled = false;
-                    schedule();
+                    start();
                 }});
         }});
     }}
@@ -170,12 +271,13 @@ fn render_poll_script(config: &PollingConfig) -> String {
         if (stopped && window._threadStale) return;
         stopped = true;
         clearTimer();
+        closeStream();
         window._threadStale = true;
         document.dispatchEvent(new Event('threadstale'));
     }}
 
     function poll() {{
-        if (stopped) return;
+        if (stopped || source) return;
 
         var now = Date.now();
         if (now - noUpdateSince > cfg.disableAfter) {{
@@ -194,6 +296,7 @@ fn render_poll_script(config: &PollingConfig) -> String {
                 }}
                 if (!r.ok) throw new Error('Poll failed: ' + r.status);
                 cfg.lastCid = r.headers.get('X-Last-CID') || cfg.lastCid;
+                applyRemovals(r.headers.get('X-Removed-URIs'));
                 return r.text();
             }})
             .then(function(html) {{
@@ -212,17 +315,62 @@ fn render_poll_script(config: &PollingConfig) -> String {
     }}
 
     function schedule() {{
-        if (stopped) return;
+        if (stopped || source) return;
         timerId = setTimeout(poll, cfg.interval);
     }}
 
+    function findPost(uri) {{
+        var posts = document.querySelectorAll('.thread [data-uri]');
+        for (var i = 0; i < posts.length; i++) {{
+            if (posts[i].getAttribute('data-uri') === uri) return posts[i];
+        }}
+        return null;
+    }}
+
+    // The last posts on the page as rkey:cid-suffix pairs, so the server can
+    // report changes to them; a bounded tail keeps the URL short
+    function knownPosts() {{
+        var posts = document.querySelectorAll('.thread [data-uri]:not([data-deleted])');
+        var known = [];
+        for (var i = Math.max(0, posts.length - {known_window}); i < posts.length; i++) {{
+            var cid = posts[i].getAttribute('data-cid') || '';
+            known.push(posts[i].getAttribute('data-uri').split('/').pop() + ':' + cid.slice(-8));
+        }}
+        return known.join(',');
+    }}
+
+    // Replace a deleted post with a tombstone so the thread keeps its shape
+    function markDeleted(uri) {{
+        var post = findPost(uri);
+        if (!post || post.hasAttribute('data-deleted')) return;
+        post.setAttribute('data-deleted', 'true');
+        post.classList.add('post-deleted');
+        post.innerHTML = '<div class="post-text">This post has been deleted.</div>';
+    }}
+
+    function applyRemovals(header) {{
+        if (!header) return;
+        header.split(',').forEach(markDeleted);
+    }}
+
+    // Append new posts and replace changed ones; posts resent unchanged
+    // (e.g. after a reconnect) are left alone
     function insertPosts(html) {{
         var thread = document.querySelector('.thread');
         var temp = document.createElement('div');
         temp.innerHTML = html;
-        while (temp.firstChild) {{
-            thread.appendChild(temp.firstChild);
-        }}
+        Array.prototype.slice.call(temp.children).forEach(function(el) {{
+            var uri = el.getAttribute('data-uri');
+            var existing = uri && findPost(uri);
+            if (existing) {{
+                if (existing.getAttribute('data-cid') !== el.getAttribute('data-cid')) {{
+                    existing.replaceWith(el);
+                }}
+                return;
+            }}
+            thread.appendChild(el);
+            if (uri) cfg.lastUri = uri;
+        }});
         // Reinitialize handlers for new posts
         if (window.setupGalleryHandlers) {{
             window.setupGalleryHandlers();
@@ -251,15 +399,17 @@ fn render_poll_script(config: &PollingConfig) -> String {
     window._pollingActive = true;
     window._lastPostTime = cfg.lastPostTime;
     window._threadStale = cfg.stale;
+    window._thread = {{ handle: cfg.handle, postId: cfg.postId }};
     window.setAutoRefreshEnabled = function(enabled) {{
         if (enabled && stopped) {{
             stopped = false;
             noUpdateSince = Date.now();
             cfg.interval = cfg.initialInterval;
-            schedule();
+            start();
         }} else if (!enabled && !stopped) {{
             stopped = true;
             clearTimer();
+            closeStream();
         }}
     }};
 
@@ -270,18 +420,20 @@ fn render_poll_script(config: &PollingConfig) -> String {
     if (cfg.stale) {{
         stopped = true;
     }} else {{
-        schedule();
+        start();
     }}
 }})();
 "#,
         handle = html_escape::encode_text(&config.handle),
         post_id = html_escape::encode_text(&config.post_id),
         last_cid = html_escape::encode_text(&config.last_cid),
+        last_uri = html_escape::encode_text(&config.last_uri),
         initial_interval = config.initial_interval,
         max_interval = config.max_interval,
         disable_after = config.disable_after,
         stale = if config.stale { "true" } else { "false" },
         last_post_iso = html_escape::encode_text(&config.last_post_iso),
+        known_window = crate::events::KNOWN_POSTS_WINDOW,
     )
 }
 
@@ -358,13 +510,28 @@ fn render_social_meta(social: &SocialMeta) -> String {
     "#,
             image
         ));
+        if social.large_image {
+            tags.push_str(&format!(
+                r#"<meta property="og:image:width" content="{}">
+    <meta property="og:image:height" content="{}">
+    "#,
+                crate::og::CARD_WIDTH,
+                crate::og::CARD_HEIGHT
+            ));
+        }
     }
 
     // Twitter Card tags
-    tags.push_str(
-        r#"<meta name="twitter:card" content="summary">
+    let twitter_card = if social.large_image && escaped_image.is_some() {
+        "summary_large_image"
+    } else {
+        "summary"
+    };
+    tags.push_str(&format!(
+        r#"<meta name="twitter:card" content="{}">
     "#,
-    );
+        twitter_card
+    ));
 
     if let Some(ref title) = escaped_title {
         tags.push_str(&format!(
@@ -437,6 +604,73 @@ pub fn base_template_with_options(title: &str, content: &str, options: TemplateO
     )
 }
 
+/// Chrome-less page used for iframe embeds.
+/// Omits the options menu, install banner and PWA tags; the only scripts are
+/// theme/time helpers and the resize script that reports height to the parent.
+pub fn embed_template(title: &str, content: &str, options: TemplateOptions) -> String {
+    let lang = options.lang.unwrap_or("en");
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="{lang}">
+<head>
+    <meta charset="utf-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1">
+    <meta name="robots" content="noindex">
+    <title>{title}</title>
+    <script>{theme_init}{font_size_init}</script>
+    <style>{css}{embed_css}</style>
+</head>
+<body class="embed">
+{content}
+<script>{local_time}{embed_resize}</script>
+</body>
+</html>"#,
+        lang = html_escape::encode_quoted_attribute(lang),
+        title = html_escape::encode_text(title),
+        theme_init = THEME_SCRIPT,
+        font_size_init = FONT_SIZE_INIT_SCRIPT,
+        css = CSS_STYLES,
+        embed_css = EMBED_STYLES,
+        content = content,
+        local_time = LOCAL_TIME_SCRIPT,
+        embed_resize = EMBED_RESIZE_SCRIPT,
+    )
+}
+
+/// Script-free page for the lite reader.
+/// Uses a small standalone stylesheet instead of the full one and never
+/// includes scripts, PWA tags or the options menu.
+pub fn lite_template(title: &str, content: &str, options: TemplateOptions) -> String {
+    let lang = options.lang.unwrap_or("en");
+    let social_meta = options
+        .social
+        .as_ref()
+        .map(render_social_meta)
+        .unwrap_or_default();
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="{lang}">
+<head>
+    <meta charset="utf-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1">
+    <title>{title}</title>
+    {social_meta}
+    <style>{css}</style>
+</head>
+<body>
+{content}
+</body>
+</html>"#,
+        lang = html_escape::encode_quoted_attribute(lang),
+        title = html_escape::encode_text(title),
+        social_meta = social_meta,
+        css = LITE_STYLES,
+        content = content,
+    )
+}
+
 pub fn landing_page() -> String {
     let content = r#"<div class="landing-header">
     <div class="header-controls">
@@ -579,24 +813,17 @@ pub fn streaming_head(options: StreamingHeadOptions) -> String {
         url: Some(options.thread_url),
         image_url: options.avatar_url,
         og_type: Some("article"),
+        large_image: false,
     };
     let social_meta = render_social_meta(&social);
 
-    // Render header from template with interpolated values
-    let header = HEADER_TEMPLATE
-        .replace(
-            "{profile_url}",
-            html_escape::encode_quoted_attribute(options.profile_url).as_ref(),
-        )
-        .replace("{avatar}", &avatar_html)
-        .replace(
-            "{display_name}",
-            html_escape::encode_text(author_name).as_ref(),
-        )
-        .replace(
-            "{handle}",
-            html_escape::encode_text(options.author_handle).as_ref(),
-        );
+    let header = render_header_html(
+        options.profile_url,
+        &avatar_html,
+        author_name,
+        options.author_handle,
+        true,
+    );
 
     format!(
         r#"<!DOCTYPE html>
@@ -606,7 +833,7 @@ pub fn streaming_head(options: StreamingHeadOptions) -> String {
     <meta name="viewport" content="width=device-width, initial-scale=1">
     <title>{title}</title>
     {social_meta}{favicon}{pwa_meta}
-    <script>{theme_init}{font_size_init}</script>
+    <script>{theme_init}{font_size_init}{stream_resume}</script>
     <style>{css}</style>
 </head>
 <body>
@@ -620,6 +847,7 @@ pub fn streaming_head(options: StreamingHeadOptions) -> String {
         pwa_meta = PWA_META_TAGS,
         theme_init = THEME_SCRIPT,
         font_size_init = FONT_SIZE_INIT_SCRIPT,
+        stream_resume = STREAM_RESUME_SCRIPT,
         css = CSS_STYLES,
         header = header,
     )
@@ -638,13 +866,19 @@ pub fn render_footer_content(original_post_url: &str) -> String {
 /// Render the closing HTML for a streaming response.
 /// This includes the footer and closing tags.
 /// If polling config is provided, the polling script is injected.
-pub fn streaming_footer(original_post_url: &str, polling: Option<&PollingConfig>) -> String {
+/// `cursor` is the URI of the last rendered post; its presence on the footer
+/// tells the resume script the stream completed.
+pub fn streaming_footer(
+    original_post_url: &str,
+    cursor: &str,
+    polling: Option<&PollingConfig>,
+) -> String {
     let poll_script = polling.map(render_poll_script).unwrap_or_default();
     let footer_content = render_footer_content(original_post_url);
 
     format!(
         r#"</main>
-<footer>
+<footer data-cursor="{cursor}">
     {footer_content}
 </footer>
 {options_menu}
@@ -654,6 +888,7 @@ document.getElementById('loading-indicator')?.remove();
 </script>
 </body>
 </html>"#,
+        cursor = html_escape::encode_quoted_attribute(cursor),
         footer_content = footer_content,
         options_menu = OPTIONS_MENU_TEMPLATE,
         theme_toggle = THEME_TOGGLE_SCRIPT,
@@ -684,11 +919,43 @@ pub fn streaming_post_before_indicator(post_html: &str) -> String {
     )
 }
 
+/// Render the notice that ends a thread cut short by the fetch budget.
+/// Streamed and fully rendered pages end the same way. `cursor` is the URI of
+/// the last post shown; "Load more" continues from it, in place on streamed
+/// pages and as a lite page elsewhere.
+pub fn truncation_notice(handle: &str, cursor: &str) -> String {
+    let post_id = cursor.rsplit('/').next().unwrap_or("");
+    let load_more_url = format!(
+        "/lite/profile/{}/post/{}?after={}",
+        handle,
+        post_id,
+        url::form_urlencoded::byte_serialize(cursor.as_bytes()).collect::<String>()
+    );
+    format!(
+        r#"<aside class="thread-truncated" data-cursor="{cursor}">
+    <p>Thread truncated: this is as much as could be loaded at once.</p>
+    <a class="load-more" href="{load_more_url}">Load more</a>
+</aside>
+"#,
+        cursor = html_escape::encode_quoted_attribute(cursor),
+        load_more_url = html_escape::encode_quoted_attribute(&load_more_url),
+    )
+}
+
 /// Render an error that occurred mid-stream.
-/// This closes the HTML properly so the page is still valid.
-pub fn streaming_error(message: &str) -> String {
+/// This closes the HTML properly so the page is still valid. With a `cursor`
+/// (the URI of the last rendered post) the page retries from there.
+pub fn streaming_error(message: &str, cursor: Option<&str>) -> String {
+    let cursor_attr = cursor
+        .map(|uri| {
+            format!(
+                r#" data-cursor="{}""#,
+                html_escape::encode_quoted_attribute(uri)
+            )
+        })
+        .unwrap_or_default();
     format!(
-        r#"<div class="stream-error">
+        r#"<div class="stream-error"{}>
     <p>Error loading thread: {}</p>
 </div>
 </main>
@@ -700,6 +967,7 @@ document.getElementById('loading-indicator')?.remove();
 </script>
 </body>
 </html>"#,
+        cursor_attr,
         html_escape::encode_text(message)
     )
 }
@@ -0,0 +1,28 @@
+(function() {
+    if (window.parent === window) return;
+
+    var lastHeight = 0;
+
+    function postHeight() {
+        var height = document.documentElement.scrollHeight;
+        if (height === lastHeight) return;
+        lastHeight = height;
+        window.parent.postMessage({ type: 'sklonger:resize', height: height }, '*');
+    }
+
+    if (window.ResizeObserver) {
+        new ResizeObserver(postHeight).observe(document.body);
+    } else {
+        window.addEventListener('resize', postHeight);
+    }
+
+    // Images and videos change the height once they load
+    var media = document.querySelectorAll('img, video');
+    for (var i = 0; i < media.length; i++) {
+        media[i].addEventListener('load', postHeight);
+        media[i].addEventListener('loadedmetadata', postHeight);
+    }
+
+    window.addEventListener('load', postHeight);
+    postHeight();
+})();
@@ -159,4 +159,99 @@
     }
 
     updateLastPostNotice();
+
+    // "Notify me" toggle: Web Push for the open thread, shown only when the
+    // server has push enabled and the browser supports it
+    var pushRow = document.getElementById('push-row');
+    var pushToggle = document.getElementById('push-toggle');
+    var thread = window._thread;
+    if (pushRow && pushToggle && thread && !window._threadStale &&
+        'serviceWorker' in navigator && 'PushManager' in window && 'Notification' in window) {
+        var storageKey = 'push:' + thread.handle + '/' + thread.postId;
+        var pushEnabled = false;
+        var busy = false;
+
+        function setPushState(enabled) {
+            pushEnabled = enabled;
+            pushToggle.setAttribute('aria-checked', enabled ? 'true' : 'false');
+        }
+
+        function decodeKey(key) {
+            var padded = key + '='.repeat((4 - key.length % 4) % 4);
+            var raw = atob(padded.replace(/-/g, '+').replace(/_/g, '/'));
+            var bytes = new Uint8Array(raw.length);
+            for (var i = 0; i < raw.length; i++) bytes[i] = raw.charCodeAt(i);
+            return bytes;
+        }
+
+        function postJson(url, body) {
+            return fetch(url, {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify(body)
+            }).then(function(r) {
+                if (!r.ok) throw new Error('Request failed: ' + r.status);
+            });
+        }
+
+        function enablePush(key) {
+            return Notification.requestPermission().then(function(permission) {
+                if (permission !== 'granted') throw new Error('Permission denied');
+                return navigator.serviceWorker.ready;
+            }).then(function(registration) {
+                return registration.pushManager.getSubscription().then(function(existing) {
+                    return existing || registration.pushManager.subscribe({
+                        userVisibleOnly: true,
+                        applicationServerKey: decodeKey(key)
+                    });
+                });
+            }).then(function(subscription) {
+                return postJson('/api/push/subscribe', {
+                    handle: thread.handle,
+                    post_id: thread.postId,
+                    subscription: subscription.toJSON()
+                }).then(function() {
+                    localStorage.setItem(storageKey, subscription.endpoint);
+                });
+            });
+        }
+
+        function disablePush() {
+            var endpoint = localStorage.getItem(storageKey);
+            localStorage.removeItem(storageKey);
+            if (!endpoint) return Promise.resolve();
+            // The browser subscription is shared by all followed threads, so keep it
+            return postJson('/api/push/unsubscribe', {
+                handle: thread.handle,
+                post_id: thread.postId,
+                endpoint: endpoint
+            });
+        }
+
+        fetch('/api/push/key').then(function(r) {
+            return r.ok ? r.text() : null;
+        }).then(function(key) {
+            if (!key) return;
+            pushRow.style.display = '';
+            setPushState(!!localStorage.getItem(storageKey) && Notification.permission === 'granted');
+
+            pushToggle.addEventListener('click', function() {
+                if (busy) return;
+                busy = true;
+                var wanted = !pushEnabled;
+                setPushState(wanted);
+                (wanted ? enablePush(key) : disablePush()).catch(function() {
+                    setPushState(!wanted);
+                }).then(function() {
+                    busy = false;
+                });
+            });
+        }).catch(function() {});
+
+        // Stale threads will not continue; the server drops their subscriptions
+        document.addEventListener('threadstale', function() {
+            pushRow.style.display = 'none';
+            localStorage.removeItem(storageKey);
+        });
+    }
 })();
@@ -0,0 +1,113 @@
+(function() {
+    // A streamed page is complete once its footer arrives. If the connection
+    // drops first, or upstream fails mid-stream, continue from the last
+    // rendered post instead of leaving the page half-loaded.
+    var MAX_ATTEMPTS = 3;
+    var attempts = 0;
+    var trimmed = false;
+
+    function pageThread() {
+        var match = location.pathname.match(/\/profile\/([^\/]+)\/post\/([^\/?#]+)/);
+        return match ? { handle: decodeURIComponent(match[1]) } : null;
+    }
+
+    function rkey(uri) {
+        return uri ? uri.split('/').pop() : '';
+    }
+
+    function cursor() {
+        var error = document.querySelector('.thread .stream-error[data-cursor]');
+        if (error) return error.getAttribute('data-cursor');
+        var posts = document.querySelectorAll('.thread article[data-uri]');
+        return posts.length ? posts[posts.length - 1].getAttribute('data-uri') : null;
+    }
+
+    function isComplete() {
+        var footer = document.querySelector('body > footer[data-cursor]');
+        return footer && !document.querySelector('.thread .stream-error');
+    }
+
+    // Scripts inserted from parsed HTML do not run; recreate them so they do
+    function runScripts(container) {
+        container.querySelectorAll('script').forEach(function(old) {
+            var script = document.createElement('script');
+            script.textContent = old.textContent;
+            old.parentNode.replaceChild(script, old);
+        });
+    }
+
+    function apply(html) {
+        var doc = new DOMParser().parseFromString(
+            '<!DOCTYPE html><html><body><main class="thread">' + html, 'text/html');
+        var main = document.querySelector('main.thread');
+        var indicator = document.getElementById('loading-indicator');
+        main.querySelectorAll('.stream-error, .thread-truncated').forEach(function(node) {
+            node.remove();
+        });
+        // Anything after the main content came with a failed attempt; replace it
+        while (main.nextSibling) main.nextSibling.remove();
+
+        var newMain = doc.querySelector('main.thread');
+        Array.prototype.slice.call(newMain.children).forEach(function(node) {
+            if (node.tagName === 'SCRIPT') return;
+            main.insertBefore(document.adoptNode(node), indicator);
+        });
+        Array.prototype.slice.call(doc.body.children).forEach(function(node) {
+            if (node === newMain) return;
+            var adopted = document.adoptNode(node);
+            if (adopted.tagName === 'SCRIPT') {
+                var script = document.createElement('script');
+                script.textContent = adopted.textContent;
+                document.body.appendChild(script);
+            } else {
+                document.body.appendChild(adopted);
+                runScripts(adopted);
+            }
+        });
+    }
+
+    // A dropped connection may have cut the last post short; render it again
+    function trimPartialPost() {
+        if (trimmed || document.querySelector('.thread .stream-error')) return;
+        trimmed = true;
+        var posts = document.querySelectorAll('.thread article[data-uri]');
+        if (posts.length > 1) posts[posts.length - 1].remove();
+    }
+
+    function resume() {
+        trimPartialPost();
+        var thread = pageThread();
+        var after = cursor();
+        var first = document.querySelector('.thread article[data-uri]');
+        if (!thread || !after || !first || attempts >= MAX_ATTEMPTS) return;
+        attempts++;
+
+        var url = '/api/thread/continue?handle=' + encodeURIComponent(thread.handle) +
+            '&post_id=' + encodeURIComponent(rkey(first.getAttribute('data-uri'))) +
+            '&after=' + encodeURIComponent(after);
+        fetch(url).then(function(r) {
+            if (!r.ok) throw new Error('Resume failed: ' + r.status);
+            return r.text();
+        }).then(function(html) {
+            apply(html);
+            if (!isComplete()) setTimeout(resume, 2000 * attempts);
+        }).catch(function() {
+            setTimeout(resume, 2000 * attempts);
+        });
+    }
+
+    document.addEventListener('DOMContentLoaded', function() {
+        if (!isComplete()) resume();
+    });
+
+    // A truncated thread loads more in place, continuing from its last post
+    document.addEventListener('click', function(e) {
+        var link = e.target.closest && e.target.closest('.thread-truncated .load-more');
+        if (!link) return;
+        e.preventDefault();
+        link.textContent = 'Loading...';
+        attempts = 0;
+        trimmed = true;
+        resume();
+    });
+})();
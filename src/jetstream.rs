@@ -0,0 +1,328 @@
+//! Optional consumer of the Bluesky Jetstream firehose.
+//!
+//! Jetstream is a JSON rendition of the firehose that can be filtered by
+//! repository DID and collection. The consumer subscribes to posts by the
+//! authors of currently watched threads and wakes the matching watchers as
+//! soon as a self-reply or deletion arrives. While it is disconnected,
+//! watchers fall back to their normal polling schedule.
+
+use std::time::Duration;
+
+use futures::{SinkExt, StreamExt};
+use serde::Deserialize;
+use thiserror::Error;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use tracing::{debug, info, warn};
+use url::Url;
+
+use crate::watcher::WatcherRegistry;
+
+const POST_COLLECTION: &str = "app.bsky.feed.post";
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+#[derive(Error, Debug)]
+pub enum JetstreamError {
+    #[error("invalid Jetstream URL: {0}")]
+    Url(#[from] url::ParseError),
+    #[error("WebSocket error: {0}")]
+    WebSocket(#[from] tokio_tungstenite::tungstenite::Error),
+    #[error("Jetstream closed the connection")]
+    Closed,
+}
+
+/// A post commit that may affect a watched thread.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PostEvent {
+    Created {
+        did: String,
+        uri: String,
+        /// Set when the post is a reply
+        parent_uri: Option<String>,
+    },
+    Deleted {
+        did: String,
+        uri: String,
+    },
+}
+
+#[derive(Deserialize)]
+struct JetstreamMessage {
+    did: String,
+    time_us: i64,
+    kind: String,
+    commit: Option<Commit>,
+}
+
+#[derive(Deserialize)]
+struct Commit {
+    operation: String,
+    collection: String,
+    rkey: String,
+    record: Option<PostRecord>,
+}
+
+#[derive(Deserialize)]
+struct PostRecord {
+    reply: Option<ReplyRef>,
+}
+
+#[derive(Deserialize)]
+struct ReplyRef {
+    parent: StrongRef,
+}
+
+#[derive(Deserialize)]
+struct StrongRef {
+    uri: String,
+}
+
+/// Parse a Jetstream message into its cursor (`time_us`) and the post event it
+/// carries, if any. Returns `None` for messages that cannot be parsed.
+fn parse_message(text: &str) -> Option<(i64, Option<PostEvent>)> {
+    let message: JetstreamMessage = match serde_json::from_str(text) {
+        Ok(message) => message,
+        Err(e) => {
+            debug!(error = %e, "ignoring unparseable Jetstream message");
+            return None;
+        }
+    };
+
+    let event = match (message.kind.as_str(), message.commit) {
+        ("commit", Some(commit)) if commit.collection == POST_COLLECTION => {
+            let uri = format!("at://{}/{}/{}", message.did, POST_COLLECTION, commit.rkey);
+            match commit.operation.as_str() {
+                "create" => Some(PostEvent::Created {
+                    did: message.did,
+                    uri,
+                    parent_uri: commit
+                        .record
+                        .and_then(|r| r.reply)
+                        .map(|reply| reply.parent.uri),
+                }),
+                "delete" => Some(PostEvent::Deleted {
+                    did: message.did,
+                    uri,
+                }),
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+
+    Some((message.time_us, event))
+}
+
+/// A subscription to a Jetstream instance.
+pub struct Connection {
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+}
+
+impl Connection {
+    /// Connect to `endpoint` (e.g. `wss://jetstream2.us-east.bsky.network/subscribe`)
+    /// for posts by `dids`, replaying from `cursor` when given.
+    pub async fn open(
+        endpoint: &str,
+        dids: &[String],
+        cursor: Option<i64>,
+    ) -> Result<Self, JetstreamError> {
+        let mut url = Url::parse(endpoint)?;
+        {
+            let mut query = url.query_pairs_mut();
+            query.append_pair("wantedCollections", POST_COLLECTION);
+            for did in dids {
+                query.append_pair("wantedDids", did);
+            }
+            if let Some(cursor) = cursor {
+                query.append_pair("cursor", &cursor.to_string());
+            }
+        }
+
+        let (socket, _) = tokio_tungstenite::connect_async(url.as_str()).await?;
+        Ok(Self { socket })
+    }
+
+    /// Replace the DID filter without reconnecting.
+    pub async fn update_dids(&mut self, dids: &[String]) -> Result<(), JetstreamError> {
+        let update = serde_json::json!({
+            "type": "options_update",
+            "payload": {
+                "wantedCollections": [POST_COLLECTION],
+                "wantedDids": dids,
+            },
+        });
+        self.socket
+            .send(Message::Text(update.to_string().into()))
+            .await?;
+        Ok(())
+    }
+
+    /// Wait for the next message, returning its cursor and post event (if any).
+    pub async fn next(&mut self) -> Result<(i64, Option<PostEvent>), JetstreamError> {
+        while let Some(message) = self.socket.next().await {
+            match message? {
+                Message::Text(text) => {
+                    if let Some(parsed) = parse_message(&text) {
+                        return Ok(parsed);
+                    }
+                }
+                Message::Close(_) => return Err(JetstreamError::Closed),
+                // Pings are answered by tungstenite; nothing else is expected
+                _ => {}
+            }
+        }
+        Err(JetstreamError::Closed)
+    }
+}
+
+/// Keep a Jetstream subscription for the authors of watched threads, waking
+/// watchers on relevant posts. Runs forever, reconnecting with backoff and
+/// resuming from the last cursor after a dropped connection.
+pub async fn run(endpoint: String, registry: WatcherRegistry) {
+    let mut cursor = None;
+    let mut delay = Duration::from_secs(1);
+
+    loop {
+        let dids = registry.watched_dids();
+        if dids.is_empty() {
+            registry.watched_changed().await;
+            continue;
+        }
+
+        match Connection::open(&endpoint, &dids, cursor).await {
+            Ok(mut connection) => {
+                info!(authors = dids.len(), "connected to Jetstream");
+                registry.set_live(true);
+                delay = Duration::from_secs(1);
+
+                let result = consume(&mut connection, &registry, &mut cursor).await;
+                registry.set_live(false);
+                match result {
+                    Ok(()) => {
+                        debug!("no watched threads left; disconnecting from Jetstream");
+                        cursor = None;
+                        continue;
+                    }
+                    Err(e) => warn!(error = %e, "Jetstream connection lost"),
+                }
+            }
+            Err(e) => warn!(error = %e, "failed to connect to Jetstream"),
+        }
+
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+    }
+}
+
+/// Forward events until the connection fails or nothing is watched any more.
+async fn consume(
+    connection: &mut Connection,
+    registry: &WatcherRegistry,
+    cursor: &mut Option<i64>,
+) -> Result<(), JetstreamError> {
+    loop {
+        tokio::select! {
+            message = connection.next() => {
+                let (time_us, event) = message?;
+                *cursor = Some(time_us);
+                if let Some(event) = event {
+                    registry.wake_for(&event);
+                }
+            }
+            _ = registry.watched_changed() => {
+                let dids = registry.watched_dids();
+                if dids.is_empty() {
+                    return Ok(());
+                }
+                connection.update_dids(&dids).await?;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
+
+    const REPLY: &str = r#"{"did":"did:plc:abc","time_us":2,"kind":"commit","commit":{"rev":"r","operation":"create","collection":"app.bsky.feed.post","rkey":"3kchild","record":{"$type":"app.bsky.feed.post","text":"more","createdAt":"2024-01-01T00:00:00Z","reply":{"root":{"uri":"at://did:plc:abc/app.bsky.feed.post/3kroot","cid":"c1"},"parent":{"uri":"at://did:plc:abc/app.bsky.feed.post/3kparent","cid":"c2"}}},"cid":"c3"}}"#;
+    const DELETE: &str = r#"{"did":"did:plc:abc","time_us":3,"kind":"commit","commit":{"rev":"r","operation":"delete","collection":"app.bsky.feed.post","rkey":"3kgone"}}"#;
+    const IDENTITY: &str = r#"{"did":"did:plc:abc","time_us":1,"kind":"identity","identity":{"did":"did:plc:abc","handle":"user.bsky.social","seq":1,"time":"2024-01-01T00:00:00Z"}}"#;
+
+    #[test]
+    fn test_parse_message() {
+        assert_eq!(parse_message(IDENTITY), Some((1, None)));
+        assert_eq!(
+            parse_message(DELETE),
+            Some((
+                3,
+                Some(PostEvent::Deleted {
+                    did: "did:plc:abc".to_string(),
+                    uri: "at://did:plc:abc/app.bsky.feed.post/3kgone".to_string(),
+                })
+            ))
+        );
+        assert_eq!(parse_message("not json"), None);
+    }
+
+    #[tokio::test]
+    async fn test_connection_against_local_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut query = String::new();
+            // The handshake callback signature is fixed by tungstenite
+            #[allow(clippy::result_large_err)]
+            let callback = |request: &Request, response: Response| {
+                query = request.uri().query().unwrap_or_default().to_string();
+                Ok(response)
+            };
+            let mut socket = tokio_tungstenite::accept_hdr_async(stream, callback)
+                .await
+                .unwrap();
+
+            for message in [IDENTITY, REPLY] {
+                socket.send(Message::Text(message.into())).await.unwrap();
+            }
+            let update = socket.next().await.unwrap().unwrap();
+            (query, update.into_text().unwrap().to_string())
+        });
+
+        let mut connection = Connection::open(
+            &format!("ws://{}/subscribe", addr),
+            &["did:plc:abc".to_string()],
+            Some(5),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(connection.next().await.unwrap(), (1, None));
+        assert_eq!(
+            connection.next().await.unwrap(),
+            (
+                2,
+                Some(PostEvent::Created {
+                    did: "did:plc:abc".to_string(),
+                    uri: "at://did:plc:abc/app.bsky.feed.post/3kchild".to_string(),
+                    parent_uri: Some("at://did:plc:abc/app.bsky.feed.post/3kparent".to_string()),
+                })
+            )
+        );
+        connection
+            .update_dids(&["did:plc:xyz".to_string()])
+            .await
+            .unwrap();
+
+        let (query, update) = server.await.unwrap();
+        assert_eq!(
+            query,
+            "wantedCollections=app.bsky.feed.post&wantedDids=did%3Aplc%3Aabc&cursor=5"
+        );
+        assert!(update.contains(r#""type":"options_update""#));
+        assert!(update.contains("did:plc:xyz"));
+    }
+}
@@ -1,45 +1,153 @@
 pub mod bluesky;
+pub mod cache;
+pub mod conditional;
 pub mod config;
 pub mod error;
+pub mod events;
+pub mod gemini;
 pub mod handlers;
+pub mod health;
 pub mod html;
+pub mod jetstream;
 pub mod logging;
+pub mod metrics;
+pub mod middleware;
+pub mod og;
+pub mod push;
 pub mod pwa;
+pub mod ratelimit;
+pub mod reload;
+pub mod store;
+pub mod watcher;
+pub mod webhooks;
 
+use std::sync::Arc;
 use std::time::Duration;
 
-use axum::{routing::get, Router};
+use arc_swap::ArcSwap;
+use axum::{
+    routing::{delete, get, post},
+    Router,
+};
 
-use crate::bluesky::BlueskyClient;
-use crate::config::Config;
+use crate::bluesky::{BlueskyClient, BreakerSettings, FetchBudget};
+use crate::cache::Cache;
+use crate::config::{Config, SharedConfig};
+use crate::health::HealthChecker;
+use crate::push::PushService;
+use crate::watcher::WatcherRegistry;
+use crate::webhooks::Webhooks;
 
 #[derive(Clone)]
 pub struct AppState {
     pub client: BlueskyClient,
-    pub config: Config,
+    /// The running config; swapped on reload, so read it where it is used
+    pub config: SharedConfig,
+    /// Shared upstream watchers for live threads
+    pub watchers: WatcherRegistry,
+    /// Outbound webhook subscriptions, when enabled
+    pub webhooks: Option<Webhooks>,
+    /// Web Push notifications, when enabled
+    pub push: Option<PushService>,
+    /// Cache for resolved handles and threads, unless `CACHE_BACKEND=none`
+    pub cache: Option<Cache>,
+    /// Upstream health from background probes, for the readiness endpoints
+    pub health: HealthChecker,
+}
+
+impl AppState {
+    pub fn new(config: &Config) -> anyhow::Result<Self> {
+        config.validate()?;
+        let shared: SharedConfig = Arc::new(ArcSwap::from_pointee(config.clone()));
+
+        let client = BlueskyClient::new(
+            &config.bluesky_api_url,
+            Duration::from_secs(config.request_timeout_seconds),
+        )?
+        .with_upstream_concurrency(config.upstream_max_concurrency)
+        .with_circuit_breaker(BreakerSettings {
+            error_rate: config.circuit_breaker_error_rate,
+            min_calls: config.circuit_breaker_min_calls,
+            open_for: Duration::from_secs(config.circuit_breaker_open_seconds),
+        });
+
+        let cache = Cache::from_config(config)?;
+
+        let health = HealthChecker::new(
+            client.clone(),
+            cache.clone(),
+            Duration::from_secs(config.health_check_interval_seconds),
+        );
+        let mut pages = client.clone().with_fetch_budget(FetchBudget::new(
+            config.thread_max_posts,
+            config.thread_fetch_timeout_seconds,
+        ));
+        if let Some(cache) = &cache {
+            pages = pages.with_cache(cache.clone());
+        }
+        // A watcher's first snapshot is fetched like a page. Its refreshes
+        // diff whole, current threads, so they use the unbudgeted, uncached
+        // client. Watchers invalidate cached threads that they see change.
+        let watchers = WatcherRegistry::new(client, pages.clone(), shared.clone(), cache.clone());
+        let client = pages;
+        let webhooks = if config.webhooks_enabled {
+            Some(Webhooks::new(config, watchers.clone())?)
+        } else {
+            None
+        };
+        let push = if config.push_enabled {
+            Some(PushService::new(config, watchers.clone())?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            watchers,
+            webhooks,
+            push,
+            cache,
+            health,
+            client,
+            config: shared,
+        })
+    }
 }
 
 pub fn create_app(config: &Config) -> anyhow::Result<Router> {
-    let client = BlueskyClient::new(
-        &config.bluesky_api_url,
-        Duration::from_secs(config.request_timeout_seconds),
-    )?;
-
-    let state = AppState {
-        client,
-        config: config.clone(),
-    };
+    Ok(create_router(AppState::new(config)?))
+}
 
-    Ok(Router::new()
+/// Build the HTTP router around existing state, so other frontends
+/// (such as the Gemini listener) can share the same client and config.
+pub fn create_router(state: AppState) -> Router {
+    let router = Router::new()
         .route("/", get(handlers::get_thread))
         .route("/thread", get(handlers::get_thread))
+        // Author page listing recent threads
+        .route("/profile/{handle}", get(handlers::get_profile))
         // Use streaming handler for direct path access (most common use case)
         .route(
             "/profile/{handle}/post/{post_id}",
             get(handlers::get_thread_streaming),
         )
+        // Script-free reader for text and locked-down browsers
+        .route(
+            "/lite/profile/{handle}/post/{post_id}",
+            get(handlers::get_thread_lite),
+        )
+        // Chrome-less thread view for iframes; the only route that allows framing
+        .route(
+            "/embed/profile/{handle}/post/{post_id}",
+            get(handlers::get_thread_embed),
+        )
+        // Generated Open Graph preview cards
+        .route(
+            "/og/profile/{handle}/post/{image}",
+            get(handlers::get_thread_og_image),
+        )
         .route("/health/live", get(handlers::health_live))
         .route("/health/ready", get(handlers::health_ready))
+        .route("/health/details", get(handlers::health_details))
         // PWA routes
         .route("/manifest.json", get(handlers::manifest))
         .route("/sw.js", get(handlers::service_worker))
@@ -48,5 +156,44 @@ pub fn create_app(config: &Config) -> anyhow::Result<Router> {
         .route("/share", get(handlers::share_target))
         // Polling API for thread updates
         .route("/api/thread/updates", get(handlers::get_thread_updates))
-        .with_state(state))
+        // Server-Sent Events stream of thread updates
+        .route("/api/thread/events", get(handlers::get_thread_events))
+        // Rest of a streamed thread page whose connection dropped
+        .route("/api/thread/continue", get(handlers::get_thread_continue))
+        // Outbound webhook subscriptions
+        .route(
+            "/api/webhooks",
+            get(handlers::list_webhooks).post(handlers::create_webhook),
+        )
+        .route("/api/webhooks/{id}", delete(handlers::delete_webhook))
+        // Web Push notifications for followed threads
+        .route("/api/push/key", get(handlers::get_push_key))
+        .route("/api/push/subscribe", post(handlers::push_subscribe))
+        .route("/api/push/unsubscribe", post(handlers::push_unsubscribe));
+
+    let config = state.config.load();
+    let router = if config.metrics_enabled && config.metrics_port == 0 {
+        router.route("/metrics", get(handlers::get_metrics))
+    } else {
+        router
+    };
+
+    // Always installed, since rate limiting can be switched on by a reload
+    router
+        .layer(axum::middleware::from_fn_with_state(
+            ratelimit::RateLimiter::new(state.config.clone()),
+            ratelimit::limit,
+        ))
+        .layer(axum::middleware::from_fn(middleware::deny_framing))
+        .layer(axum::middleware::from_fn(metrics::track_requests))
+        .layer(axum::middleware::from_fn(middleware::trace_requests))
+        .layer(axum::middleware::from_fn(middleware::request_id))
+        .with_state(state)
+}
+
+/// Router for the separate admin port (`METRICS_PORT`), serving only `/metrics`.
+pub fn create_admin_router(state: AppState) -> Router {
+    Router::new()
+        .route("/metrics", get(handlers::get_metrics))
+        .with_state(state)
 }
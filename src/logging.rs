@@ -1,5 +1,12 @@
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::{Protocol, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
 use thiserror::Error;
-use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+
+use crate::config::{Config, LogFormat};
 
 #[derive(Error, Debug)]
 pub enum LoggingError {
@@ -7,17 +14,156 @@ pub enum LoggingError {
     InvalidLogLevel(String),
     #[error("failed to initialize tracing subscriber")]
     SubscriberInit,
+    #[error("failed to build OTLP exporter: {0}")]
+    Exporter(#[from] opentelemetry_otlp::ExporterBuildError),
+}
+
+/// Keeps the trace exporter alive; call [`Telemetry::shutdown`] before exit
+/// so buffered spans are flushed.
+pub struct Telemetry {
+    provider: Option<SdkTracerProvider>,
+}
+
+impl Telemetry {
+    pub fn shutdown(self) {
+        if let Some(provider) = self.provider {
+            if let Err(e) = provider.shutdown() {
+                eprintln!("failed to flush traces: {}", e);
+            }
+        }
+    }
 }
 
-pub fn init(log_level: &str) -> Result<(), LoggingError> {
-    let filter = EnvFilter::try_new(log_level)
-        .map_err(|_| LoggingError::InvalidLogLevel(log_level.to_string()))?;
+pub fn init(config: &Config) -> Result<Telemetry, LoggingError> {
+    let filter = EnvFilter::try_new(&config.log_level)
+        .map_err(|_| LoggingError::InvalidLogLevel(config.log_level.clone()))?;
+
+    let provider = if config.otel_enabled {
+        // Continue traces from incoming W3C `traceparent` headers
+        opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+        Some(tracer_provider(
+            &config.otel_endpoint,
+            &config.otel_service_name,
+        )?)
+    } else {
+        None
+    };
+    let otel_layer = provider
+        .as_ref()
+        .map(|p| tracing_opentelemetry::layer().with_tracer(p.tracer("sklonger")));
+
+    let fmt_layer = match config.log_format {
+        LogFormat::Text => fmt::layer().with_target(true).boxed(),
+        // Span fields (such as the request ID) are included on every line
+        LogFormat::Json => fmt::layer()
+            .json()
+            .with_current_span(true)
+            .with_span_list(false)
+            .boxed(),
+    };
 
     tracing_subscriber::registry()
+        .with(fmt_layer)
         .with(filter)
-        .with(fmt::layer().with_target(true))
+        .with(otel_layer)
         .try_init()
         .map_err(|_| LoggingError::SubscriberInit)?;
 
-    Ok(())
+    Ok(Telemetry { provider })
+}
+
+/// Build a provider that batches spans and exports them as OTLP/HTTP JSON to
+/// `{endpoint}/v1/traces`.
+pub fn tracer_provider(
+    endpoint: &str,
+    service_name: &str,
+) -> Result<SdkTracerProvider, LoggingError> {
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_protocol(Protocol::HttpJson)
+        .with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+        .build()?;
+
+    Ok(SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            Resource::builder()
+                .with_service_name(service_name.to_string())
+                .build(),
+        )
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request, routing::get, Router};
+    use std::sync::mpsc;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    /// Collector stand-in on its own thread, so flushing from the test thread
+    /// cannot block it. Sends each received OTLP body down the channel.
+    fn spawn_collector() -> (String, mpsc::Receiver<String>) {
+        let (tx, rx) = mpsc::channel();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        listener.set_nonblocking(true).unwrap();
+
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            runtime.block_on(async move {
+                let app = Router::new().route(
+                    "/v1/traces",
+                    axum::routing::post(move |body: String| {
+                        let tx = tx.clone();
+                        async move {
+                            let _ = tx.send(body);
+                            "{}"
+                        }
+                    }),
+                );
+                let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+                axum::serve(listener, app).await.unwrap();
+            });
+        });
+        (format!("http://{}", addr), rx)
+    }
+
+    #[tokio::test]
+    async fn test_exports_request_spans_with_remote_parent() {
+        let (endpoint, bodies) = spawn_collector();
+        let provider = tracer_provider(&endpoint, "sklonger-test").unwrap();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        let _guard = tracing::subscriber::set_default(subscriber);
+        opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+
+        let app = Router::new()
+            .route("/things/{id}", get(|| async { "thing" }))
+            .layer(axum::middleware::from_fn(crate::middleware::trace_requests));
+        app.oneshot(
+            Request::builder()
+                .uri("/things/42")
+                .header(
+                    "traceparent",
+                    "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+                )
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+        provider.force_flush().unwrap();
+        let body = bodies.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(body.contains("\"GET /things/{id}\""));
+        assert!(body.contains("4bf92f3577b34da6a3ce929d0e0e4736"));
+        assert!(body.contains("00f067aa0ba902b7"));
+        assert!(body.contains("sklonger-test"));
+        provider.shutdown().unwrap();
+    }
 }
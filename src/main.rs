@@ -1,27 +1,140 @@
 use std::net::SocketAddr;
+use std::path::PathBuf;
 
+use clap::{Parser, Subcommand};
 use tokio::net::TcpListener;
 use tracing::info;
 
-use skeet_longer::{config::Config, create_app, logging};
+use skeet_longer::{
+    config::{Config, ConfigError},
+    create_admin_router, create_router, gemini, jetstream, logging, reload, AppState,
+};
+
+/// Configuration is read from defaults, then `--config`, then environment
+/// variables, then the flags below; later sources win.
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    /// TOML config file; keys are the environment variable names in lowercase
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+    /// HTTP server port (overrides PORT)
+    #[arg(long)]
+    port: Option<u16>,
+    /// Validate the configuration and exit
+    #[arg(long)]
+    check_config: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the effective configuration as TOML, with secrets redacted
+    PrintConfig,
+}
+
+impl Cli {
+    /// Read the config sources; also used to reload on SIGHUP.
+    fn load_config(&self) -> Result<Config, ConfigError> {
+        let mut config = Config::load(self.config.as_deref())?;
+        if let Some(port) = self.port {
+            config.port = port;
+        }
+        Ok(config)
+    }
+}
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let config = Config::from_env()?;
+    let cli = Cli::parse();
+    let config = cli.load_config()?;
 
-    logging::init(&config.log_level)?;
+    if let Some(Command::PrintConfig) = cli.command {
+        print!("{}", config.redacted_toml());
+        return Ok(());
+    }
+    config.validate()?;
+    if cli.check_config {
+        println!("configuration OK");
+        return Ok(());
+    }
 
-    let app = create_app(&config)?;
+    let telemetry = logging::init(&config)?;
+
+    let state = AppState::new(&config)?;
+
+    #[cfg(unix)]
+    tokio::spawn(reload::on_sighup(state.config.clone(), move || {
+        cli.load_config()
+    }));
+
+    if config.gemini_enabled {
+        let acceptor =
+            gemini::load_tls_acceptor(&config.gemini_cert_path, &config.gemini_key_path)?;
+        let addr = SocketAddr::from(([0, 0, 0, 0], config.gemini_port));
+        let listener = TcpListener::bind(addr).await?;
+        info!(port = config.gemini_port, "starting Gemini server");
+
+        tokio::spawn(gemini::serve(listener, acceptor, state.clone()));
+    }
+
+    if config.jetstream_enabled {
+        info!(url = %config.jetstream_url, "starting Jetstream consumer");
+        tokio::spawn(jetstream::run(
+            config.jetstream_url.clone(),
+            state.watchers.clone(),
+        ));
+    }
+
+    state.health.start();
+    if let Some(cache) = &state.cache {
+        cache.start();
+    }
+    if let Some(webhooks) = &state.webhooks {
+        webhooks.start();
+    }
+    if let Some(push) = &state.push {
+        push.start();
+    }
+
+    if config.metrics_enabled && config.metrics_port != 0 {
+        let addr = SocketAddr::from(([0, 0, 0, 0], config.metrics_port));
+        let listener = TcpListener::bind(addr).await?;
+        info!(port = config.metrics_port, "starting metrics server");
+
+        let admin = create_admin_router(state.clone());
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, admin).await {
+                tracing::error!(error = %e, "metrics server stopped");
+            }
+        });
+    }
+
+    let (webhooks, push) = (state.webhooks.clone(), state.push.clone());
+    let app = create_router(state);
 
     let addr = SocketAddr::from(([0, 0, 0, 0], config.port));
     let listener = TcpListener::bind(addr).await?;
     info!(port = config.port, "starting HTTP server");
 
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await?;
+    // Peer addresses feed the per-client rate limiter
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal())
+    .await?;
 
+    // Subscription stores write behind; save what is still pending
+    if let Some(webhooks) = webhooks {
+        webhooks.flush().await;
+    }
+    if let Some(push) = push {
+        push.flush().await;
+    }
     info!("server shutdown complete");
+    telemetry.shutdown();
     Ok(())
 }
 
@@ -0,0 +1,277 @@
+//! Prometheus metrics, served at `/metrics` when enabled.
+//!
+//! Collectors live in one process-wide registry so the HTTP middleware, the
+//! Bluesky client and the handlers can record without threading a handle
+//! through every call. Recording is always on and cheap; `METRICS_ENABLED`
+//! only controls whether the registry is exposed.
+
+use std::future::Future;
+use std::sync::OnceLock;
+use std::time::Instant;
+
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+use prometheus::{
+    Histogram, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+
+use crate::bluesky::client::ClientError;
+
+/// Buckets for the number of posts in a fetched thread
+const THREAD_POST_BUCKETS: &[f64] = &[
+    1.0, 2.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0,
+];
+
+pub struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_duration: HistogramVec,
+    upstream_requests: IntCounterVec,
+    upstream_errors: IntCounterVec,
+    upstream_duration: HistogramVec,
+    thread_posts: Histogram,
+    streams_in_flight: IntGaugeVec,
+    active_pollers: IntGauge,
+    cache_lookups: IntCounterVec,
+}
+
+/// The process-wide metrics registry.
+pub fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(Metrics::new)
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("sklonger".to_string()), None)
+            .expect("registry prefix is valid");
+
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests by route and status"),
+            &["route", "method", "status"],
+        )
+        .unwrap();
+        let http_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Time until the response headers are sent, by route",
+            ),
+            &["route", "method"],
+        )
+        .unwrap();
+        let upstream_requests = IntCounterVec::new(
+            Opts::new(
+                "upstream_requests_total",
+                "XRPC calls to the AppView by method",
+            ),
+            &["method"],
+        )
+        .unwrap();
+        let upstream_errors = IntCounterVec::new(
+            Opts::new(
+                "upstream_errors_total",
+                "Failed XRPC calls to the AppView by method and error",
+            ),
+            &["method", "error"],
+        )
+        .unwrap();
+        let upstream_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "upstream_request_duration_seconds",
+                "XRPC call latency by method",
+            ),
+            &["method"],
+        )
+        .unwrap();
+        let thread_posts = Histogram::with_opts(
+            HistogramOpts::new("thread_posts", "Posts per fetched thread")
+                .buckets(THREAD_POST_BUCKETS.to_vec()),
+        )
+        .unwrap();
+        let streams_in_flight = IntGaugeVec::new(
+            Opts::new("streams_in_flight", "Open streaming responses by kind"),
+            &["kind"],
+        )
+        .unwrap();
+        let active_pollers = IntGauge::new(
+            "active_pollers",
+            "Thread watchers currently polling upstream",
+        )
+        .unwrap();
+        let cache_lookups = IntCounterVec::new(
+            Opts::new("cache_lookups_total", "Cache lookups by cache and result"),
+            &["cache", "result"],
+        )
+        .unwrap();
+
+        registry.register(Box::new(http_requests.clone())).unwrap();
+        registry.register(Box::new(http_duration.clone())).unwrap();
+        registry
+            .register(Box::new(upstream_requests.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(upstream_errors.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(upstream_duration.clone()))
+            .unwrap();
+        registry.register(Box::new(thread_posts.clone())).unwrap();
+        registry
+            .register(Box::new(streams_in_flight.clone()))
+            .unwrap();
+        registry.register(Box::new(active_pollers.clone())).unwrap();
+        registry.register(Box::new(cache_lookups.clone())).unwrap();
+
+        Self {
+            registry,
+            http_requests,
+            http_duration,
+            upstream_requests,
+            upstream_errors,
+            upstream_duration,
+            thread_posts,
+            streams_in_flight,
+            active_pollers,
+            cache_lookups,
+        }
+    }
+
+    /// Render every metric in the Prometheus text exposition format.
+    pub fn encode(&self) -> String {
+        TextEncoder::new()
+            .encode_to_string(&self.registry.gather())
+            .unwrap_or_default()
+    }
+
+    pub fn record_thread_posts(&self, count: usize) {
+        self.thread_posts.observe(count as f64);
+    }
+
+    /// Record a lookup in one of the app's caches (`cache` names which).
+    pub fn record_cache_lookup(&self, cache: &str, hit: bool) {
+        let result = if hit { "hit" } else { "miss" };
+        self.cache_lookups.with_label_values(&[cache, result]).inc();
+    }
+
+    pub fn set_active_pollers(&self, count: usize) {
+        self.active_pollers.set(count as i64);
+    }
+
+    /// Count a streaming response as open until the returned guard is dropped.
+    pub fn stream_started(&self, kind: &'static str) -> StreamGuard {
+        self.streams_in_flight.with_label_values(&[kind]).inc();
+        StreamGuard { kind }
+    }
+}
+
+/// Marks a streaming response as closed when dropped.
+pub struct StreamGuard {
+    kind: &'static str,
+}
+
+impl Drop for StreamGuard {
+    fn drop(&mut self) {
+        metrics()
+            .streams_in_flight
+            .with_label_values(&[self.kind])
+            .dec();
+    }
+}
+
+/// Time an XRPC call to the AppView and count it, with its error if it fails.
+/// `method` is the lexicon method name, e.g. `app.bsky.feed.getPostThread`.
+pub async fn observe_upstream<T>(
+    method: &'static str,
+    call: impl Future<Output = Result<T, ClientError>>,
+) -> Result<T, ClientError> {
+    let m = metrics();
+    let started = Instant::now();
+    let result = call.await;
+
+    m.upstream_requests.with_label_values(&[method]).inc();
+    m.upstream_duration
+        .with_label_values(&[method])
+        .observe(started.elapsed().as_secs_f64());
+    if let Err(e) = &result {
+        m.upstream_errors
+            .with_label_values(&[method, e.kind()])
+            .inc();
+    }
+    result
+}
+
+/// Count and time every request by its route template (not the raw path, so
+/// handles and post IDs do not become labels).
+pub async fn track_requests(request: Request, next: Next) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let method = request.method().to_string();
+    let started = Instant::now();
+
+    let response = next.run(request).await;
+
+    let m = metrics();
+    m.http_duration
+        .with_label_values(&[&route, &method])
+        .observe(started.elapsed().as_secs_f64());
+    m.http_requests
+        .with_label_values(&[&route, &method, response.status().as_str()])
+        .inc();
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_records_upstream_calls() {
+        let ok: Result<(), ClientError> = observe_upstream("test.ok", async { Ok(()) }).await;
+        assert!(ok.is_ok());
+        let err: Result<(), ClientError> =
+            observe_upstream("test.err", async { Err(ClientError::NotFound) }).await;
+        assert!(err.is_err());
+
+        {
+            let _guard = metrics().stream_started("test");
+            assert!(metrics()
+                .encode()
+                .contains("sklonger_streams_in_flight{kind=\"test\"} 1"));
+        }
+
+        let text = metrics().encode();
+        assert!(text.contains("sklonger_upstream_requests_total{method=\"test.ok\"} 1"));
+        assert!(text
+            .contains("sklonger_upstream_errors_total{error=\"not_found\",method=\"test.err\"} 1"));
+        assert!(text.contains("sklonger_streams_in_flight{kind=\"test\"} 0"));
+    }
+
+    #[tokio::test]
+    async fn test_requests_are_labelled_by_route() {
+        use axum::{body::Body, routing::get, Router};
+        use tower::ServiceExt;
+
+        let app = Router::new()
+            .route("/things/{id}", get(|| async { "thing" }))
+            .layer(axum::middleware::from_fn(track_requests));
+        app.oneshot(
+            Request::builder()
+                .uri("/things/42")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+        assert!(metrics().encode().contains(
+            "sklonger_http_requests_total{method=\"GET\",route=\"/things/{id}\",status=\"200\"} 1"
+        ));
+    }
+}
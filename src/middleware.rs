@@ -0,0 +1,199 @@
+//! Cross-cutting HTTP middleware applied to the whole router.
+
+use axum::{
+    extract::{MatchedPath, Request},
+    http::{
+        header::{CONTENT_SECURITY_POLICY, X_FRAME_OPTIONS},
+        HeaderMap, HeaderValue,
+    },
+    middleware::Next,
+    response::Response,
+};
+use opentelemetry::propagation::Extractor;
+use rand_core::RngCore;
+use tracing::Instrument;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// Forbid framing by default.
+/// Handlers that should be embeddable (the `/embed/...` route) set their own
+/// `Content-Security-Policy` with a `frame-ancestors` directive, which is left alone.
+pub async fn deny_framing(request: Request, next: Next) -> Response {
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+
+    if !headers.contains_key(CONTENT_SECURITY_POLICY) {
+        headers.insert(
+            CONTENT_SECURITY_POLICY,
+            HeaderValue::from_static("frame-ancestors 'none'"),
+        );
+        headers.insert(X_FRAME_OPTIONS, HeaderValue::from_static("DENY"));
+    }
+
+    response
+}
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest `X-Request-Id` accepted from a client; longer ones are replaced
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// A request's correlation ID, available to handlers as an extension.
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+fn generate_request_id() -> String {
+    let mut bytes = [0u8; 16];
+    rand_core::OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Use the caller's `X-Request-Id` (from a proxy or another service) when it
+/// is reasonable, otherwise generate one, and echo it on the response.
+pub async fn request_id(mut request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| {
+            !id.is_empty()
+                && id.len() <= MAX_REQUEST_ID_LEN
+                && id.bytes().all(|b| b.is_ascii_graphic())
+        })
+        .map(String::from)
+        .unwrap_or_else(generate_request_id);
+    request.extensions_mut().insert(RequestId(id.clone()));
+
+    let mut response = next.run(request).await;
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+/// Reads trace context from request headers.
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|v| v.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|k| k.as_str()).collect()
+    }
+}
+
+/// Open a span per request, continuing the caller's trace when it sends a
+/// W3C `traceparent` header. Handler and upstream call spans nest under it.
+pub async fn trace_requests(request: Request, next: Next) -> Response {
+    let parent = opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(request.headers()))
+    });
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let method = request.method().clone();
+    let request_id = request
+        .extensions()
+        .get::<RequestId>()
+        .map(|id| id.0.clone())
+        .unwrap_or_default();
+
+    // Every log line inside the request (and tasks it spawns in this span)
+    // carries the request ID
+    let span = tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        otel.name = %format!("{} {}", method, route),
+        otel.kind = "server",
+        http.request.method = %method,
+        http.route = %route,
+        http.response.status_code = tracing::field::Empty,
+    );
+    // Only fails when no OpenTelemetry layer is installed
+    let _ = span.set_parent(parent);
+
+    let response = next.run(request).instrument(span.clone()).await;
+    span.record("http.response.status_code", response.status().as_u16());
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+        routing::get,
+        Router,
+    };
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        Router::new()
+            .route("/page", get(|| async { "page" }))
+            .route(
+                "/embed",
+                get(|| async { ([(CONTENT_SECURITY_POLICY, "frame-ancestors *")], "embed") }),
+            )
+            .layer(axum::middleware::from_fn(deny_framing))
+    }
+
+    async fn send(uri: &str) -> Response {
+        app()
+            .oneshot(
+                Request::builder()
+                    .uri(uri)
+                    .body(Body::empty())
+                    .expect("request should build"),
+            )
+            .await
+            .expect("request should succeed")
+    }
+
+    #[tokio::test]
+    async fn test_request_id_is_accepted_or_generated() {
+        let app = Router::new()
+            .route("/page", get(|| async { "page" }))
+            .layer(axum::middleware::from_fn(request_id));
+        let send = |id: Option<&str>| {
+            let mut request = Request::builder().uri("/page");
+            if let Some(id) = id {
+                request = request.header(REQUEST_ID_HEADER, id);
+            }
+            app.clone().oneshot(request.body(Body::empty()).unwrap())
+        };
+
+        let response = send(Some("abc-123")).await.unwrap();
+        assert_eq!(response.headers()[REQUEST_ID_HEADER], "abc-123");
+
+        let response = send(None).await.unwrap();
+        assert_eq!(response.headers()[REQUEST_ID_HEADER].len(), 32);
+
+        let response = send(Some("has spaces")).await.unwrap();
+        assert_ne!(response.headers()[REQUEST_ID_HEADER], "has spaces");
+    }
+
+    #[tokio::test]
+    async fn test_pages_deny_framing() {
+        let response = send("/page").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[X_FRAME_OPTIONS], "DENY");
+        assert_eq!(
+            response.headers()[CONTENT_SECURITY_POLICY],
+            "frame-ancestors 'none'"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_embed_policy_is_preserved() {
+        let response = send("/embed").await;
+        assert!(response.headers().get(X_FRAME_OPTIONS).is_none());
+        assert_eq!(
+            response.headers()[CONTENT_SECURITY_POLICY],
+            "frame-ancestors *"
+        );
+    }
+}
@@ -0,0 +1,349 @@
+//! Open Graph preview images for threads.
+//!
+//! Cards are rasterized in pure Rust (tiny-skia for shapes, ab_glyph for text)
+//! with the DejaVu Sans fonts embedded at compile time, so the container image
+//! needs no system fonts or native libraries.
+
+use ab_glyph::{Font, FontRef, PxScale, ScaleFont};
+use thiserror::Error;
+use tiny_skia::{Color, Paint, Pixmap, Rect, Transform};
+
+/// Card dimensions recommended for `summary_large_image` and `og:image`.
+pub const CARD_WIDTH: u32 = 1200;
+pub const CARD_HEIGHT: u32 = 630;
+
+const MARGIN: f32 = 80.0;
+const ACCENT_BAR_WIDTH: f32 = 16.0;
+const EXCERPT_MAX_LINES: usize = 5;
+
+/// Colors match the light theme in styles.css.
+const BACKGROUND: Rgb = Rgb(0xfa, 0xfa, 0xfa);
+const TEXT_PRIMARY: Rgb = Rgb(0x1f, 0x29, 0x37);
+const TEXT_SECONDARY: Rgb = Rgb(0x4b, 0x55, 0x63);
+const TEXT_MUTED: Rgb = Rgb(0x6b, 0x72, 0x80);
+const ACCENT: Rgb = Rgb(0x3b, 0x82, 0xf6);
+const BORDER: Rgb = Rgb(0xd1, 0xd5, 0xdb);
+
+#[derive(Error, Debug)]
+pub enum OgError {
+    #[error("failed to load embedded font")]
+    Font,
+    #[error("failed to allocate image")]
+    Allocation,
+    #[error("failed to encode PNG: {0}")]
+    Encode(String),
+}
+
+/// Content shown on a thread preview card.
+pub struct ThreadCard<'a> {
+    pub display_name: &'a str,
+    pub handle: &'a str,
+    /// Text of the first post; wrapped and truncated to fit
+    pub excerpt: &'a str,
+    pub post_count: usize,
+}
+
+#[derive(Clone, Copy)]
+struct Rgb(u8, u8, u8);
+
+impl Rgb {
+    fn to_color(self) -> Color {
+        Color::from_rgba8(self.0, self.1, self.2, 255)
+    }
+}
+
+struct Fonts<'a> {
+    regular: FontRef<'a>,
+    bold: FontRef<'a>,
+}
+
+impl Fonts<'static> {
+    fn load() -> Result<Self, OgError> {
+        Ok(Self {
+            regular: FontRef::try_from_slice(dejavu::sans::regular()).map_err(|_| OgError::Font)?,
+            bold: FontRef::try_from_slice(dejavu::sans::bold()).map_err(|_| OgError::Font)?,
+        })
+    }
+}
+
+/// Render a thread preview card as a PNG image.
+pub fn render_thread_card(card: &ThreadCard) -> Result<Vec<u8>, OgError> {
+    let fonts = Fonts::load()?;
+    let mut pixmap = Pixmap::new(CARD_WIDTH, CARD_HEIGHT).ok_or(OgError::Allocation)?;
+    pixmap.fill(BACKGROUND.to_color());
+
+    let width = CARD_WIDTH as f32;
+    let height = CARD_HEIGHT as f32;
+    let text_width = width - MARGIN * 2.0;
+
+    fill_rect(&mut pixmap, 0.0, 0.0, ACCENT_BAR_WIDTH, height, ACCENT);
+
+    // Author
+    let name = fit_line(&fonts.bold, 56.0, card.display_name, text_width);
+    draw_text(
+        &mut pixmap,
+        &fonts.bold,
+        56.0,
+        MARGIN,
+        130.0,
+        TEXT_PRIMARY,
+        &name,
+    );
+    let handle = fit_line(
+        &fonts.regular,
+        32.0,
+        &format!("@{}", card.handle),
+        text_width,
+    );
+    draw_text(
+        &mut pixmap,
+        &fonts.regular,
+        32.0,
+        MARGIN,
+        180.0,
+        TEXT_SECONDARY,
+        &handle,
+    );
+
+    // First post excerpt
+    let lines = wrap_text(
+        &fonts.regular,
+        38.0,
+        card.excerpt,
+        text_width,
+        EXCERPT_MAX_LINES,
+    );
+    for (i, line) in lines.iter().enumerate() {
+        let baseline = 270.0 + i as f32 * 54.0;
+        draw_text(
+            &mut pixmap,
+            &fonts.regular,
+            38.0,
+            MARGIN,
+            baseline,
+            TEXT_PRIMARY,
+            line,
+        );
+    }
+
+    // Footer: post count and branding
+    fill_rect(&mut pixmap, MARGIN, 530.0, text_width, 2.0, BORDER);
+    let count = match card.post_count {
+        1 => "1 post".to_string(),
+        n => format!("{} posts", n),
+    };
+    draw_text(
+        &mut pixmap,
+        &fonts.regular,
+        30.0,
+        MARGIN,
+        585.0,
+        TEXT_MUTED,
+        &count,
+    );
+    let brand_width = measure_text(&fonts.bold, 36.0, "sklonger");
+    draw_text(
+        &mut pixmap,
+        &fonts.bold,
+        36.0,
+        width - MARGIN - brand_width,
+        587.0,
+        ACCENT,
+        "sklonger",
+    );
+
+    pixmap
+        .encode_png()
+        .map_err(|e| OgError::Encode(e.to_string()))
+}
+
+fn fill_rect(pixmap: &mut Pixmap, x: f32, y: f32, w: f32, h: f32, color: Rgb) {
+    if let Some(rect) = Rect::from_xywh(x, y, w, h) {
+        let mut paint = Paint::default();
+        paint.set_color(color.to_color());
+        pixmap.fill_rect(rect, &paint, Transform::identity(), None);
+    }
+}
+
+/// Whether the font can draw this character (DejaVu has no emoji, for example).
+fn has_glyph(font: &FontRef, c: char) -> bool {
+    font.glyph_id(c).0 != 0
+}
+
+/// Width in pixels of a single line of text.
+fn measure_text(font: &FontRef, size: f32, text: &str) -> f32 {
+    let scaled = font.as_scaled(PxScale::from(size));
+    let mut width = 0.0;
+    let mut previous = None;
+    for c in text.chars().filter(|&c| has_glyph(font, c)) {
+        let id = scaled.glyph_id(c);
+        if let Some(prev) = previous {
+            width += scaled.kern(prev, id);
+        }
+        width += scaled.h_advance(id);
+        previous = Some(id);
+    }
+    width
+}
+
+/// Draw a single line of text with its baseline at `y`.
+fn draw_text(
+    pixmap: &mut Pixmap,
+    font: &FontRef,
+    size: f32,
+    x: f32,
+    y: f32,
+    color: Rgb,
+    text: &str,
+) {
+    let scaled = font.as_scaled(PxScale::from(size));
+    let (width, height) = (pixmap.width() as i32, pixmap.height() as i32);
+    let data = pixmap.data_mut();
+
+    let mut caret = x;
+    let mut previous = None;
+    for c in text.chars().filter(|&c| has_glyph(font, c)) {
+        let id = scaled.glyph_id(c);
+        if let Some(prev) = previous {
+            caret += scaled.kern(prev, id);
+        }
+        let glyph = id.with_scale_and_position(size, ab_glyph::point(caret, y));
+        caret += scaled.h_advance(id);
+        previous = Some(id);
+
+        let Some(outline) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outline.px_bounds();
+        outline.draw(|gx, gy, coverage| {
+            let px = bounds.min.x as i32 + gx as i32;
+            let py = bounds.min.y as i32 + gy as i32;
+            if px < 0 || py < 0 || px >= width || py >= height {
+                return;
+            }
+            // The canvas is fully opaque, so premultiplied blending reduces to a lerp
+            let idx = ((py * width + px) * 4) as usize;
+            let alpha = coverage.clamp(0.0, 1.0);
+            for (channel, value) in [color.0, color.1, color.2].into_iter().enumerate() {
+                let dst = data[idx + channel] as f32;
+                data[idx + channel] = (value as f32 * alpha + dst * (1.0 - alpha)).round() as u8;
+            }
+        });
+    }
+}
+
+/// Truncate a single line with an ellipsis so it fits within `max_width`.
+fn fit_line(font: &FontRef, size: f32, text: &str, max_width: f32) -> String {
+    if measure_text(font, size, text) <= max_width {
+        return text.to_string();
+    }
+
+    let mut line: String = text.to_string();
+    while !line.is_empty() && measure_text(font, size, &format!("{}…", line)) > max_width {
+        line.pop();
+    }
+    format!("{}…", line.trim_end())
+}
+
+/// Word-wrap text into at most `max_lines` lines of `max_width` pixels.
+/// Whitespace (including newlines) is collapsed; the last line gets an
+/// ellipsis when the text does not fit.
+fn wrap_text(
+    font: &FontRef,
+    size: f32,
+    text: &str,
+    max_width: f32,
+    max_lines: usize,
+) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut words = text.split_whitespace().peekable();
+    let mut truncated = false;
+
+    while let Some(word) = words.next() {
+        let candidate = if current.is_empty() {
+            word.to_string()
+        } else {
+            format!("{} {}", current, word)
+        };
+
+        if measure_text(font, size, &candidate) <= max_width {
+            current = candidate;
+            continue;
+        }
+
+        if !current.is_empty() {
+            lines.push(std::mem::take(&mut current));
+        }
+        if lines.len() == max_lines {
+            truncated = true;
+            break;
+        }
+
+        // A single word wider than the line (e.g. a URL) is hard-truncated
+        current = fit_line(font, size, word, max_width);
+        if current != word && words.peek().is_some() {
+            lines.push(std::mem::take(&mut current));
+            if lines.len() == max_lines {
+                truncated = true;
+                break;
+            }
+        }
+    }
+
+    if !current.is_empty() {
+        if lines.len() < max_lines {
+            lines.push(current);
+        } else {
+            truncated = true;
+        }
+    }
+
+    if truncated {
+        if let Some(last) = lines.last_mut() {
+            *last = fit_line(font, size, &format!("{}…", last), max_width);
+        }
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_card_is_png_with_expected_size() {
+        let png = render_thread_card(&ThreadCard {
+            display_name: "Jay 🦋",
+            handle: "jay.bsky.team",
+            excerpt: "A long thread about protocols. ".repeat(40).as_str(),
+            post_count: 12,
+        })
+        .expect("card should render");
+
+        let decoded = Pixmap::decode_png(&png).expect("output should be a valid PNG");
+        assert_eq!(decoded.width(), CARD_WIDTH);
+        assert_eq!(decoded.height(), CARD_HEIGHT);
+    }
+
+    #[test]
+    fn test_wrap_text_respects_line_limit_and_width() {
+        let fonts = Fonts::load().unwrap();
+        let text = "word ".repeat(500);
+        let lines = wrap_text(&fonts.regular, 38.0, &text, 600.0, 3);
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines[2].ends_with('…'));
+        for line in &lines {
+            assert!(measure_text(&fonts.regular, 38.0, line) <= 600.0);
+        }
+    }
+
+    #[test]
+    fn test_wrap_text_keeps_short_text_intact() {
+        let fonts = Fonts::load().unwrap();
+        let lines = wrap_text(&fonts.regular, 38.0, "hello\n\nworld", 600.0, 3);
+        assert_eq!(lines, vec!["hello world".to_string()]);
+    }
+}
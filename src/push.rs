@@ -0,0 +1,844 @@
+//! Web Push notifications for followed threads.
+//!
+//! Readers can ask to be notified when a thread continues. The browser hands
+//! us a push subscription (an endpoint on its vendor's push service plus the
+//! keys to encrypt for it); we follow the thread through the shared watcher
+//! and, whenever new posts appear, POST an encrypted message (RFC 8291,
+//! `aes128gcm`) authorised with a VAPID token (RFC 8292). Subscriptions are
+//! persisted and removed when the thread goes stale or the push service
+//! reports them expired.
+//!
+//! Subscribing needs no authentication, so endpoints are limited to the
+//! browser vendors' push services (anything else would let callers point our
+//! signed POSTs at arbitrary hosts), and subscriptions are capped per thread,
+//! per client address and in total.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes128Gcm, Nonce};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use hkdf::Hkdf;
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey};
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use p256::{PublicKey, SecretKey};
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tokio::sync::watch;
+use tokio::task::AbortHandle;
+use tracing::{debug, info, warn};
+
+use crate::bluesky::client::ClientError;
+use crate::bluesky::types::{Author, ThreadPost};
+use crate::config::Config;
+use crate::events::{new_post_batches, FollowCursor};
+use crate::store::{JsonStore, Keyed, StoreError};
+use crate::watcher::{Snapshot, WatcherRegistry};
+
+/// How long a push service should hold a message for an offline device
+const MESSAGE_TTL_SECONDS: u32 = 24 * 60 * 60;
+/// VAPID tokens may be valid for at most 24 hours
+const VAPID_TOKEN_LIFETIME_SECONDS: i64 = 12 * 60 * 60;
+/// Record size advertised in the `aes128gcm` header; messages fit in one record
+const RECORD_SIZE: u32 = 4096;
+const MAX_ATTEMPTS: u32 = 4;
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(2);
+/// Characters of the first new post shown in a notification
+const NOTIFICATION_EXCERPT_CHARS: usize = 140;
+/// Push services of the major browsers; endpoint hosts must be one of these
+/// or a subdomain of one
+const PUSH_SERVICE_HOSTS: &[&str] = &[
+    "fcm.googleapis.com",
+    "android.googleapis.com",
+    "push.services.mozilla.com",
+    "push.apple.com",
+    "notify.windows.com",
+];
+const MAX_SUBSCRIPTIONS: usize = 10_000;
+const MAX_SUBSCRIPTIONS_PER_THREAD: usize = 1_000;
+const MAX_SUBSCRIPTIONS_PER_CLIENT: usize = 20;
+/// Applies instead of the per-client limit when the address is unknown
+const MAX_SUBSCRIPTIONS_PER_HANDLE: usize = 2_000;
+
+#[derive(Error, Debug)]
+pub enum PushError {
+    #[error("invalid VAPID private key")]
+    InvalidVapidKey,
+    #[error("invalid push subscription: {0}")]
+    InvalidSubscription(&'static str),
+    #[error("too many push subscriptions {0}")]
+    Limit(&'static str),
+    #[error("failed to encrypt push message")]
+    Encryption,
+    #[error(transparent)]
+    Store(#[from] StoreError),
+    #[error("failed to encode message: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("failed to fetch thread: {0}")]
+    Client(#[from] ClientError),
+    #[error("thread is no longer live")]
+    Stale,
+    #[error("push request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("push service returned {0}")]
+    Status(reqwest::StatusCode),
+    /// The push service no longer accepts messages for this subscription
+    #[error("push subscription has expired")]
+    Gone,
+}
+
+impl PushError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            PushError::Http(_) => true,
+            PushError::Status(status) => {
+                status.is_server_error() || *status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            }
+            _ => false,
+        }
+    }
+}
+
+/// A browser push subscription, as returned by `PushSubscription.toJSON()`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PushSubscription {
+    pub endpoint: String,
+    pub keys: PushKeys,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PushKeys {
+    /// Base64url P-256 public key of the browser
+    pub p256dh: String,
+    /// Base64url 16-byte authentication secret
+    pub auth: String,
+}
+
+/// Decoded key material of a subscription.
+struct ReceiverKeys {
+    public: Vec<u8>,
+    auth: Vec<u8>,
+}
+
+impl PushSubscription {
+    fn receiver_keys(&self) -> Result<ReceiverKeys, PushError> {
+        let public = decode_base64(&self.keys.p256dh)
+            .filter(|key| PublicKey::from_sec1_bytes(key).is_ok() && key.len() == 65)
+            .ok_or(PushError::InvalidSubscription(
+                "p256dh is not a P-256 public key",
+            ))?;
+        let auth = decode_base64(&self.keys.auth)
+            .filter(|auth| auth.len() == 16)
+            .ok_or(PushError::InvalidSubscription("auth must be 16 bytes"))?;
+        Ok(ReceiverKeys { public, auth })
+    }
+
+    fn validate(&self) -> Result<(), PushError> {
+        let url = match url::Url::parse(&self.endpoint) {
+            Ok(url) if url.scheme() == "https" && url.port().is_none() => url,
+            _ => {
+                return Err(PushError::InvalidSubscription(
+                    "endpoint must be an https URL",
+                ))
+            }
+        };
+        let host = url.host_str().unwrap_or_default();
+        if !PUSH_SERVICE_HOSTS
+            .iter()
+            .any(|known| host == *known || host.ends_with(&format!(".{}", known)))
+        {
+            return Err(PushError::InvalidSubscription(
+                "endpoint is not on a known push service",
+            ));
+        }
+        self.receiver_keys().map(|_| ())
+    }
+}
+
+/// Browsers use unpadded base64url, but some libraries pad.
+fn decode_base64(value: &str) -> Option<Vec<u8>> {
+    URL_SAFE_NO_PAD.decode(value.trim_end_matches('=')).ok()
+}
+
+/// The server's VAPID key pair (RFC 8292), identifying us to push services.
+pub struct VapidKey {
+    signing: SigningKey,
+}
+
+impl VapidKey {
+    /// Parse a base64url-encoded raw P-256 private key, the format generated by
+    /// common web-push tooling (`npx web-push generate-vapid-keys`).
+    pub fn from_base64(private_key: &str) -> Result<Self, PushError> {
+        let bytes = decode_base64(private_key.trim()).ok_or(PushError::InvalidVapidKey)?;
+        let signing = SigningKey::from_slice(&bytes).map_err(|_| PushError::InvalidVapidKey)?;
+        Ok(Self { signing })
+    }
+
+    /// Uncompressed public key, base64url-encoded; the browser's `applicationServerKey`.
+    pub fn public_key(&self) -> String {
+        let point = self.signing.verifying_key().to_encoded_point(false);
+        URL_SAFE_NO_PAD.encode(point.as_bytes())
+    }
+
+    /// `Authorization` header value for a request to `endpoint`.
+    fn authorization(
+        &self,
+        endpoint: &str,
+        subject: &str,
+        expires: i64,
+    ) -> Result<String, PushError> {
+        let audience = url::Url::parse(endpoint)
+            .map_err(|_| PushError::InvalidSubscription("endpoint is not a URL"))?
+            .origin()
+            .ascii_serialization();
+        let header = URL_SAFE_NO_PAD.encode(r#"{"typ":"JWT","alg":"ES256"}"#);
+        let claims = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&serde_json::json!({
+            "aud": audience,
+            "exp": expires,
+            "sub": subject,
+        }))?);
+        let signing_input = format!("{}.{}", header, claims);
+        let signature: Signature = self.signing.sign(signing_input.as_bytes());
+        Ok(format!(
+            "vapid t={}.{}, k={}",
+            signing_input,
+            URL_SAFE_NO_PAD.encode(signature.to_bytes()),
+            self.public_key()
+        ))
+    }
+}
+
+/// Derive the content encryption key and nonce (RFC 8291 section 3.4).
+fn derive_keys(
+    ecdh_secret: &[u8],
+    auth: &[u8],
+    receiver_public: &[u8],
+    sender_public: &[u8],
+    salt: &[u8],
+) -> Result<([u8; 16], [u8; 12]), PushError> {
+    let mut key_info = b"WebPush: info\0".to_vec();
+    key_info.extend_from_slice(receiver_public);
+    key_info.extend_from_slice(sender_public);
+    let mut ikm = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(auth), ecdh_secret)
+        .expand(&key_info, &mut ikm)
+        .map_err(|_| PushError::Encryption)?;
+
+    let prk = Hkdf::<Sha256>::new(Some(salt), &ikm);
+    let mut cek = [0u8; 16];
+    let mut nonce = [0u8; 12];
+    prk.expand(b"Content-Encoding: aes128gcm\0", &mut cek)
+        .map_err(|_| PushError::Encryption)?;
+    prk.expand(b"Content-Encoding: nonce\0", &mut nonce)
+        .map_err(|_| PushError::Encryption)?;
+    Ok((cek, nonce))
+}
+
+/// Encrypt a message as a single `aes128gcm` record with the given sender key and salt.
+fn encrypt_with(
+    payload: &[u8],
+    receiver: &ReceiverKeys,
+    sender: &SecretKey,
+    salt: [u8; 16],
+) -> Result<Vec<u8>, PushError> {
+    let receiver_key =
+        PublicKey::from_sec1_bytes(&receiver.public).map_err(|_| PushError::Encryption)?;
+    let sender_public = sender.public_key().to_encoded_point(false);
+    let shared = p256::ecdh::diffie_hellman(sender.to_nonzero_scalar(), receiver_key.as_affine());
+    let (cek, nonce) = derive_keys(
+        shared.raw_secret_bytes(),
+        &receiver.auth,
+        &receiver.public,
+        sender_public.as_bytes(),
+        &salt,
+    )?;
+
+    // A single record ends with the 0x02 delimiter and needs no padding
+    let mut plaintext = payload.to_vec();
+    plaintext.push(2);
+    let ciphertext = Aes128Gcm::new_from_slice(&cek)
+        .map_err(|_| PushError::Encryption)?
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+        .map_err(|_| PushError::Encryption)?;
+
+    let mut body = Vec::with_capacity(86 + ciphertext.len());
+    body.extend_from_slice(&salt);
+    body.extend_from_slice(&RECORD_SIZE.to_be_bytes());
+    body.push(sender_public.as_bytes().len() as u8);
+    body.extend_from_slice(sender_public.as_bytes());
+    body.extend_from_slice(&ciphertext);
+    Ok(body)
+}
+
+/// Encrypt a message with a fresh sender key and salt.
+fn encrypt(payload: &[u8], receiver: &ReceiverKeys) -> Result<Vec<u8>, PushError> {
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    encrypt_with(payload, receiver, &SecretKey::random(&mut OsRng), salt)
+}
+
+/// Deliver one message to a push service.
+async fn send_notification(
+    http: &reqwest::Client,
+    vapid: &VapidKey,
+    subject: &str,
+    subscription: &PushSubscription,
+    payload: &[u8],
+) -> Result<(), PushError> {
+    let body = encrypt(payload, &subscription.receiver_keys()?)?;
+    let expires = Utc::now().timestamp() + VAPID_TOKEN_LIFETIME_SECONDS;
+    let response = http
+        .post(&subscription.endpoint)
+        .header(
+            reqwest::header::AUTHORIZATION,
+            vapid.authorization(&subscription.endpoint, subject, expires)?,
+        )
+        .header(reqwest::header::CONTENT_ENCODING, "aes128gcm")
+        .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
+        .header("TTL", MESSAGE_TTL_SECONDS.to_string())
+        .header("Urgency", "normal")
+        .body(body)
+        .send()
+        .await?;
+
+    match response.status() {
+        status if status.is_success() => Ok(()),
+        reqwest::StatusCode::NOT_FOUND | reqwest::StatusCode::GONE => Err(PushError::Gone),
+        status => Err(PushError::Status(status)),
+    }
+}
+
+/// A push subscription following one thread.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Follower {
+    id: String,
+    handle: String,
+    post_id: String,
+    subscription: PushSubscription,
+    created_at: DateTime<Utc>,
+    #[serde(flatten)]
+    cursor: FollowCursor,
+    /// Address that subscribed, for the per-client limit; not persisted
+    #[serde(skip)]
+    client: Option<IpAddr>,
+}
+
+impl Keyed for Follower {
+    fn key(&self) -> &str {
+        &self.id
+    }
+}
+
+/// Followers are identified by thread and endpoint, so subscribing twice is harmless.
+fn follower_id(handle: &str, post_id: &str, endpoint: &str) -> String {
+    let digest = Sha256::digest(format!(
+        "{}/{} {}",
+        handle.to_lowercase(),
+        post_id,
+        endpoint
+    ));
+    digest[..8].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Refuse a new follower of `handle`/`post_id` from `client` when `others`
+/// already use up one of the limits.
+fn check_limits<'a>(
+    others: impl Iterator<Item = &'a Follower>,
+    handle: &str,
+    post_id: &str,
+    client: Option<IpAddr>,
+) -> Result<(), PushError> {
+    let (mut total, mut thread, mut from_client, mut for_handle) = (0, 0, 0, 0);
+    for follower in others {
+        total += 1;
+        let same_handle = follower.handle.eq_ignore_ascii_case(handle);
+        if same_handle && follower.post_id == post_id {
+            thread += 1;
+        }
+        if client.is_some() && follower.client == client {
+            from_client += 1;
+        }
+        if client.is_none() && follower.client.is_none() && same_handle {
+            for_handle += 1;
+        }
+    }
+    if total >= MAX_SUBSCRIPTIONS {
+        Err(PushError::Limit("on this server"))
+    } else if thread >= MAX_SUBSCRIPTIONS_PER_THREAD {
+        Err(PushError::Limit("for this thread"))
+    } else if from_client >= MAX_SUBSCRIPTIONS_PER_CLIENT {
+        Err(PushError::Limit("from this address"))
+    } else if for_handle >= MAX_SUBSCRIPTIONS_PER_HANDLE {
+        Err(PushError::Limit("for this author"))
+    } else {
+        Ok(())
+    }
+}
+
+/// The notification shown for new posts, read by the service worker.
+fn notification_json(
+    author: &Author,
+    posts: &[ThreadPost],
+    thread_url: &str,
+    tag: &str,
+) -> serde_json::Value {
+    let first = posts.first().map(|p| p.text.as_str()).unwrap_or_default();
+    let mut body: String = first.chars().take(NOTIFICATION_EXCERPT_CHARS).collect();
+    if first.chars().count() > NOTIFICATION_EXCERPT_CHARS {
+        body.push('…');
+    }
+    if posts.len() > 1 {
+        body.push_str(&format!(" (+{} more)", posts.len() - 1));
+    }
+    serde_json::json!({
+        "title": format!("@{} continued the thread", author.handle),
+        "body": body,
+        "url": thread_url,
+        "tag": tag,
+    })
+}
+
+/// Web Push subscriptions, shared through `AppState`.
+#[derive(Clone)]
+pub struct PushService {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    store: JsonStore<Follower>,
+    watchers: WatcherRegistry,
+    http: reqwest::Client,
+    vapid: VapidKey,
+    /// `sub` claim of VAPID tokens: a contact URL for push service operators
+    subject: String,
+    public_url: String,
+    /// Delivery task per follower ID
+    tasks: Mutex<HashMap<String, AbortHandle>>,
+}
+
+impl PushService {
+    pub fn new(config: &Config, watchers: WatcherRegistry) -> Result<Self, PushError> {
+        // Push services answer directly; a redirect could lead anywhere
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.request_timeout_seconds))
+            .redirect(reqwest::redirect::Policy::none())
+            .build()?;
+        let subject = if config.vapid_subject.is_empty() {
+            config.public_url.clone()
+        } else {
+            config.vapid_subject.clone()
+        };
+
+        Ok(Self {
+            inner: Arc::new(Inner {
+                store: JsonStore::load(&config.push_subscriptions_path)?,
+                watchers,
+                http,
+                vapid: VapidKey::from_base64(&config.vapid_private_key)?,
+                subject,
+                public_url: config.public_url.clone(),
+                tasks: Mutex::new(HashMap::new()),
+            }),
+        })
+    }
+
+    /// Resume delivery for subscriptions loaded from the store. Ones whose
+    /// endpoint is no longer accepted are dropped.
+    pub fn start(&self) {
+        let followers = self.inner.store.all();
+        info!(count = followers.len(), "resuming push subscriptions");
+        for follower in followers {
+            if let Err(e) = follower.subscription.validate() {
+                warn!(id = %follower.id, error = %e, "dropping push subscription");
+                self.inner.store.remove(&follower.id);
+                continue;
+            }
+            self.spawn(follower, None);
+        }
+    }
+
+    /// Write pending subscription changes to disk.
+    pub async fn flush(&self) {
+        self.inner.store.flush().await;
+    }
+
+    /// The VAPID public key browsers subscribe with.
+    pub fn public_key(&self) -> String {
+        self.inner.vapid.public_key()
+    }
+
+    /// Notify `subscription` about posts added to a thread from now on.
+    /// `client` is the subscriber's address, for the per-client limit.
+    pub async fn subscribe(
+        &self,
+        handle: &str,
+        post_id: &str,
+        subscription: PushSubscription,
+        client: Option<IpAddr>,
+    ) -> Result<(), PushError> {
+        subscription.validate()?;
+
+        let id = follower_id(handle, post_id, &subscription.endpoint);
+        if self.inner.tasks.lock().unwrap().contains_key(&id) {
+            return Ok(());
+        }
+        // Checked again on insert; this avoids starting a watcher for nothing
+        self.inner
+            .store
+            .read(|followers| check_limits(followers, handle, post_id, client))?;
+
+        let rx = self.inner.watchers.subscribe(handle, post_id).await?;
+        let snapshot = rx.borrow().clone();
+        if snapshot.stale {
+            return Err(PushError::Stale);
+        }
+
+        let follower = Follower {
+            id,
+            handle: handle.to_string(),
+            post_id: post_id.to_string(),
+            subscription,
+            created_at: Utc::now(),
+            cursor: FollowCursor::at_end(&snapshot.thread.posts),
+            client,
+        };
+        self.inner.store.try_insert(follower.clone(), |others| {
+            check_limits(others, handle, post_id, client)
+        })?;
+        info!(id = %follower.id, handle = %handle, post_id = %post_id, "push subscribed");
+        self.spawn(follower, Some(rx));
+        Ok(())
+    }
+
+    /// Stop notifying an endpoint about a thread. Returns whether it was subscribed.
+    pub fn unsubscribe(&self, handle: &str, post_id: &str, endpoint: &str) -> bool {
+        let id = follower_id(handle, post_id, endpoint);
+        if let Some(task) = self.inner.tasks.lock().unwrap().remove(&id) {
+            task.abort();
+        }
+        self.inner.store.remove(&id).is_some()
+    }
+
+    fn spawn(&self, follower: Follower, rx: Option<watch::Receiver<Snapshot>>) {
+        // Hold the lock while spawning so a task that ends at once cannot
+        // remove its entry before it is inserted
+        let mut tasks = self.inner.tasks.lock().unwrap();
+        let id = follower.id.clone();
+        let task = tokio::spawn(self.clone().deliver(follower, rx));
+        tasks.insert(id, task.abort_handle());
+    }
+
+    /// Follow the thread and push its new posts until it goes stale, it
+    /// disappears or the push service drops the subscription.
+    async fn deliver(self, mut follower: Follower, rx: Option<watch::Receiver<Snapshot>>) {
+        let rx = match rx {
+            Some(rx) => Some(rx),
+            None => {
+                self.inner
+                    .watchers
+                    .subscribe_with_retry(&follower.handle, &follower.post_id)
+                    .await
+            }
+        };
+        if let Some(rx) = rx {
+            self.forward(&mut follower, rx).await;
+        }
+
+        info!(id = %follower.id, "push subscription ended");
+        self.inner.tasks.lock().unwrap().remove(&follower.id);
+        self.inner.store.remove(&follower.id);
+    }
+
+    async fn forward(&self, follower: &mut Follower, rx: watch::Receiver<Snapshot>) {
+        let author = rx.borrow().thread.author.clone();
+        let thread_url = format!(
+            "{}/profile/{}/post/{}",
+            self.inner.public_url, follower.handle, follower.post_id
+        );
+        let tag = format!("thread:{}/{}", follower.handle, follower.post_id);
+
+        let batches = new_post_batches(rx, follower.cursor.clone());
+        futures::pin_mut!(batches);
+
+        while let Some(batch) = batches.next().await {
+            if !batch.posts.is_empty() {
+                let payload = notification_json(&author, &batch.posts, &thread_url, &tag);
+                match self.send(&follower.subscription, &payload).await {
+                    Ok(()) => {
+                        debug!(id = %follower.id, count = batch.posts.len(), "push delivered")
+                    }
+                    Err(PushError::Gone) => return,
+                    Err(e) => warn!(id = %follower.id, error = %e, "dropping push message"),
+                }
+
+                follower.cursor.advance(&batch.posts);
+                self.inner.store.replace(follower.clone());
+            }
+
+            if batch.stale {
+                return;
+            }
+        }
+    }
+
+    /// Push a message, retrying transient failures with exponential backoff.
+    async fn send(
+        &self,
+        subscription: &PushSubscription,
+        payload: &serde_json::Value,
+    ) -> Result<(), PushError> {
+        let payload = serde_json::to_vec(payload)?;
+        let mut delay = INITIAL_RETRY_DELAY;
+
+        for attempt in 1.. {
+            let result = send_notification(
+                &self.inner.http,
+                &self.inner.vapid,
+                &self.inner.subject,
+                subscription,
+                &payload,
+            )
+            .await;
+            match result {
+                Err(e) if e.is_retryable() && attempt < MAX_ATTEMPTS => {
+                    debug!(attempt, error = %e, "retrying push delivery");
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+                result => return result,
+            }
+        }
+        unreachable!("the final attempt returns")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Bytes;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::post;
+    use p256::ecdsa::signature::Verifier;
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+
+    fn b64(value: &str) -> Vec<u8> {
+        decode_base64(value).unwrap()
+    }
+
+    /// What a browser does with a received message.
+    fn decrypt(body: &[u8], receiver: &SecretKey, auth: &[u8]) -> Vec<u8> {
+        let (salt, rest) = body.split_at(16);
+        let id_len = rest[4] as usize;
+        let (sender_public, ciphertext) = rest[5..].split_at(id_len);
+
+        let sender_key = PublicKey::from_sec1_bytes(sender_public).unwrap();
+        let shared =
+            p256::ecdh::diffie_hellman(receiver.to_nonzero_scalar(), sender_key.as_affine());
+        let receiver_public = receiver.public_key().to_encoded_point(false);
+        let (cek, nonce) = derive_keys(
+            shared.raw_secret_bytes(),
+            auth,
+            receiver_public.as_bytes(),
+            sender_public,
+            salt,
+        )
+        .unwrap();
+
+        let mut plaintext = Aes128Gcm::new_from_slice(&cek)
+            .unwrap()
+            .decrypt(Nonce::from_slice(&nonce), ciphertext)
+            .unwrap();
+        assert_eq!(plaintext.pop(), Some(2));
+        plaintext
+    }
+
+    #[test]
+    fn test_encrypt_matches_rfc8291_example() {
+        // RFC 8291 appendix A
+        let receiver = ReceiverKeys {
+            public: b64("BCVxsr7N_eNgVRqvHtD0zTZsEc6-VV-JvLexhqUzORcxaOzi6-AYWXvTBHm4bjyPjs7Vd8pZGH6SRpkNtoIAiw4"),
+            auth: b64("BTBZMqHH6r4Tts7J_aSIgg"),
+        };
+        let sender =
+            SecretKey::from_slice(&b64("yfWPiYE-n46HLnH0KqZOF1fJJU3MYrct3AELtAQ-oRw")).unwrap();
+        let salt: [u8; 16] = b64("DGv6ra1nlYgDCS1FRnbzlw").try_into().unwrap();
+
+        let body = encrypt_with(
+            b"When I grow up, I want to be a watermelon",
+            &receiver,
+            &sender,
+            salt,
+        )
+        .unwrap();
+        assert_eq!(
+            URL_SAFE_NO_PAD.encode(body),
+            "DGv6ra1nlYgDCS1FRnbzlwAAEABBBP4z9KsN6nGRTbVYI_c7VJSPQTBtkgcy27mlmlMoZIIgDll6e3vCYLocInmYWAmS6TlzAC8wEqKK6PBru3jl7A_yl95bQpu6cVPTpK4Mqgkf1CXztLVBSt2Ks3oZwbuwXPXLWyouBWLVWGNWQexSgSxsj_Qulcy4a-fN"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_notification_to_local_push_service() {
+        // A stand-in push service that records what it receives
+        let (tx, mut rx) = mpsc::channel::<(HeaderMap, Bytes)>(1);
+        let app = axum::Router::new()
+            .route(
+                "/push/{id}",
+                post(|headers: HeaderMap, body: Bytes| async move {
+                    tx.send((headers, body)).await.unwrap();
+                    StatusCode::CREATED
+                }),
+            )
+            .route("/gone", post(|| async { StatusCode::GONE }));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let browser_key = SecretKey::random(&mut OsRng);
+        let auth = [7u8; 16];
+        let mut subscription = PushSubscription {
+            endpoint: format!("http://{}/push/abc", addr),
+            keys: PushKeys {
+                p256dh: URL_SAFE_NO_PAD
+                    .encode(browser_key.public_key().to_encoded_point(false).as_bytes()),
+                auth: URL_SAFE_NO_PAD.encode(auth),
+            },
+        };
+        let vapid = VapidKey::from_base64(
+            &URL_SAFE_NO_PAD.encode(SecretKey::random(&mut OsRng).to_bytes()),
+        )
+        .unwrap();
+        let http = reqwest::Client::new();
+
+        send_notification(
+            &http,
+            &vapid,
+            "mailto:ops@example.com",
+            &subscription,
+            b"{\"title\":\"hi\"}",
+        )
+        .await
+        .unwrap();
+        let (headers, body) = rx.recv().await.unwrap();
+
+        assert_eq!(headers["content-encoding"], "aes128gcm");
+        assert_eq!(decrypt(&body, &browser_key, &auth), b"{\"title\":\"hi\"}");
+
+        // The VAPID token is a valid ES256 JWT for the push service's origin
+        let authorization = headers["authorization"].to_str().unwrap();
+        let (token, key) = authorization
+            .strip_prefix("vapid t=")
+            .and_then(|rest| rest.split_once(", k="))
+            .unwrap();
+        assert_eq!(key, vapid.public_key());
+        let (signing_input, signature) = token.rsplit_once('.').unwrap();
+        let signature = Signature::from_slice(&b64(signature)).unwrap();
+        vapid
+            .signing
+            .verifying_key()
+            .verify(signing_input.as_bytes(), &signature)
+            .unwrap();
+        let claims: serde_json::Value =
+            serde_json::from_slice(&b64(signing_input.split('.').nth(1).unwrap())).unwrap();
+        assert_eq!(claims["aud"], format!("http://{}", addr));
+
+        subscription.endpoint = format!("http://{}/gone", addr);
+        let result = send_notification(
+            &http,
+            &vapid,
+            "mailto:ops@example.com",
+            &subscription,
+            b"{}",
+        )
+        .await;
+        assert!(matches!(result, Err(PushError::Gone)));
+    }
+
+    #[test]
+    fn test_subscription_validation() {
+        let key = SecretKey::random(&mut OsRng);
+        let mut subscription = PushSubscription {
+            endpoint: "https://fcm.googleapis.com/fcm/send/abc".to_string(),
+            keys: PushKeys {
+                p256dh: URL_SAFE_NO_PAD.encode(key.public_key().to_encoded_point(false).as_bytes()),
+                auth: URL_SAFE_NO_PAD.encode([1u8; 16]),
+            },
+        };
+        assert!(subscription.validate().is_ok());
+
+        subscription.keys.auth = URL_SAFE_NO_PAD.encode([1u8; 8]);
+        assert!(subscription.validate().is_err());
+
+        subscription.keys.auth = URL_SAFE_NO_PAD.encode([1u8; 16]);
+        for endpoint in [
+            "http://fcm.googleapis.com/fcm/send/abc",
+            "https://push.example.com/send/abc",
+            "https://evilfcm.googleapis.com.example.com/send/abc",
+            "https://fcm.googleapis.com:8443/fcm/send/abc",
+            "https://127.0.0.1/send/abc",
+        ] {
+            subscription.endpoint = endpoint.to_string();
+            assert!(subscription.validate().is_err(), "{}", endpoint);
+        }
+        subscription.endpoint = "https://wns2-par02p.notify.windows.com/w/?token=abc".to_string();
+        assert!(subscription.validate().is_ok());
+    }
+
+    #[test]
+    fn test_subscription_limits() {
+        let client: IpAddr = "192.0.2.1".parse().unwrap();
+        let follower = |handle: &str, post_id: &str, client| Follower {
+            id: follower_id(handle, post_id, "https://fcm.googleapis.com/fcm/send/x"),
+            handle: handle.to_string(),
+            post_id: post_id.to_string(),
+            subscription: PushSubscription {
+                endpoint: "https://fcm.googleapis.com/fcm/send/x".to_string(),
+                keys: PushKeys {
+                    p256dh: String::new(),
+                    auth: String::new(),
+                },
+            },
+            created_at: Utc::now(),
+            cursor: FollowCursor::at_end(&[]),
+            client,
+        };
+
+        let from_client: Vec<Follower> = (0..MAX_SUBSCRIPTIONS_PER_CLIENT)
+            .map(|i| follower("alice.test", &i.to_string(), Some(client)))
+            .collect();
+        assert!(matches!(
+            check_limits(from_client.iter(), "bob.test", "1", Some(client)),
+            Err(PushError::Limit(_))
+        ));
+        assert!(check_limits(from_client.iter(), "bob.test", "1", None).is_ok());
+
+        let on_thread: Vec<Follower> = (0..MAX_SUBSCRIPTIONS_PER_THREAD)
+            .map(|_| follower("Alice.test", "1", None))
+            .collect();
+        assert!(matches!(
+            check_limits(on_thread.iter(), "alice.test", "1", None),
+            Err(PushError::Limit("for this thread"))
+        ));
+        assert!(check_limits(on_thread.iter(), "alice.test", "2", None).is_ok());
+
+        // Without an address, subscriptions are capped per handle instead
+        let for_handle: Vec<Follower> = (0..MAX_SUBSCRIPTIONS_PER_HANDLE)
+            .map(|i| follower("alice.test", &i.to_string(), None))
+            .collect();
+        assert!(matches!(
+            check_limits(for_handle.iter(), "alice.test", "x", None),
+            Err(PushError::Limit("for this author"))
+        ));
+        assert!(check_limits(for_handle.iter(), "alice.test", "x", Some(client)).is_ok());
+        assert!(check_limits(for_handle.iter(), "bob.test", "1", None).is_ok());
+    }
+}
@@ -32,11 +32,37 @@ pub const MANIFEST_JSON: &str = r##"{
   }
 }"##;
 
-/// Minimal service worker for PWA installability.
-/// This is a passthrough implementation - no offline caching.
-/// The app requires network access to fetch Bluesky content anyway.
+/// Minimal service worker for PWA installability and push notifications.
+/// Fetches are passed through - no offline caching, since the app requires
+/// network access to fetch Bluesky content anyway. Push messages carry a
+/// ready-made notification (`title`, `body`, `url`, `tag`) for followed threads.
 pub const SERVICE_WORKER_JS: &str = r#"self.addEventListener('fetch', function(event) {
   event.respondWith(fetch(event.request));
+});
+
+self.addEventListener('push', function(event) {
+  var data = {};
+  try {
+    data = event.data ? event.data.json() : {};
+  } catch (e) {}
+  event.waitUntil(self.registration.showNotification(data.title || 'Sklonger', {
+    body: data.body || '',
+    icon: '/icon.svg',
+    tag: data.tag,
+    renotify: !!data.tag,
+    data: { url: data.url || '/' }
+  }));
+});
+
+self.addEventListener('notificationclick', function(event) {
+  event.notification.close();
+  var url = event.notification.data && event.notification.data.url;
+  event.waitUntil(self.clients.matchAll({ type: 'window' }).then(function(windows) {
+    for (var i = 0; i < windows.length; i++) {
+      if (windows[i].url === url && 'focus' in windows[i]) return windows[i].focus();
+    }
+    return self.clients.openWindow(url || '/');
+  }));
 });"#;
 
 /// Simple snake SVG icon for the PWA.
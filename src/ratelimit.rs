@@ -0,0 +1,325 @@
+//! Per-client inbound rate limiting.
+//!
+//! Each client IP gets a token bucket per budget: page views, the JSON/SSE
+//! API that live pages poll, and link-preview crawlers (which are identified
+//! by User-Agent and share one bucket per IP like everyone else). A bucket
+//! holds up to a minute's allowance and refills continuously, so short bursts
+//! pass while sustained hammering gets a 429 with `Retry-After`.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header::USER_AGENT, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::config::{Config, SharedConfig};
+use crate::error::AppError;
+use crate::handlers::is_social_crawler;
+
+/// Above this many tracked buckets, full (idle) ones are dropped
+const PRUNE_THRESHOLD: usize = 10_000;
+/// Minimum time between prunes, so a flood from many addresses does not make
+/// every request scan every bucket
+const PRUNE_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Budget {
+    Pages,
+    Polling,
+    Crawlers,
+}
+
+impl Budget {
+    /// Which budget a request draws from, or `None` for routes that are never
+    /// limited (health checks, PWA assets, metrics).
+    fn for_request(path: &str, headers: &HeaderMap, crawlers: &[String]) -> Option<Self> {
+        const EXEMPT: &[&str] = &[
+            "/health/",
+            "/metrics",
+            "/manifest.json",
+            "/sw.js",
+            "/icon.svg",
+        ];
+        if EXEMPT.iter().any(|prefix| path.starts_with(prefix)) {
+            return None;
+        }
+
+        let user_agent = headers
+            .get(USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("");
+        Some(if is_social_crawler(user_agent, crawlers) {
+            Budget::Crawlers
+        } else if path.starts_with("/api/") {
+            Budget::Polling
+        } else {
+            Budget::Pages
+        })
+    }
+}
+
+/// Allowed requests per minute; also the bucket size.
+#[derive(Debug, Clone, Copy)]
+struct Rate {
+    per_minute: u32,
+}
+
+impl Rate {
+    fn capacity(&self) -> f64 {
+        self.per_minute as f64
+    }
+
+    fn per_second(&self) -> f64 {
+        self.per_minute as f64 / 60.0
+    }
+}
+
+fn rate_for(config: &Config, budget: Budget) -> Rate {
+    let per_minute = match budget {
+        Budget::Pages => config.rate_limit_pages_per_minute,
+        Budget::Polling => config.rate_limit_polling_per_minute,
+        Budget::Crawlers => config.rate_limit_crawlers_per_minute,
+    };
+    Rate { per_minute }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+#[derive(Default)]
+struct Buckets {
+    map: HashMap<(IpAddr, Budget), Bucket>,
+    last_pruned: Option<Instant>,
+}
+
+impl Buckets {
+    /// Drop buckets that have refilled, as a new one would start full anyway.
+    fn prune(&mut self, rate: impl Fn(Budget) -> Rate, now: Instant) {
+        self.last_pruned = Some(now);
+        self.map.retain(|(_, budget), bucket| {
+            let rate = rate(*budget);
+            let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+            bucket.tokens + elapsed * rate.per_second() < rate.capacity()
+        });
+    }
+}
+
+/// Rates and the trusted proxy count are read from the config on every
+/// request, so a reload applies to buckets already being tracked.
+#[derive(Clone)]
+pub struct RateLimiter {
+    config: SharedConfig,
+    buckets: Arc<Mutex<Buckets>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: SharedConfig) -> Self {
+        Self {
+            config,
+            buckets: Arc::new(Mutex::new(Buckets::default())),
+        }
+    }
+
+    fn rate(&self, budget: Budget) -> Rate {
+        rate_for(&self.config.load(), budget)
+    }
+
+    /// Take a token for `ip` from `budget`. When the bucket is empty, returns
+    /// how long until the next token arrives.
+    pub fn check(&self, ip: IpAddr, budget: Budget, now: Instant) -> Result<(), Duration> {
+        let rate = self.rate(budget);
+        if rate.per_minute == 0 {
+            return Ok(());
+        }
+
+        let mut buckets = self.buckets.lock().unwrap();
+        let prune_due = buckets
+            .last_pruned
+            .is_none_or(|at| now.saturating_duration_since(at) >= PRUNE_INTERVAL);
+        if buckets.map.len() > PRUNE_THRESHOLD && prune_due {
+            let config = self.config.load();
+            buckets.prune(|budget| rate_for(&config, budget), now);
+        }
+
+        let bucket = buckets.map.entry((ip, budget)).or_insert(Bucket {
+            tokens: rate.capacity(),
+            updated: now,
+        });
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate.per_second()).min(rate.capacity());
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / rate.per_second(),
+            ))
+        }
+    }
+
+    fn client_ip(&self, headers: &HeaderMap, peer: Option<IpAddr>) -> Option<IpAddr> {
+        client_ip(headers, peer, self.config.load().rate_limit_trusted_proxies)
+    }
+}
+
+/// The client's address: the entry the outermost of `trusted_proxies` added to
+/// `X-Forwarded-For`, or the peer address when no proxies are trusted or the
+/// header is missing or too short.
+pub fn client_ip(
+    headers: &HeaderMap,
+    peer: Option<IpAddr>,
+    trusted_proxies: usize,
+) -> Option<IpAddr> {
+    if trusted_proxies == 0 {
+        return peer;
+    }
+    let forwarded: Vec<&str> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .collect();
+    forwarded
+        .len()
+        .checked_sub(trusted_proxies)
+        .and_then(|i| forwarded[i].parse().ok())
+        .or(peer)
+}
+
+/// Middleware rejecting clients that have spent their budget, while
+/// `RATE_LIMIT_ENABLED` is on.
+pub async fn limit(
+    State(limiter): State<RateLimiter>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    if !limiter.config.load().rate_limit_enabled {
+        return Ok(next.run(request).await);
+    }
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|info| info.0.ip());
+    let budget = Budget::for_request(
+        request.uri().path(),
+        request.headers(),
+        &limiter.config.load().crawler_user_agents,
+    );
+
+    if let (Some(budget), Some(ip)) = (budget, limiter.client_ip(request.headers(), peer)) {
+        if let Err(wait) = limiter.check(ip, budget, Instant::now()) {
+            tracing::debug!(ip = %ip, budget = ?budget, "rate limited");
+            return Err(AppError::RateLimited {
+                retry_after: Some(wait.as_secs_f64().ceil() as u64),
+            });
+        }
+    }
+    Ok(next.run(request).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use arc_swap::ArcSwap;
+
+    fn limiter(trusted_proxies: usize) -> RateLimiter {
+        let mut config = Config::from_env().unwrap();
+        config.rate_limit_pages_per_minute = 2;
+        config.rate_limit_polling_per_minute = 60;
+        config.rate_limit_crawlers_per_minute = 0;
+        config.rate_limit_trusted_proxies = trusted_proxies;
+        RateLimiter::new(Arc::new(ArcSwap::from_pointee(config)))
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let limiter = limiter(0);
+        let ip: IpAddr = "192.0.2.1".parse().unwrap();
+        let other: IpAddr = "192.0.2.2".parse().unwrap();
+        let start = Instant::now();
+
+        assert!(limiter.check(ip, Budget::Pages, start).is_ok());
+        assert!(limiter.check(ip, Budget::Pages, start).is_ok());
+        let wait = limiter.check(ip, Budget::Pages, start).unwrap_err();
+        assert_eq!(wait.as_secs(), 30);
+
+        // Other clients and budgets are unaffected; a rate of 0 is unlimited
+        assert!(limiter.check(other, Budget::Pages, start).is_ok());
+        assert!(limiter.check(ip, Budget::Polling, start).is_ok());
+        assert!(limiter.check(ip, Budget::Crawlers, start).is_ok());
+
+        // A token comes back after 30 seconds
+        assert!(limiter
+            .check(ip, Budget::Pages, start + Duration::from_secs(30))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_pruning_is_spaced_out() {
+        let limiter = limiter(0);
+        let start = Instant::now();
+        let fill = |at: Instant| {
+            let mut buckets = limiter.buckets.lock().unwrap();
+            for i in 0..=PRUNE_THRESHOLD as u32 {
+                let bucket = Bucket {
+                    tokens: 2.0,
+                    updated: at,
+                };
+                buckets
+                    .map
+                    .insert((IpAddr::from(i.to_be_bytes()), Budget::Pages), bucket);
+            }
+        };
+        let tracked = || limiter.buckets.lock().unwrap().map.len();
+        let ip: IpAddr = "2001:db8::1".parse().unwrap();
+
+        // Full buckets are dropped once there are too many
+        fill(start);
+        assert!(limiter.check(ip, Budget::Polling, start).is_ok());
+        assert_eq!(tracked(), 1);
+
+        // But not again until the interval has passed
+        fill(start);
+        let soon = start + PRUNE_INTERVAL / 2;
+        assert!(limiter.check(ip, Budget::Polling, soon).is_ok());
+        assert!(tracked() > PRUNE_THRESHOLD);
+        assert!(limiter
+            .check(ip, Budget::Polling, start + PRUNE_INTERVAL)
+            .is_ok());
+        assert_eq!(tracked(), 1);
+    }
+
+    #[test]
+    fn test_client_ip() {
+        let peer: IpAddr = "10.0.0.9".parse().unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-forwarded-for",
+            "203.0.113.7, 198.51.100.1, 10.0.0.2".parse().unwrap(),
+        );
+
+        assert_eq!(limiter(0).client_ip(&headers, Some(peer)), Some(peer));
+        assert_eq!(
+            limiter(1).client_ip(&headers, Some(peer)),
+            Some("10.0.0.2".parse().unwrap())
+        );
+        assert_eq!(
+            limiter(3).client_ip(&headers, Some(peer)),
+            Some("203.0.113.7".parse().unwrap())
+        );
+        assert_eq!(limiter(4).client_ip(&headers, Some(peer)), Some(peer));
+    }
+}
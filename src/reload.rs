@@ -0,0 +1,206 @@
+//! Runtime configuration reload on SIGHUP.
+//!
+//! The running config lives behind a [`SharedConfig`] handle, and the code
+//! that honours reloads (thread watchers, the rate limiter, page handlers)
+//! reads it on each use rather than copying values at startup. A reload
+//! re-reads the config file and environment, validates the result and swaps
+//! it in. Settings that are only read at startup keep their running values,
+//! and each attempted change to one is logged as a warning.
+
+use std::fmt;
+use std::sync::Arc;
+
+use tracing::{error, info, warn};
+
+use crate::config::{Config, ConfigError, SharedConfig, REDACTED, SECRET_KEYS};
+
+/// Config file keys that take effect without a restart
+const RELOADABLE: &[&str] = &[
+    "poll_enabled",
+    "poll_initial_interval_seconds",
+    "poll_max_interval_seconds",
+    "poll_disable_after_seconds",
+    "crawler_user_agents",
+    "rate_limit_enabled",
+    "rate_limit_trusted_proxies",
+    "rate_limit_pages_per_minute",
+    "rate_limit_polling_per_minute",
+    "rate_limit_crawlers_per_minute",
+];
+
+/// One setting that differs between two configs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub key: String,
+    pub old: String,
+    pub new: String,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} -> {}", self.key, self.old, self.new)
+    }
+}
+
+/// The result of a successful reload.
+#[derive(Debug, Default)]
+pub struct Reload {
+    /// Changes now in effect
+    pub applied: Vec<Change>,
+    /// Changes to startup-only settings, which kept their running values
+    pub rejected: Vec<Change>,
+}
+
+/// Settings that differ between `old` and `new`, with secret values hidden.
+fn diff(old: &toml::Table, new: &toml::Table) -> Vec<Change> {
+    new.iter()
+        .filter(|(key, value)| old.get(*key) != Some(*value))
+        .map(|(key, value)| {
+            let show = |v: Option<&toml::Value>| match v {
+                _ if SECRET_KEYS.contains(&key.as_str()) => REDACTED.to_string(),
+                Some(v) => v.to_string(),
+                None => "(unset)".to_string(),
+            };
+            Change {
+                key: key.clone(),
+                old: show(old.get(key)),
+                new: show(Some(value)),
+            }
+        })
+        .collect()
+}
+
+/// Validate `new` and swap it in, keeping the running value of every
+/// startup-only setting. On error the running config is left untouched.
+pub fn apply(shared: &SharedConfig, new: Config) -> Result<Reload, ConfigError> {
+    new.validate()?;
+
+    let old = shared.load().to_table();
+    let mut merged = new.to_table();
+    let (applied, rejected): (Vec<Change>, Vec<Change>) = diff(&old, &merged)
+        .into_iter()
+        .partition(|change| RELOADABLE.contains(&change.key.as_str()));
+    for change in &rejected {
+        merged.insert(change.key.clone(), old[&change.key].clone());
+    }
+
+    let merged: Config = merged
+        .try_into()
+        .expect("a table from a config deserializes back");
+    merged.validate()?;
+    shared.store(Arc::new(merged));
+    Ok(Reload { applied, rejected })
+}
+
+/// Reload the config each time the process receives SIGHUP. `load` reads
+/// the config sources the same way startup did.
+#[cfg(unix)]
+pub async fn on_sighup<F>(shared: SharedConfig, load: F)
+where
+    F: Fn() -> Result<Config, ConfigError>,
+{
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(e) => {
+            warn!(error = %e, "failed to install SIGHUP handler; config reload disabled");
+            return;
+        }
+    };
+
+    while hangup.recv().await.is_some() {
+        match load().and_then(|config| apply(&shared, config)) {
+            Ok(reload) => {
+                for change in &reload.rejected {
+                    warn!(
+                        key = %change.key,
+                        change = %change,
+                        "setting cannot change at runtime; restart to apply it"
+                    );
+                }
+                if reload.applied.is_empty() {
+                    info!("configuration reloaded; nothing changed");
+                } else {
+                    let changes: Vec<String> =
+                        reload.applied.iter().map(ToString::to_string).collect();
+                    info!(changes = %changes.join(", "), "configuration reloaded");
+                }
+            }
+            Err(e) => error!(error = %e, "config reload failed; keeping the running settings"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arc_swap::ArcSwap;
+
+    #[test]
+    fn test_apply_keeps_startup_only_settings() {
+        let shared: SharedConfig = Arc::new(ArcSwap::from_pointee(Config::default()));
+        let new = Config {
+            port: 9999,
+            poll_max_interval: 300,
+            webhook_secret: "hunter2".to_string(),
+            ..Config::default()
+        };
+
+        let reload = apply(&shared, new).unwrap();
+        assert_eq!(
+            reload.applied,
+            vec![Change {
+                key: "poll_max_interval_seconds".to_string(),
+                old: "120".to_string(),
+                new: "300".to_string(),
+            }]
+        );
+        let rejected: Vec<String> = reload.rejected.iter().map(ToString::to_string).collect();
+        assert!(rejected.contains(&"port: 8080 -> 9999".to_string()));
+        assert!(rejected.contains(&"webhook_secret: <redacted> -> <redacted>".to_string()));
+
+        let running = shared.load();
+        assert_eq!(running.poll_max_interval, 300);
+        assert_eq!(running.port, 8080);
+        assert_eq!(running.webhook_secret, "");
+    }
+
+    #[test]
+    fn test_reload_crawler_patterns() {
+        use crate::handlers::is_social_crawler;
+
+        let shared: SharedConfig = Arc::new(ArcSwap::from_pointee(Config::default()));
+        let new = Config {
+            crawler_user_agents: vec!["Mastodon".to_string()],
+            ..Config::default()
+        };
+        let agent = "Mastodon/4.2 (http.rb/5.1; +https://example.social/)";
+        assert!(!is_social_crawler(
+            agent,
+            &shared.load().crawler_user_agents
+        ));
+
+        let reload = apply(&shared, new).unwrap();
+        assert_eq!(reload.applied.len(), 1);
+        assert_eq!(reload.applied[0].key, "crawler_user_agents");
+        assert!(is_social_crawler(agent, &shared.load().crawler_user_agents));
+        assert!(!is_social_crawler(
+            "Twitterbot/1.0",
+            &shared.load().crawler_user_agents
+        ));
+    }
+
+    #[test]
+    fn test_invalid_reload_is_not_applied() {
+        let shared: SharedConfig = Arc::new(ArcSwap::from_pointee(Config::default()));
+        let new = Config {
+            poll_initial_interval: 600,
+            ..Config::default()
+        };
+
+        let err = apply(&shared, new).unwrap_err();
+        assert!(err.to_string().contains("POLL_INITIAL_INTERVAL_SECONDS"));
+        assert_eq!(shared.load().poll_initial_interval, 30);
+    }
+}
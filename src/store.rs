@@ -0,0 +1,221 @@
+//! Small JSON-file-backed collections for state that must survive restarts
+//! (webhook and push subscriptions).
+//!
+//! Changes apply in memory at once. The whole collection is then rewritten by
+//! a background writer, at most once per `WRITE_DELAY` and off the async
+//! runtime, so a burst of changes costs one write. The files hold at most a
+//! few thousand entries, so this stays simple and the file is always valid.
+
+use std::collections::btree_map::Values;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, Once};
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use thiserror::Error;
+use tokio::sync::Notify;
+use tracing::warn;
+
+/// How long changes are collected before the file is rewritten
+const WRITE_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Error, Debug)]
+pub enum StoreError {
+    #[error("failed to access store: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid store contents: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// Items stored in a [`JsonStore`] are identified by a string key.
+pub trait Keyed {
+    fn key(&self) -> &str;
+}
+
+/// A keyed collection persisted as a JSON array.
+pub struct JsonStore<T> {
+    inner: Arc<Inner<T>>,
+}
+
+struct Inner<T> {
+    path: PathBuf,
+    items: Mutex<BTreeMap<String, T>>,
+    /// Signalled on every change; the writer rewrites the file after it
+    changed: Notify,
+    writer: Once,
+    /// Held while writing, so writes land in order
+    writing: tokio::sync::Mutex<()>,
+}
+
+impl<T> JsonStore<T>
+where
+    T: Keyed + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    /// Load the collection, starting empty if the file does not exist yet.
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self, StoreError> {
+        let path = path.into();
+        let items = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice::<Vec<T>>(&bytes)?
+                .into_iter()
+                .map(|item| (item.key().to_string(), item))
+                .collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            inner: Arc::new(Inner {
+                path,
+                items: Mutex::new(items),
+                changed: Notify::new(),
+                writer: Once::new(),
+                writing: tokio::sync::Mutex::new(()),
+            }),
+        })
+    }
+
+    pub fn all(&self) -> Vec<T> {
+        self.inner.items.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Inspect the items without cloning them.
+    pub fn read<R>(&self, f: impl FnOnce(Values<'_, String, T>) -> R) -> R {
+        f(self.inner.items.lock().unwrap().values())
+    }
+
+    pub fn insert(&self, item: T) {
+        self.update(|items| items.insert(item.key().to_string(), item));
+    }
+
+    /// Insert an item unless `check`, given the other items, rejects it. The
+    /// check and the insert happen under one lock.
+    pub fn try_insert<E>(
+        &self,
+        item: T,
+        check: impl FnOnce(&mut dyn Iterator<Item = &T>) -> Result<(), E>,
+    ) -> Result<(), E> {
+        self.update(|items| {
+            let key = item.key().to_string();
+            check(&mut items.values().filter(|other| other.key() != key))?;
+            items.insert(key, item);
+            Ok(())
+        })
+    }
+
+    /// Replace an item only if it is still present.
+    pub fn replace(&self, item: T) {
+        self.update(|items| {
+            if let Some(stored) = items.get_mut(item.key()) {
+                *stored = item;
+            }
+        });
+    }
+
+    pub fn remove(&self, key: &str) -> Option<T> {
+        self.update(|items| items.remove(key))
+    }
+
+    /// Write pending changes now, e.g. before shutting down.
+    pub async fn flush(&self) {
+        self.inner.write().await;
+    }
+
+    /// Apply a change and schedule a write.
+    fn update<R>(&self, f: impl FnOnce(&mut BTreeMap<String, T>) -> R) -> R {
+        let result = f(&mut self.inner.items.lock().unwrap());
+        self.inner.writer.call_once(|| {
+            tokio::spawn(self.inner.clone().write_behind());
+        });
+        self.inner.changed.notify_one();
+        result
+    }
+}
+
+impl<T> Inner<T>
+where
+    T: Serialize + Send + Sync + 'static,
+{
+    async fn write_behind(self: Arc<Self>) {
+        loop {
+            self.changed.notified().await;
+            tokio::time::sleep(WRITE_DELAY).await;
+            self.write().await;
+        }
+    }
+
+    async fn write(&self) {
+        let _writing = self.writing.lock().await;
+        if let Err(e) = self.save().await {
+            warn!(error = %e, path = %self.path.display(), "failed to save store");
+        }
+    }
+
+    async fn save(&self) -> Result<(), StoreError> {
+        let bytes = {
+            let items = self.items.lock().unwrap();
+            let list: Vec<&T> = items.values().collect();
+            serde_json::to_vec_pretty(&list)?
+        };
+        let path = self.path.clone();
+        tokio::task::spawn_blocking(move || {
+            let tmp = path.with_extension("tmp");
+            std::fs::write(&tmp, bytes)?;
+            std::fs::rename(&tmp, &path)
+        })
+        .await
+        .expect("store write does not panic")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Item {
+        id: String,
+        value: u32,
+    }
+
+    impl Keyed for Item {
+        fn key(&self) -> &str {
+            &self.id
+        }
+    }
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let path = std::env::temp_dir().join(format!(
+            "sklonger-store-{}-{:?}.json",
+            std::process::id(),
+            std::time::SystemTime::now()
+        ));
+        let item = |id: &str, value| Item {
+            id: id.to_string(),
+            value,
+        };
+
+        let store = JsonStore::<Item>::load(&path).unwrap();
+        assert!(store.all().is_empty());
+        store.insert(item("a", 1));
+        store.insert(item("b", 2));
+        store.replace(item("a", 3));
+        store.replace(item("missing", 4));
+        assert_eq!(store.remove("b"), Some(item("b", 2)));
+        assert_eq!(
+            store.try_insert(item("c", 5), |others| match others.count() {
+                0 => Ok(()),
+                n => Err(n),
+            }),
+            Err(1)
+        );
+        store.flush().await;
+
+        let reloaded = JsonStore::<Item>::load(&path).unwrap();
+        assert_eq!(reloaded.all(), vec![item("a", 3)]);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,483 @@
+//! Shared server-side thread watchers.
+//!
+//! Every reader of a live thread used to check upstream on its own. The
+//! registry keeps one background task per actively watched thread instead:
+//! it re-fetches the thread on the polling backoff schedule and publishes
+//! each snapshot through a `watch` channel, so SSE subscribers and pollers
+//! all read the same copy. Refreshes re-check the known posts in batches and
+//! follow the thread forward from the last one rather than re-walking it from
+//! the root, so deleted and changed posts show up in the next snapshot.
+//!
+//! The first snapshot is fetched like a page: held to the page budget and
+//! served from the page cache. A thread that is stale, or too long to load
+//! within the budget, gets no watcher; its snapshot is kept for a while so
+//! repeated requests for it do not walk it again.
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use tokio::sync::{watch, Notify};
+use tracing::{debug, info, warn};
+
+use crate::bluesky::client::ClientError;
+use crate::bluesky::types::Thread;
+use crate::bluesky::BlueskyClient;
+use crate::cache::Cache;
+use crate::config::SharedConfig;
+use crate::jetstream::PostEvent;
+use crate::metrics::metrics;
+
+/// Longest wait between attempts in [`WatcherRegistry::subscribe_with_retry`]
+const MAX_RESUBSCRIBE_DELAY: Duration = Duration::from_secs(300);
+
+/// How long snapshots of threads that are not watched are reused
+const UNWATCHED_TTL: Duration = Duration::from_secs(300);
+
+/// Most unwatched snapshots kept at once
+const MAX_UNWATCHED: usize = 1_000;
+
+/// The latest known state of a watched thread.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub thread: Arc<Thread>,
+    /// The last post is older than `poll_disable_after`, or the thread was
+    /// truncated; the watcher has stopped
+    pub stale: bool,
+}
+
+impl Snapshot {
+    fn new(thread: Thread, disable_after: u64) -> Self {
+        let stale = thread
+            .posts
+            .last()
+            .map(|p| {
+                Utc::now().signed_duration_since(p.created_at).num_seconds() >= disable_after as i64
+            })
+            .unwrap_or(true);
+        Self {
+            stale: stale || thread.truncated,
+            thread: Arc::new(thread),
+        }
+    }
+
+    /// Whether two snapshots differ in a way readers care about.
+    fn same_posts(&self, other: &Snapshot) -> bool {
+        self.thread.posts.len() == other.thread.posts.len()
+            && self
+                .thread
+                .posts
+                .iter()
+                .zip(&other.thread.posts)
+                .all(|(a, b)| a.cid == b.cid)
+    }
+}
+
+/// Threads are keyed by the handle and post ID readers use to open them.
+type WatchKey = (String, String);
+
+struct Watcher {
+    tx: watch::Sender<Snapshot>,
+    /// Last time a poller read the snapshot; pollers do not hold a receiver
+    last_read: Mutex<Instant>,
+    /// Triggers an immediate refresh when the live stream reports a change
+    wake: Notify,
+}
+
+impl Watcher {
+    fn touch(&self) {
+        *self.last_read.lock().unwrap() = Instant::now();
+    }
+
+    /// No SSE subscribers and no poller has read the snapshot for `idle_timeout`.
+    fn is_abandoned(&self, idle_timeout: Duration) -> bool {
+        self.tx.receiver_count() == 0 && self.last_read.lock().unwrap().elapsed() >= idle_timeout
+    }
+}
+
+/// Registry of running thread watchers, shared through `AppState`.
+#[derive(Clone)]
+pub struct WatcherRegistry {
+    /// Refreshes running watchers; unbudgeted and uncached, so diffs see
+    /// whole, current threads
+    client: BlueskyClient,
+    /// Fetches first snapshots, with the page budget and cache
+    pages: BlueskyClient,
+    config: SharedConfig,
+    /// Page cache, whose copy of a thread is dropped when the thread changes
+    cache: Option<Cache>,
+    watchers: Arc<Mutex<HashMap<WatchKey, Arc<Watcher>>>>,
+    /// Recent snapshots of stale or truncated threads, and when they were taken
+    unwatched: Arc<Mutex<HashMap<WatchKey, (Instant, Snapshot)>>>,
+    /// Signalled whenever a watcher starts or stops, so the set of watched authors changes
+    watched_changed: Arc<Notify>,
+    /// Whether the Jetstream consumer is connected; polling then only backs it up
+    live: Arc<AtomicBool>,
+}
+
+impl WatcherRegistry {
+    pub fn new(
+        client: BlueskyClient,
+        pages: BlueskyClient,
+        config: SharedConfig,
+        cache: Option<Cache>,
+    ) -> Self {
+        Self {
+            client,
+            pages,
+            config,
+            cache,
+            watchers: Arc::new(Mutex::new(HashMap::new())),
+            unwatched: Arc::new(Mutex::new(HashMap::new())),
+            watched_changed: Arc::new(Notify::new()),
+            live: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// DIDs of the authors of all watched threads.
+    pub fn watched_dids(&self) -> Vec<String> {
+        let watchers = self.watchers.lock().unwrap();
+        let dids: BTreeSet<String> = watchers
+            .values()
+            .map(|w| w.tx.borrow().thread.author.did.clone())
+            .collect();
+        dids.into_iter().collect()
+    }
+
+    /// Wait until a watcher starts or stops.
+    pub async fn watched_changed(&self) {
+        self.watched_changed.notified().await;
+    }
+
+    /// Record whether live updates are flowing from the Jetstream consumer.
+    pub fn set_live(&self, live: bool) {
+        self.live.store(live, Ordering::Relaxed);
+    }
+
+    /// Whether live updates are flowing from the Jetstream consumer.
+    pub fn is_live(&self) -> bool {
+        self.live.load(Ordering::Relaxed)
+    }
+
+    /// Refresh any watched thread a post event touches: a reply to one of its
+    /// posts by the thread author, or the deletion of one of its posts.
+    pub fn wake_for(&self, event: &PostEvent) {
+        let watchers = self.watchers.lock().unwrap();
+        for watcher in watchers.values() {
+            let relevant = {
+                let snapshot = watcher.tx.borrow();
+                let thread = &snapshot.thread;
+                match event {
+                    PostEvent::Created {
+                        did,
+                        parent_uri: Some(parent),
+                        ..
+                    } => did == &thread.author.did && thread.posts.iter().any(|p| &p.uri == parent),
+                    PostEvent::Created { .. } => false,
+                    PostEvent::Deleted { uri, .. } => thread.posts.iter().any(|p| &p.uri == uri),
+                }
+            };
+            if relevant {
+                watcher.wake.notify_one();
+            }
+        }
+    }
+
+    /// Subscribe to a thread, starting a watcher if none is running.
+    ///
+    /// The returned receiver already holds a snapshot. It is closed when the
+    /// thread goes stale or disappears upstream, and from the start for
+    /// threads that are not watched.
+    pub async fn subscribe(
+        &self,
+        handle: &str,
+        post_id: &str,
+    ) -> Result<watch::Receiver<Snapshot>, ClientError> {
+        let key = (handle.to_lowercase(), post_id.to_string());
+        if let Some(watcher) = self.watchers.lock().unwrap().get(&key) {
+            metrics().record_cache_lookup("watcher", true);
+            watcher.touch();
+            return Ok(watcher.tx.subscribe());
+        }
+        metrics().record_cache_lookup("watcher", false);
+        if let Some(snapshot) = self.unwatched_snapshot(&key) {
+            return Ok(watch::channel(snapshot).1);
+        }
+
+        let thread = self.pages.get_thread_by_handle(handle, post_id).await?;
+        let snapshot = Snapshot::new(thread, self.config.load().poll_disable_after);
+
+        let mut watchers = self.watchers.lock().unwrap();
+        // Another reader may have started a watcher while we were fetching
+        if let Some(watcher) = watchers.get(&key) {
+            watcher.touch();
+            return Ok(watcher.tx.subscribe());
+        }
+
+        if snapshot.stale {
+            // Nothing to watch; the closed channel still carries the snapshot
+            drop(watchers);
+            self.keep_unwatched(key, snapshot.clone());
+            return Ok(watch::channel(snapshot).1);
+        }
+        let (tx, rx) = watch::channel(snapshot);
+
+        let watcher = Arc::new(Watcher {
+            tx,
+            last_read: Mutex::new(Instant::now()),
+            wake: Notify::new(),
+        });
+        watchers.insert(key.clone(), watcher.clone());
+        drop(watchers);
+        self.watched_changed.notify_one();
+
+        info!(handle = %key.0, post_id = %key.1, "starting thread watcher");
+        tokio::spawn(self.clone().run(key, watcher));
+        Ok(rx)
+    }
+
+    fn unwatched_snapshot(&self, key: &WatchKey) -> Option<Snapshot> {
+        let unwatched = self.unwatched.lock().unwrap();
+        let (taken, snapshot) = unwatched.get(key)?;
+        (taken.elapsed() < UNWATCHED_TTL).then(|| snapshot.clone())
+    }
+
+    fn keep_unwatched(&self, key: WatchKey, snapshot: Snapshot) {
+        let mut unwatched = self.unwatched.lock().unwrap();
+        if unwatched.len() >= MAX_UNWATCHED {
+            unwatched.retain(|_, (taken, _)| taken.elapsed() < UNWATCHED_TTL);
+        }
+        if unwatched.len() >= MAX_UNWATCHED {
+            // Still full of fresh entries; make room by dropping the oldest
+            if let Some(oldest) = unwatched
+                .iter()
+                .min_by_key(|(_, (taken, _))| *taken)
+                .map(|(key, _)| key.clone())
+            {
+                unwatched.remove(&oldest);
+            }
+        }
+        unwatched.insert(key, (Instant::now(), snapshot));
+    }
+
+    /// Subscribe on behalf of a long-lived follower, retrying with backoff
+    /// while upstream is unavailable. Returns `None` once the thread is gone.
+    pub async fn subscribe_with_retry(
+        &self,
+        handle: &str,
+        post_id: &str,
+    ) -> Option<watch::Receiver<Snapshot>> {
+        let mut delay = Duration::from_secs(2);
+        loop {
+            match self.subscribe(handle, post_id).await {
+                Ok(rx) => return Some(rx),
+                Err(ClientError::NotFound | ClientError::Blocked) => return None,
+                Err(e) => {
+                    warn!(handle = %handle, post_id = %post_id, error = %e, "failed to subscribe to thread");
+                    tokio::time::sleep(delay).await;
+                    delay = (delay * 2).min(MAX_RESUBSCRIBE_DELAY);
+                }
+            }
+        }
+    }
+
+    /// Number of threads being watched.
+    pub fn active_count(&self) -> usize {
+        self.watchers.lock().unwrap().len()
+    }
+
+    /// Read the snapshot of a thread only if a watcher is already running for it.
+    pub fn running_snapshot(&self, handle: &str, post_id: &str) -> Option<Snapshot> {
+        let key = (handle.to_lowercase(), post_id.to_string());
+        let watchers = self.watchers.lock().unwrap();
+        let watcher = watchers.get(&key);
+        metrics().record_cache_lookup("watcher", watcher.is_some());
+        let watcher = watcher?;
+        watcher.touch();
+        let snapshot = watcher.tx.borrow().clone();
+        Some(snapshot)
+    }
+
+    /// Read the current snapshot of a thread without holding a subscription.
+    /// Keeps the watcher alive for pollers between their requests.
+    pub async fn snapshot(&self, handle: &str, post_id: &str) -> Result<Snapshot, ClientError> {
+        let rx = self.subscribe(handle, post_id).await?;
+        let snapshot = rx.borrow().clone();
+        Ok(snapshot)
+    }
+
+    /// Re-fetch a thread from the posts already known: refresh them, drop
+    /// deleted ones and follow the chain forward from the last survivor.
+    /// Falls back to fetching it like a page when none of them exist any more.
+    async fn refresh(
+        &self,
+        current: &Thread,
+        handle: &str,
+        post_id: &str,
+    ) -> Result<Thread, ClientError> {
+        let known: Vec<String> = current.posts.iter().map(|p| p.uri.clone()).collect();
+        match self.client.refresh_thread(&known).await? {
+            Some(posts) => Ok(Thread::new(posts, current.author.clone())),
+            None => self.pages.get_thread_by_handle(handle, post_id).await,
+        }
+    }
+
+    async fn run(self, key: WatchKey, watcher: Arc<Watcher>) {
+        let (handle, post_id) = (&key.0, &key.1);
+        let mut interval = Duration::from_secs(self.config.load().poll_initial_interval);
+
+        loop {
+            // Read each time round, so reloaded intervals apply to running watchers
+            let (initial_interval, max_interval) = {
+                let config = self.config.load();
+                (
+                    Duration::from_secs(config.poll_initial_interval),
+                    Duration::from_secs(config.poll_max_interval),
+                )
+            };
+            // Pollers come back at most every max_interval, so allow them one missed poll
+            let idle_timeout = max_interval * 2;
+            interval = interval.min(max_interval);
+
+            // With live updates flowing, polling is only a safety net
+            let delay = if self.live.load(Ordering::Relaxed) {
+                max_interval
+            } else {
+                interval
+            };
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = watcher.wake.notified() => {
+                    debug!(handle = %handle, post_id = %post_id, "woken by live update");
+                }
+            }
+
+            if watcher.is_abandoned(idle_timeout) {
+                debug!(handle = %handle, post_id = %post_id, "no readers left");
+                break;
+            }
+
+            let current = watcher.tx.borrow().thread.clone();
+            let thread = match self.refresh(&current, handle, post_id).await {
+                Ok(thread) => thread,
+                Err(ClientError::NotFound | ClientError::Blocked) => {
+                    debug!(handle = %handle, post_id = %post_id, "watched thread is gone");
+                    break;
+                }
+                Err(e) => {
+                    warn!(error = %e, "failed to refresh watched thread");
+                    interval = (interval * 2).min(max_interval);
+                    continue;
+                }
+            };
+
+            let snapshot = Snapshot::new(thread, self.config.load().poll_disable_after);
+            let changed = !watcher.tx.borrow().same_posts(&snapshot);
+            let stale = snapshot.stale;
+
+            if changed || stale {
+                watcher.tx.send_replace(snapshot);
+            }
+            if changed {
+                if let Some(cache) = &self.cache {
+                    cache.invalidate_thread(handle, post_id).await;
+                }
+            }
+            if stale {
+                break;
+            }
+            interval = if changed {
+                initial_interval
+            } else {
+                interval.mul_f64(1.5).min(max_interval)
+            };
+        }
+
+        let mut watchers = self.watchers.lock().unwrap();
+        if watchers
+            .get(&key)
+            .is_some_and(|current| Arc::ptr_eq(current, &watcher))
+        {
+            watchers.remove(&key);
+            self.watched_changed.notify_one();
+        }
+        info!(handle = %handle, post_id = %post_id, "stopped thread watcher");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bluesky::types::{Author, ThreadPost};
+    use chrono::{DateTime, Duration as ChronoDuration};
+
+    fn thread(posts: &[(&str, DateTime<Utc>)]) -> Thread {
+        Thread {
+            posts: posts
+                .iter()
+                .map(|(cid, created_at)| ThreadPost {
+                    uri: format!("at://did:plc:abc/app.bsky.feed.post/{}", cid),
+                    cid: cid.to_string(),
+                    text: String::new(),
+                    created_at: *created_at,
+                    reply_count: None,
+                    repost_count: None,
+                    like_count: None,
+                    embed: None,
+                    langs: vec![],
+                })
+                .collect(),
+            author: Author {
+                did: "did:plc:abc".to_string(),
+                handle: "user.bsky.social".to_string(),
+                display_name: None,
+                avatar_url: None,
+            },
+            truncated: false,
+        }
+    }
+
+    #[test]
+    fn test_snapshot_staleness() {
+        let now = Utc::now();
+        let old = now - ChronoDuration::hours(1);
+
+        assert!(!Snapshot::new(thread(&[("a", old), ("b", now)]), 1800).stale);
+        assert!(Snapshot::new(thread(&[("a", old)]), 1800).stale);
+        assert!(Snapshot::new(thread(&[]), 1800).stale);
+    }
+
+    #[test]
+    fn test_snapshot_same_posts_compares_cids() {
+        let now = Utc::now();
+        let a = Snapshot::new(thread(&[("a", now), ("b", now)]), 1800);
+
+        assert!(a.same_posts(&Snapshot::new(thread(&[("a", now), ("b", now)]), 1800)));
+        assert!(!a.same_posts(&Snapshot::new(thread(&[("a", now)]), 1800)));
+        assert!(!a.same_posts(&Snapshot::new(thread(&[("a", now), ("c", now)]), 1800)));
+    }
+
+    #[tokio::test]
+    async fn test_unwatched_snapshots_are_reused() {
+        // Unreachable upstream: subscribing can only succeed from the kept snapshot
+        let client = BlueskyClient::new("http://127.0.0.1:1", Duration::from_secs(1)).unwrap();
+        let config: SharedConfig = Arc::new(arc_swap::ArcSwap::from_pointee(
+            crate::config::Config::default(),
+        ));
+        let registry = WatcherRegistry::new(client.clone(), client, config, None);
+
+        let mut truncated = thread(&[("a", Utc::now())]);
+        truncated.truncated = true;
+        let snapshot = Snapshot::new(truncated, 1800);
+        assert!(snapshot.stale);
+
+        let key = ("user.bsky.social".to_string(), "a".to_string());
+        registry.keep_unwatched(key, snapshot);
+        let rx = registry.subscribe("User.bsky.social", "a").await.unwrap();
+        assert_eq!(rx.borrow().thread.posts.len(), 1);
+        assert_eq!(registry.active_count(), 0);
+        assert!(registry.subscribe("user.bsky.social", "b").await.is_err());
+    }
+}
@@ -0,0 +1,623 @@
+//! Outbound webhooks for followed threads.
+//!
+//! A subscription pairs a live thread with a callback URL. Each one holds a
+//! subscription to the shared thread watcher and turns its new-post events
+//! (the same diff the updates API and SSE stream use) into JSON POSTs signed
+//! with HMAC-SHA256 over the shared secret. Subscriptions are kept in a JSON
+//! file so they survive restarts, and end on their own once the thread goes
+//! stale or disappears upstream.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use thiserror::Error;
+use tokio::sync::watch;
+use tokio::task::AbortHandle;
+use tracing::{debug, info, warn};
+
+use crate::bluesky::client::ClientError;
+use crate::bluesky::types::{Author, ThreadPost};
+use crate::config::Config;
+use crate::events::{new_post_batches, FollowCursor};
+use crate::store::{JsonStore, Keyed, StoreError};
+use crate::watcher::{Snapshot, WatcherRegistry};
+
+/// Delivery attempts per notification before it is dropped
+const MAX_ATTEMPTS: u32 = 5;
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+pub const SIGNATURE_HEADER: &str = "X-Sklonger-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Sklonger-Timestamp";
+
+#[derive(Error, Debug)]
+pub enum WebhookError {
+    #[error(transparent)]
+    Store(#[from] StoreError),
+    #[error("failed to encode payload: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("failed to fetch thread: {0}")]
+    Client(#[from] ClientError),
+    #[error("thread is no longer live")]
+    Stale,
+    #[error("callback request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("callback returned {0}")]
+    Status(reqwest::StatusCode),
+}
+
+/// A callback registered for a thread.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Subscription {
+    pub id: String,
+    pub handle: String,
+    pub post_id: String,
+    pub callback_url: String,
+    pub created_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub cursor: FollowCursor,
+}
+
+impl Keyed for Subscription {
+    fn key(&self) -> &str {
+        &self.id
+    }
+}
+
+/// Hex-encoded HMAC-SHA256 of `message`.
+fn hmac_hex(secret: &str, message: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(message);
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Signature sent in [`SIGNATURE_HEADER`]: the HMAC of `"{timestamp}.{body}"`,
+/// so receivers can reject replayed deliveries by their timestamp.
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut message = format!("{}.", timestamp).into_bytes();
+    message.extend_from_slice(body);
+    format!("sha256={}", hmac_hex(secret, &message))
+}
+
+/// Compare secrets without short-circuiting on the first differing byte.
+pub fn secrets_match(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+fn post_json(post: &ThreadPost, author: &Author, public_url: &str) -> serde_json::Value {
+    let rkey = post.uri.rsplit('/').next().unwrap_or("");
+    serde_json::json!({
+        "uri": post.uri,
+        "cid": post.cid,
+        "text": post.text,
+        "created_at": post.created_at.to_rfc3339(),
+        "url": format!("https://bsky.app/profile/{}/post/{}", author.handle, rkey),
+        "thread_url": format!("{}/profile/{}/post/{}", public_url, author.handle, rkey),
+    })
+}
+
+/// Outbound webhook subscriptions, shared through `AppState`.
+#[derive(Clone)]
+pub struct Webhooks {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    store: JsonStore<Subscription>,
+    watchers: WatcherRegistry,
+    http: reqwest::Client,
+    /// Wait before the first retry, doubling for each one after
+    retry_delay: Duration,
+    secret: String,
+    public_url: String,
+    /// Delivery task per subscription ID
+    tasks: Mutex<HashMap<String, AbortHandle>>,
+}
+
+impl Webhooks {
+    pub fn new(config: &Config, watchers: WatcherRegistry) -> Result<Self, WebhookError> {
+        // Deliveries are signed; following a redirect would hand them to
+        // whichever host the subscriber points at
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.request_timeout_seconds))
+            .user_agent(concat!("sklonger/", env!("CARGO_PKG_VERSION")))
+            .redirect(reqwest::redirect::Policy::none())
+            .build()?;
+
+        Ok(Self {
+            inner: Arc::new(Inner {
+                store: JsonStore::load(&config.webhooks_path)?,
+                watchers,
+                http,
+                retry_delay: INITIAL_RETRY_DELAY,
+                secret: config.webhook_secret.clone(),
+                public_url: config.public_url.clone(),
+                tasks: Mutex::new(HashMap::new()),
+            }),
+        })
+    }
+
+    /// Resume delivery for subscriptions loaded from the store.
+    pub fn start(&self) {
+        let subscriptions = self.inner.store.all();
+        info!(
+            count = subscriptions.len(),
+            "resuming webhook subscriptions"
+        );
+        for subscription in subscriptions {
+            self.spawn(subscription, None);
+        }
+    }
+
+    /// Write pending subscription changes to disk.
+    pub async fn flush(&self) {
+        self.inner.store.flush().await;
+    }
+
+    /// Whether a bearer token grants access to the subscription API.
+    pub fn authorizes(&self, token: &str) -> bool {
+        secrets_match(token, &self.inner.secret)
+    }
+
+    pub fn list(&self) -> Vec<Subscription> {
+        self.inner.store.all()
+    }
+
+    /// Register a callback for a thread. Only posts added after this call are
+    /// delivered.
+    pub async fn subscribe(
+        &self,
+        handle: &str,
+        post_id: &str,
+        callback_url: &str,
+    ) -> Result<Subscription, WebhookError> {
+        let rx = self.inner.watchers.subscribe(handle, post_id).await?;
+        let snapshot = rx.borrow().clone();
+        if snapshot.stale {
+            return Err(WebhookError::Stale);
+        }
+
+        let created_at = Utc::now();
+        let id_source = format!(
+            "{}/{}/{}/{}",
+            handle,
+            post_id,
+            callback_url,
+            created_at.timestamp_nanos_opt().unwrap_or_default()
+        );
+        let subscription = Subscription {
+            id: hmac_hex(&self.inner.secret, id_source.as_bytes())[..16].to_string(),
+            handle: handle.to_string(),
+            post_id: post_id.to_string(),
+            callback_url: callback_url.to_string(),
+            created_at,
+            cursor: FollowCursor::at_end(&snapshot.thread.posts),
+        };
+
+        self.inner.store.insert(subscription.clone());
+        info!(id = %subscription.id, handle = %handle, post_id = %post_id, "webhook subscribed");
+        self.spawn(subscription.clone(), Some(rx));
+        Ok(subscription)
+    }
+
+    /// Remove a subscription. Returns whether it existed.
+    pub fn unsubscribe(&self, id: &str) -> bool {
+        if let Some(task) = self.inner.tasks.lock().unwrap().remove(id) {
+            task.abort();
+        }
+        self.inner.store.remove(id).is_some()
+    }
+
+    fn spawn(&self, subscription: Subscription, rx: Option<watch::Receiver<Snapshot>>) {
+        // Hold the lock while spawning so a task that ends at once cannot
+        // remove its entry before it is inserted
+        let mut tasks = self.inner.tasks.lock().unwrap();
+        let id = subscription.id.clone();
+        let task = tokio::spawn(self.clone().deliver(subscription, rx));
+        tasks.insert(id, task.abort_handle());
+    }
+
+    /// Follow the thread and deliver its new posts until it goes stale or
+    /// disappears, then notify the callback and drop the subscription.
+    async fn deliver(self, mut subscription: Subscription, rx: Option<watch::Receiver<Snapshot>>) {
+        let rx = match rx {
+            Some(rx) => Some(rx),
+            None => {
+                self.inner
+                    .watchers
+                    .subscribe_with_retry(&subscription.handle, &subscription.post_id)
+                    .await
+            }
+        };
+
+        let reason = match rx {
+            Some(rx) => {
+                let author = rx.borrow().thread.author.clone();
+                let stale = self.forward(&mut subscription, &author, rx).await;
+                if stale {
+                    "stale"
+                } else {
+                    "gone"
+                }
+            }
+            None => "gone",
+        };
+
+        let payload = serde_json::json!({
+            "event": "thread.ended",
+            "subscription_id": subscription.id,
+            "reason": reason,
+        });
+        if let Err(e) = self.send(&subscription, &payload).await {
+            warn!(id = %subscription.id, error = %e, "failed to deliver webhook");
+        }
+
+        info!(id = %subscription.id, reason = reason, "webhook subscription expired");
+        self.inner.tasks.lock().unwrap().remove(&subscription.id);
+        self.inner.store.remove(&subscription.id);
+    }
+
+    /// Deliver new posts as they appear. Returns whether the thread went stale
+    /// (as opposed to the watcher stopping because the thread is gone).
+    async fn forward(
+        &self,
+        subscription: &mut Subscription,
+        author: &Author,
+        rx: watch::Receiver<Snapshot>,
+    ) -> bool {
+        let batches = new_post_batches(rx, subscription.cursor.clone());
+        futures::pin_mut!(batches);
+
+        while let Some(batch) = batches.next().await {
+            if !batch.posts.is_empty() {
+                let payload = serde_json::json!({
+                    "event": "thread.new_posts",
+                    "subscription_id": subscription.id,
+                    "author": { "did": author.did, "handle": author.handle },
+                    "posts": batch
+                        .posts
+                        .iter()
+                        .map(|p| post_json(p, author, &self.inner.public_url))
+                        .collect::<Vec<_>>(),
+                });
+                match self.send(subscription, &payload).await {
+                    Ok(()) => {
+                        debug!(id = %subscription.id, count = batch.posts.len(), "webhook delivered")
+                    }
+                    Err(e) => warn!(id = %subscription.id, error = %e, "dropping webhook delivery"),
+                }
+
+                subscription.cursor.advance(&batch.posts);
+                self.inner.store.replace(subscription.clone());
+            }
+
+            if batch.stale {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// POST a signed payload, retrying with exponential backoff.
+    async fn send(
+        &self,
+        subscription: &Subscription,
+        payload: &serde_json::Value,
+    ) -> Result<(), WebhookError> {
+        let body = serde_json::to_vec(payload)?;
+        let mut delay = self.inner.retry_delay;
+
+        for attempt in 1.. {
+            match self.send_once(subscription, &body).await {
+                Ok(()) => return Ok(()),
+                Err(e) if attempt < MAX_ATTEMPTS => {
+                    debug!(id = %subscription.id, attempt, error = %e, "retrying webhook delivery");
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        unreachable!("the final attempt returns")
+    }
+
+    async fn send_once(
+        &self,
+        subscription: &Subscription,
+        body: &[u8],
+    ) -> Result<(), WebhookError> {
+        let timestamp = Utc::now().timestamp();
+        let response = self
+            .inner
+            .http
+            .post(&subscription.callback_url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .header(SIGNATURE_HEADER, sign(&self.inner.secret, timestamp, body))
+            .body(body.to_vec())
+            .send()
+            .await?;
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(WebhookError::Status(response.status()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    use axum::{
+        body::Bytes,
+        http::{HeaderMap, StatusCode},
+        routing::post,
+    };
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+
+    use crate::bluesky::client::BlueskyClient;
+    use crate::bluesky::types::Thread;
+    use crate::config::SharedConfig;
+
+    const SECRET: &str = "s3cret";
+
+    fn thread_post(id: &str) -> ThreadPost {
+        ThreadPost {
+            uri: format!("at://did:plc:abc/app.bsky.feed.post/{}", id),
+            cid: id.to_string(),
+            text: format!("post {}", id),
+            // Later ids are later posts, whichever snapshot they appear in
+            created_at: DateTime::from_timestamp(1_700_000_000 + id.as_bytes()[0] as i64, 0)
+                .unwrap(),
+            reply_count: None,
+            repost_count: None,
+            like_count: None,
+            embed: None,
+            langs: vec![],
+        }
+    }
+
+    fn snapshot(ids: &[&str], stale: bool) -> Snapshot {
+        Snapshot {
+            thread: Arc::new(Thread::new(
+                ids.iter().map(|id| thread_post(id)).collect(),
+                Author {
+                    did: "did:plc:abc".to_string(),
+                    handle: "user.bsky.social".to_string(),
+                    display_name: None,
+                    avatar_url: None,
+                },
+            )),
+            stale,
+        }
+    }
+
+    fn webhooks(path: &std::path::Path) -> Webhooks {
+        let config = Config {
+            webhook_secret: SECRET.to_string(),
+            webhooks_path: path.to_string_lossy().into_owned(),
+            ..Config::default()
+        };
+        // Deliveries here never need upstream
+        let client = BlueskyClient::new("http://127.0.0.1:1", Duration::from_secs(1)).unwrap();
+        let shared: SharedConfig = Arc::new(arc_swap::ArcSwap::from_pointee(config.clone()));
+        let watchers = WatcherRegistry::new(client.clone(), client, shared, None);
+        let mut webhooks = Webhooks::new(&config, watchers).unwrap();
+        Arc::get_mut(&mut webhooks.inner).unwrap().retry_delay = Duration::from_millis(20);
+        webhooks
+    }
+
+    fn store_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!(
+            "sklonger-webhooks-{}-{}-{:?}.json",
+            name,
+            std::process::id(),
+            std::time::SystemTime::now()
+        ))
+    }
+
+    /// Subscribe `callback_url` to a thread driven by the returned sender.
+    fn follow(webhooks: &Webhooks, callback_url: &str) -> (Subscription, watch::Sender<Snapshot>) {
+        let (tx, rx) = watch::channel(snapshot(&["a"], false));
+        let subscription = Subscription {
+            id: "sub1".to_string(),
+            handle: "user.bsky.social".to_string(),
+            post_id: "a".to_string(),
+            callback_url: callback_url.to_string(),
+            created_at: Utc::now(),
+            cursor: FollowCursor::at_end(&rx.borrow().thread.posts),
+        };
+        webhooks.inner.store.insert(subscription.clone());
+        webhooks.spawn(subscription.clone(), Some(rx));
+        (subscription, tx)
+    }
+
+    /// A stand-in receiver that fails the first `failures` deliveries and
+    /// reports every attempt.
+    async fn receiver(
+        failures: usize,
+    ) -> (String, mpsc::UnboundedReceiver<(Instant, HeaderMap, Bytes)>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let remaining = Arc::new(Mutex::new(failures));
+        let app = axum::Router::new().route(
+            "/hook",
+            post(move |headers: HeaderMap, body: Bytes| async move {
+                tx.send((Instant::now(), headers, body)).unwrap();
+                let mut remaining = remaining.lock().unwrap();
+                if *remaining > 0 {
+                    *remaining -= 1;
+                    StatusCode::SERVICE_UNAVAILABLE
+                } else {
+                    StatusCode::NO_CONTENT
+                }
+            }),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{}/hook", addr), rx)
+    }
+
+    fn payload(body: &Bytes) -> serde_json::Value {
+        serde_json::from_slice(body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_delivers_signed_posts_until_stale() {
+        let path = store_path("deliver");
+        let webhooks = webhooks(&path);
+        let (url, mut deliveries) = receiver(0).await;
+        let (subscription, thread) = follow(&webhooks, &url);
+
+        thread.send(snapshot(&["a", "b", "c"], false)).unwrap();
+        let (_, headers, body) = deliveries.recv().await.unwrap();
+        let timestamp: i64 = headers[TIMESTAMP_HEADER].to_str().unwrap().parse().unwrap();
+        assert_eq!(headers[SIGNATURE_HEADER], sign(SECRET, timestamp, &body));
+        let delivered = payload(&body);
+        assert_eq!(delivered["event"], "thread.new_posts");
+        assert_eq!(delivered["subscription_id"], subscription.id);
+        let cids: Vec<_> = delivered["posts"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| p["cid"].as_str().unwrap())
+            .collect();
+        assert_eq!(cids, ["b", "c"]);
+
+        // A stale thread ends the subscription
+        thread.send(snapshot(&["a", "b", "c"], true)).unwrap();
+        let (_, _, body) = deliveries.recv().await.unwrap();
+        let ended = payload(&body);
+        assert_eq!(ended["event"], "thread.ended");
+        assert_eq!(ended["reason"], "stale");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(webhooks.list().is_empty());
+        assert!(webhooks.inner.tasks.lock().unwrap().is_empty());
+
+        webhooks.flush().await;
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_failed_deliveries_are_retried_with_backoff() {
+        let path = store_path("retry");
+        let webhooks = webhooks(&path);
+        let (url, mut deliveries) = receiver(2).await;
+        let (_, thread) = follow(&webhooks, &url);
+
+        thread.send(snapshot(&["a", "b"], false)).unwrap();
+        let mut attempts = Vec::new();
+        for _ in 0..3 {
+            let (at, _, body) = deliveries.recv().await.unwrap();
+            assert_eq!(payload(&body)["event"], "thread.new_posts");
+            attempts.push(at);
+        }
+        assert!(attempts[1] - attempts[0] >= Duration::from_millis(20));
+        assert!(attempts[2] - attempts[1] >= Duration::from_millis(40));
+
+        // Delivered on the third attempt, so nothing more is sent
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(deliveries.try_recv().is_err());
+
+        webhooks.flush().await;
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_redirects_are_not_followed() {
+        let (target, mut redirected) = receiver(0).await;
+        let app = axum::Router::new().route(
+            "/hook",
+            post(move || async move { axum::response::Redirect::temporary(&target) }),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let path = store_path("redirect");
+        let webhooks = webhooks(&path);
+        let subscription = Subscription {
+            id: "sub1".to_string(),
+            handle: "user.bsky.social".to_string(),
+            post_id: "a".to_string(),
+            callback_url: format!("http://{}/hook", addr),
+            created_at: Utc::now(),
+            cursor: FollowCursor::default(),
+        };
+        assert!(matches!(
+            webhooks
+                .send(&subscription, &serde_json::json!({ "event": "test" }))
+                .await,
+            Err(WebhookError::Status(status)) if status.is_redirection()
+        ));
+        assert!(redirected.try_recv().is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_subscriptions_survive_restart() {
+        let path = store_path("restart");
+        let webhooks = webhooks(&path);
+        let (url, mut deliveries) = receiver(0).await;
+        let (subscription, thread) = follow(&webhooks, &url);
+
+        thread.send(snapshot(&["a", "b"], false)).unwrap();
+        deliveries.recv().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        webhooks.flush().await;
+        // Stop delivering without removing the subscription, as a shutdown would
+        let task = webhooks
+            .inner
+            .tasks
+            .lock()
+            .unwrap()
+            .remove(&subscription.id);
+        task.unwrap().abort();
+
+        // The restarted server resumes after the last delivered post
+        let restarted = self::webhooks(&path);
+        let resumed = restarted.list();
+        assert_eq!(resumed.len(), 1);
+        assert_eq!(resumed[0].id, subscription.id);
+        assert_eq!(resumed[0].callback_url, url);
+        assert_eq!(resumed[0].cursor.last_cid.as_deref(), Some("b"));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_hmac_matches_rfc4231_vector() {
+        assert_eq!(
+            hmac_hex("Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_eq!(
+            sign("Jefe", 1700000000, b"{}"),
+            format!("sha256={}", hmac_hex("Jefe", b"1700000000.{}"))
+        );
+    }
+
+    #[test]
+    fn test_secrets_match() {
+        assert!(secrets_match("s3cret", "s3cret"));
+        assert!(!secrets_match("s3cret", "s3creT"));
+        assert!(!secrets_match("s3cret", "s3cre"));
+    }
+}